@@ -0,0 +1,90 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+type Handler<Args> = Box<dyn FnMut(&Args) + Send + 'static>;
+
+/// A set of callbacks that are invoked within the `enter` context of an owner.
+///
+/// Handlers typically capture weak references to the owner's value. They may
+/// be registered from anywhere, via any clone of the set. Passing the set to
+/// the `dispatch` method of the owner enters it once and invokes all handlers,
+/// so that every captured weak reference of that owner returns `Some`.
+///
+/// Handlers must be dropped within the `enter` context of the owner whose weak
+/// references they capture. Either keep the set within the owned value or
+/// [`clear`][CallbackSet::clear()] it while entered.
+pub struct CallbackSet<Args> {
+    handlers: Arc<Mutex<Vec<Handler<Args>>>>,
+}
+
+impl<Args> CallbackSet<Args> {
+    /// Creates a new, empty `CallbackSet`.
+    pub fn new() -> Self {
+        Self {
+            handlers: Default::default(),
+        }
+    }
+
+    /// Registers `handler` to be invoked by every subsequent dispatch.
+    pub fn register<F>(&self, handler: F)
+    where
+        F: FnMut(&Args) + Send + 'static,
+    {
+        self.lock().push(Box::new(handler));
+    }
+
+    /// Returns the number of registered handlers.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no handlers are registered.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Removes all handlers, dropping them.
+    pub fn clear(&self) {
+        let handlers = std::mem::take(&mut *self.lock());
+        drop(handlers);
+    }
+
+    /// Invokes all handlers in the order they were registered.
+    ///
+    /// The lock is not held while handlers are running, i.e., they may register
+    /// further handlers, which are first invoked by the next dispatch. Should a
+    /// handler panic, all handlers remain registered.
+    #[doc(hidden)]
+    pub fn invoke(&self, args: &Args) {
+        let mut handlers = scopeguard::guard(std::mem::take(&mut *self.lock()), |mut handlers| {
+            let mut registered = self.lock();
+            handlers.append(&mut registered);
+            *registered = handlers;
+        });
+
+        for handler in handlers.iter_mut() {
+            handler(args);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Handler<Args>>> {
+        self.handlers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<Args> Clone for CallbackSet<Args> {
+    fn clone(&self) -> Self {
+        Self {
+            handlers: Arc::clone(&self.handlers),
+        }
+    }
+}
+
+impl<Args> Default for CallbackSet<Args> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
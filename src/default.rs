@@ -7,433 +7,3629 @@ pub use scopeguard;
 ///  - `$unsend`, an unsendable owning/strong reference, and
 ///  - `$ref`, a sendable weak reference.
 ///
+/// Optionally, two more parameters may be given after a `=>`, i.e.,
+/// `snarc!(Snarc, Narc, SnarcRef => ErasedSnarc, ErasedNarc)`. They name
+/// type-erased counterparts to `$send` and `$unsend`, which, unlike the crate's
+/// [`ErasedSnarc`](crate::ErasedSnarc) and [`ErasedNarc`](crate::ErasedNarc),
+/// only accept structs defined by the same invocation.
+///
+/// Several such families of structs may be defined by a single invocation by
+/// separating them with semicolons, e.g.,
+/// `snarc! { SessionSnarc, SessionNarc, SessionRef; MetricsSnarc, MetricsNarc, MetricsRef }`.
+/// Each family has a context of its own.
+///
 /// Note that this macro declares a `thread_local!` that is is shared by all
-/// instances. That means that instances cannot bind their value to the current
-/// thread at the same time. If this is too restrictive, enable the
-/// `thread-local` feature and use the structs defined in the `thread_local`
-/// module.
+/// instances of a family. Instances may be entered in a nested fashion, but
+/// while any one of them is entered, weak references to all of them resolve.
+/// If this is too permissive, prefix the parameters with `per_instance;`, i.e.,
+/// `snarc!(per_instance; Snarc, Narc, SnarcRef)`. The structs so defined store
+/// the binding in their allocation instead, which gives them the semantics of
+/// the structs defined in the `thread_local` module.
+///
+/// Zero-sized values are not allocated for, unless `per_instance;` is given.
+/// Without a control block, they are not tracked: references are not counted
+/// and never dangle, `defer`red tasks are dropped, `when_entered` resolves
+/// right away and `send` returns the message. Use `per_instance;` for values
+/// that rely on these.
+///
+/// With the `zeroize` feature, the bytes of values are overwritten with zeros
+/// once they are dropped or moved out, e.g., by `try_unwrap` or `map`. Memory
+/// owned by the values, e.g., the buffer of a `Vec`, is not affected, wrap
+/// them in `zeroize::Zeroizing` to that end.
+///
+/// The structs are defined in a module named `_snarc_impl` and re-exported, so
+/// a second invocation within the same module must name a module of its own,
+/// e.g., `snarc!(mod other_snarc_impl; OtherSnarc, OtherNarc, OtherSnarcRef)`,
+/// followed by `per_instance;` if need be.
+///
+/// Forwarding implementations of traits may be requested per family by a
+/// derive attribute, e.g., `snarc!(#[derive(Debug)] Snarc, Narc, SnarcRef)`.
+/// The owners forward to their value, the weak reference only does so within
+/// the `enter` context. Supported traits are `Debug` and, given the `serde`
+/// feature, `Serialize`.
 #[macro_export]
 macro_rules! snarc {
-    ($send:ident, $unsend:ident, $ref:ident $(, $expect:literal)?) => {
-        pub use _snarc_impl::$send;
-        pub use _snarc_impl::$unsend;
-        pub use _snarc_impl::$ref;
-
-        mod _snarc_impl {
-            use std::alloc;
-            use std::ops::Deref;
-            use std::ops::DerefMut;
-            use std::ptr;
+    (mod $module:ident; per_instance; $(
+        $(#[derive($($derive:ident),* $(,)?)])?
+        $send:ident, $unsend:ident, $ref:ident $(, $expect:literal)?
+        $(=> $erased_send:ident, $erased_unsend:ident)?
+    );+ $(;)?) => {
+        $crate::snarc!(@families $module per_instance; $(
+            [$($($derive),*)?]
+            $send, $unsend, $ref $(, $expect)? $(=> $erased_send, $erased_unsend)?
+        );+);
+    };
+    (mod $module:ident; $(
+        $(#[derive($($derive:ident),* $(,)?)])?
+        $send:ident, $unsend:ident, $ref:ident $(, $expect:literal)?
+        $(=> $erased_send:ident, $erased_unsend:ident)?
+    );+ $(;)?) => {
+        $crate::snarc!(@families $module shared; $(
+            [$($($derive),*)?]
+            $send, $unsend, $ref $(, $expect)? $(=> $erased_send, $erased_unsend)?
+        );+);
+    };
+    (per_instance; $(
+        $(#[derive($($derive:ident),* $(,)?)])?
+        $send:ident, $unsend:ident, $ref:ident $(, $expect:literal)?
+        $(=> $erased_send:ident, $erased_unsend:ident)?
+    );+ $(;)?) => {
+        $crate::snarc!(@families _snarc_impl per_instance; $(
+            [$($($derive),*)?]
+            $send, $unsend, $ref $(, $expect)? $(=> $erased_send, $erased_unsend)?
+        );+);
+    };
+    ($(
+        $(#[derive($($derive:ident),* $(,)?)])?
+        $send:ident, $unsend:ident, $ref:ident $(, $expect:literal)?
+        $(=> $erased_send:ident, $erased_unsend:ident)?
+    );+ $(;)?) => {
+        $crate::snarc!(@families _snarc_impl shared; $(
+            [$($($derive),*)?]
+            $send, $unsend, $ref $(, $expect)? $(=> $erased_send, $erased_unsend)?
+        );+);
+    };
+    (@families $module:ident $context:ident; $(
+        [$($derive:ident),*]
+        $send:ident, $unsend:ident, $ref:ident $(, $expect:literal)?
+        $(=> $erased_send:ident, $erased_unsend:ident)?
+    );+) => {
+        $(
+            pub use $module::$send::$send;
+            pub use $module::$send::$unsend;
+            pub use $module::$send::$ref;
+            $(
+                pub use $module::$send::$erased_send;
+                pub use $module::$send::$erased_unsend;
+            )?
+        )+
+
+        mod $module {
+            $(
+                $crate::snarc!(@define $context;
+                    $send, $unsend, $ref $(, $expect)? $(=> $erased_send, $erased_unsend)?
+                );
+                $(
+                    $crate::__derive!($derive; $send, $unsend, $ref);
+                )*
+            )+
+        }
+    };
+    (@context shared) => {
+        thread_local!(static THREAD_LOCAL: std::cell::Cell<State> = Default::default());
+
+        // the guards created by `borrow` and `get_mut` on this thread
+        thread_local!(static BORROWS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) });
+
+        // the instances entered on this thread, innermost last
+        thread_local!(static ENTERED: std::cell::RefCell<Vec<*mut ()>> =
+            const { std::cell::RefCell::new(Vec::new()) });
+
+        #[repr(C)]
+        struct SnarcBox<T> {
+            head: $crate::Canary,
+            count: std::cell::Cell<$crate::Count>,
+            balance: $crate::Balance,
+            generation: std::sync::atomic::AtomicU32,
+            dropped: std::sync::atomic::AtomicBool,
+            holder: $crate::Holder,
+            borrows: $crate::BorrowFlag,
+            snapshots: $crate::SnapshotLock,
+            notifier: $crate::Notifier,
+            deferred: $crate::DeferQueue,
+            mailbox: $crate::Mailbox,
+            value: T,
+            tail: $crate::Canary,
+        }
 
-            use $crate::Context;
-            use $crate::ErasedNarc;
-            use $crate::ErasedSnarc;
-            use $crate::State;
+        impl<T> SnarcBox<T> {
+            /// Zero-sized values need neither an allocation nor a count, their
+            /// pointers dangle instead.
+            const ALLOCATES: bool = std::mem::size_of::<T>() != 0;
 
-            thread_local!(static THREAD_LOCAL: std::cell::Cell<State> = Default::default());
+            fn new_ptr(value: T) -> *mut Self {
+                if !Self::ALLOCATES {
+                    std::mem::forget(value);
+                    return ptr::NonNull::dangling().as_ptr();
+                }
 
-            struct SnarcBox<T> {
-                count: std::cell::Cell<usize>,
-                value: T,
+                Box::leak(Box::new(Self {
+                    head: $crate::Canary::head(),
+                    count: std::cell::Cell::new(0),
+                    balance: Default::default(),
+                    generation: std::sync::atomic::AtomicU32::new(0),
+                    dropped: std::sync::atomic::AtomicBool::new(false),
+                    holder: Default::default(),
+                    borrows: Default::default(),
+                    snapshots: Default::default(),
+                    notifier: Default::default(),
+                    deferred: Default::default(),
+                    mailbox: Default::default(),
+                    value,
+                    tail: $crate::Canary::tail(),
+                }))
             }
 
-            impl<T> SnarcBox<T> {
-                fn new_ptr(value: T) -> *mut Self {
-                    Box::leak(Box::new(Self {
-                        count: std::cell::Cell::new(0),
-                        value,
-                    }))
-                }
+            /// Allocates a control block whose value is written later, by
+            /// `init`.
+            fn new_uninit_ptr() -> *mut Self {
+                Self::try_new_uninit_ptr()
+                    .unwrap_or_else(|_| alloc::handle_alloc_error(alloc::Layout::new::<Self>()))
             }
 
-            pub struct $send<T> {
-                ptr: *mut SnarcBox<T>,
-                phantom: std::marker::PhantomData<SnarcBox<T>>,
-            }
+            /// Like `new_uninit_ptr`, but fails instead of aborting if memory
+            /// cannot be allocated.
+            fn try_new_uninit_ptr() -> Result<*mut Self, $crate::AllocError> {
+                if !Self::ALLOCATES {
+                    return Ok(ptr::NonNull::dangling().as_ptr());
+                }
 
-            unsafe impl<T: Send> Send for $send<T> {}
-            unsafe impl<T: Sync> Sync for $send<T> {}
+                let this = unsafe { alloc::alloc(alloc::Layout::new::<Self>()) }.cast::<Self>();
+                if this.is_null() {
+                    return Err($crate::AllocError);
+                }
 
-            impl<T> $send<T> {
-                /// Creates a new `
-                #[doc = stringify!($send)]
-                /// ` with the given inner `value`.
-                pub fn new(value: T) -> Self {
-                    Self {
-                        ptr: SnarcBox::new_ptr(value),
-                        phantom: std::marker::PhantomData,
-                    }
+                unsafe {
+                    ptr::addr_of_mut!((*this).head).write($crate::Canary::head());
+                    ptr::addr_of_mut!((*this).count).write(std::cell::Cell::new(0));
+                    ptr::addr_of_mut!((*this).balance).write(Default::default());
+                    ptr::addr_of_mut!((*this).generation).write(std::sync::atomic::AtomicU32::new(0));
+                    ptr::addr_of_mut!((*this).dropped).write(std::sync::atomic::AtomicBool::new(false));
+                    ptr::addr_of_mut!((*this).holder).write(Default::default());
+                    ptr::addr_of_mut!((*this).borrows).write(Default::default());
+                    ptr::addr_of_mut!((*this).snapshots).write(Default::default());
+                    ptr::addr_of_mut!((*this).notifier).write(Default::default());
+                    ptr::addr_of_mut!((*this).deferred).write(Default::default());
+                    ptr::addr_of_mut!((*this).mailbox).write(Default::default());
+                    ptr::addr_of_mut!((*this).tail).write($crate::Canary::tail());
                 }
 
-                /// Turn this `
-                #[doc = stringify!($send)]
-                /// ` into the `!Send` version `
-                #[doc = stringify!($unsend)]
-                /// `.
-                pub fn into_unsend(mut self) -> $unsend<T> {
-                    let narc = $unsend {
-                        ptr: self.ptr,
-                        phantom: self.phantom,
-                    };
+                Ok(this)
+            }
 
-                    self.ptr = ptr::null_mut();
+            /// Zero-sized values are written to a dangling pointer, which
+            /// moves them into the owner all the same.
+            unsafe fn init(this: *mut Self, value: T) {
+                Self::value(this).write(value);
+            }
 
-                    narc
+            /// Panics if `this` belongs to an owner that was closed, see
+            /// `close`.
+            #[inline(always)]
+            fn check_open(this: *mut Self) {
+                if this.is_null() {
+                    $crate::__panic!("Owner was closed.")
                 }
+            }
 
-                /// Turn this parameterized `
-                #[doc = stringify!($send)]
-                /// ` the unparameterized `ErasedSnarc`.
-                pub fn into_erased(self) -> ErasedSnarc
-                where
-                    T: Send + 'static,
-                {
-                    let snarc: Box<dyn Context + Send + 'static> = Box::new(self);
-                    ErasedSnarc::from(snarc)
-                }
+            #[inline(always)]
+            fn check(this: *mut Self) {
+                Self::check_open(this);
 
-                #[inline(always)]
-                fn inner(&self) -> &SnarcBox<T> {
-                    unsafe { &*self.ptr }
+                if Self::ALLOCATES {
+                    unsafe { $crate::Canary::check(&(*this).head, &(*this).tail) }
                 }
+            }
 
-                #[inline]
-                unsafe fn get_mut_unchecked(this: &mut Self) -> &mut T {
-                    &mut (*this.ptr).value
+            #[inline(always)]
+            fn value(this: *mut Self) -> *mut T {
+                Self::check_open(this);
+
+                if Self::ALLOCATES {
+                    unsafe { ptr::addr_of_mut!((*this).value) }
+                } else {
+                    ptr::NonNull::dangling().as_ptr()
                 }
+            }
 
-                /// Creates a new non-owning reference to the inner value.
-                pub fn new_ref(&self) -> $ref<T> {
-                    let inner = self.inner();
+            #[inline(always)]
+            fn state(_this: *mut Self) -> State {
+                THREAD_LOCAL.with(|c| c.get())
+            }
 
-                    inner.count.set(inner.count.get() + 1);
+            #[inline(always)]
+            fn set_state(this: *mut Self, v: State) {
+                match v {
+                    State::Default => {
+                        $crate::check_borrows(BORROWS.with(|b| b.get()));
 
-                    $ref {
-                        ptr: self.ptr,
-                        phantom: Default::default(),
+                        if Self::ALLOCATES {
+                            unsafe { (*this).snapshots.unlock() }
+                        }
                     }
+                    _ if !Self::ALLOCATES => {}
+                    State::Entered => unsafe {
+                        (*this).snapshots.lock();
+                        (*this).balance.reconcile(&(*this).count)
+                    },
+                    State::Unsend => unsafe { (*this).snapshots.lock() },
                 }
 
-                /// Temporarily bind the inner value to this thread and evaluate `f`
-                /// within that context.
-                pub fn enter<F, R>(&mut self, f: F) -> R
-                where
-                    F: FnOnce(&T) -> R,
-                {
-                    THREAD_LOCAL.with(|c| {
-                        if c.get() == State::Entered {
-                            panic!(concat!(
-                                "Another ",
-                                stringify!($send),
-                                " is already entered."
-                            ))
-                        }
-
-                        c.set(State::Entered);
-                    });
-
-                    let _guard = $crate::scopeguard::guard((), |_| {
-                        THREAD_LOCAL.with(|c| c.set(State::Default));
-                    });
-
-                    f(&self.inner().value)
-                }
+                THREAD_LOCAL.with(|c| c.set(v))
             }
 
-            impl<T: Send + 'static> From<$send<T>> for ErasedSnarc {
-                fn from(snarc: $send<T>) -> Self {
-                    snarc.into_erased()
+            /// Registers a guard created by `borrow`, which the context must
+            /// not be left before, unless the value is borrowed mutably.
+            #[inline(always)]
+            fn borrow(this: *mut Self) -> bool {
+                if Self::ALLOCATES && !unsafe { (*this).borrows.try_borrow() } {
+                    return false;
                 }
-            }
 
-            impl<T: Send + 'static> From<$send<T>> for ErasedNarc {
-                fn from(snarc: $send<T>) -> Self {
-                    snarc.into_unsend().into_erased()
-                }
+                BORROWS.with(|b| b.set(b.get() + 1));
+                true
             }
 
-            impl<T> Context for $send<T> {
-                fn set(&mut self, v: State) {
-                    THREAD_LOCAL.with(|c| {
-                        if v == State::Entered && c.get() == State::Entered {
-                            panic!(concat!(
-                                "Another ",
-                                stringify!($send),
-                                " is already entered."
-                            ))
-                        }
-
-                        c.set(v);
-                    });
+            /// Like `borrow`, but for a guard created by `get_mut`. Zero-sized
+            /// values have no control block to track the borrow in, which is
+            /// harmless as there is nothing to access.
+            #[inline(always)]
+            fn borrow_mut(this: *mut Self) -> bool {
+                if Self::ALLOCATES && !unsafe { (*this).borrows.try_borrow_mut() } {
+                    return false;
                 }
-            }
-
-            impl<T> Deref for $send<T> {
-                type Target = T;
 
-                #[inline(always)]
-                fn deref(&self) -> &Self::Target {
-                    &self.inner().value
+                if Self::ALLOCATES {
+                    // the value may be borrowed within the context of another
+                    // value of the family, which does not lock out snapshots
+                    unsafe { (*this).snapshots.lock() }
                 }
+
+                BORROWS.with(|b| b.set(b.get() + 1));
+                true
             }
 
-            impl<T> DerefMut for $send<T> {
-                #[inline(always)]
-                fn deref_mut(&mut self) -> &mut Self::Target {
-                    unsafe { Self::get_mut_unchecked(self) }
+            #[inline(always)]
+            fn unborrow(this: *mut ()) {
+                if Self::ALLOCATES {
+                    unsafe { (*this.cast::<Self>()).borrows.release() }
                 }
+
+                BORROWS.with(|b| b.set(b.get() - 1))
             }
 
-            impl<T> Drop for $send<T> {
-                fn drop(&mut self) {
-                    if !self.ptr.is_null() {
-                        THREAD_LOCAL.with(|c| {
-                            if c.get() == State::Entered {
-                                panic!(concat!(
-                                    "Another ",
-                                    stringify!($send),
-                                    " is already entered."
-                                ))
-                            }
+            /// Enters the context, unless it was entered by other means than
+            /// `try_bind` or the instance is entered on another thread.
+            /// Instances, the same or distinct ones, may be entered in a
+            /// nested fashion.
+            #[inline(always)]
+            fn try_bind(this: *mut Self) -> bool {
+                // the context only guards the count on the thread holding the
+                // instance, zero-sized values have no count to guard
+                let claimed = if Self::ALLOCATES {
+                    match unsafe { (*this).holder.claim() } {
+                        Some(claimed) => claimed,
+                        None => return false,
+                    }
+                } else {
+                    false
+                };
 
-                            c.set(State::Entered)
-                        });
+                ENTERED.with(|entered| {
+                    let mut entered = entered.borrow_mut();
 
-                        let _guard = $crate::scopeguard::guard((), |_| {
-                            THREAD_LOCAL.with(|c| c.set(State::Default));
-                        });
+                    if entered.is_empty() {
+                        if Self::state(this) == State::Entered {
+                            if claimed {
+                                unsafe { (*this).holder.release() }
+                            }
 
-                        unsafe {
-                            // destroy the contained object
-                            ptr::drop_in_place(Self::get_mut_unchecked(self));
+                            return false;
                         }
 
-                        if self.inner().count.get() == 0 {
-                            unsafe {
-                                ptr::addr_of_mut!((*self.ptr).count).drop_in_place();
-                                let layout = alloc::Layout::for_value(&*self.ptr);
-                                alloc::dealloc(self.ptr.cast(), layout);
-                            }
-                        }
+                        Self::set_state(this, State::Entered);
                     }
-                }
-            }
 
-            /// A non-sendable, owning reference-counted pointer to a `T`.
-            ///
-            /// When `
-            #[doc = stringify!($unsend)]
-            /// ` is used exclusively, i.e., never `
-            #[doc = stringify!($send)]
-            /// `, then `Rc` should
-            /// likely be used instead.
-            pub struct $unsend<T> {
-                ptr: *mut SnarcBox<T>,
-                phantom: std::marker::PhantomData<SnarcBox<T>>,
+                    // values entered within the context of another are only
+                    // unlocked once they are entered on their own
+                    Self::lock_snapshots(this);
+
+                    entered.push(this.cast());
+                    true
+                })
             }
 
-            unsafe impl<T: Sync> Sync for $unsend<T> {}
+            /// Undoes the innermost `try_bind`, leaving the context once the
+            /// outermost one is undone.
+            #[inline(always)]
+            fn unbind(this: *mut Self) {
+                ENTERED.with(|entered| {
+                    let mut entered = entered.borrow_mut();
+                    entered.pop();
 
-            impl<T> $unsend<T> {
-                /// Creates a new `
-                #[doc = stringify!($unsend)]
-                /// ` with the given inner `value`.
-                pub fn new(value: T) -> Self {
-                    Self {
-                        ptr: SnarcBox::new_ptr(value),
-                        phantom: std::marker::PhantomData,
+                    if entered.is_empty() {
+                        Self::set_state(this, State::Default);
                     }
-                }
 
-                /// Turn this `
-                #[doc = stringify!($unsend)]
-                /// ` into the `Send` version `
-                #[doc = stringify!($send)]
-                /// `.
-                pub fn into_send(mut self) -> $send<T> {
-                    let snarc = $send {
-                        ptr: self.ptr,
-                        phantom: self.phantom,
-                    };
+                    if Self::ALLOCATES && !entered.contains(&this.cast()) {
+                        unsafe { (*this).holder.release() }
+                    }
+                })
+            }
 
-                    self.ptr = ptr::null_mut();
+            #[inline(always)]
+            fn increment(this: *mut Self) {
+                Self::check_open(this);
 
-                    snarc
+                if Self::ALLOCATES {
+                    $crate::increment(unsafe { &(*this).count });
                 }
+            }
 
-                /// Turn this parameterized `
-                #[doc = stringify!($unsend)]
-                /// ` the unparameterized `ErasedNarc`.
-                pub fn into_erased(self) -> ErasedNarc
-                where
-                    T: Send + 'static,
-                {
-                    self.into_send().into_erased().into_unsend()
-                }
+            /// Zero-sized values have no generation, so their references
+            /// cannot be revoked.
+            #[inline(always)]
+            fn generation(this: *mut Self) -> u32 {
+                Self::check_open(this);
 
-                #[inline(always)]
-                fn inner(&self) -> &SnarcBox<T> {
-                    unsafe { &*self.ptr }
+                if Self::ALLOCATES {
+                    unsafe { (*this).generation.load(std::sync::atomic::Ordering::Relaxed) }
+                } else {
+                    0
                 }
+            }
 
-                #[inline]
-                unsafe fn get_mut_unchecked(this: &mut Self) -> &mut T {
-                    &mut (*this.ptr).value
+            /// Records that the owner is gone. Zero-sized values have no
+            /// control block to record it in, so they never dangle.
+            fn mark_dropped(this: *mut Self) {
+                if Self::ALLOCATES {
+                    let dropped = unsafe { &(*this).dropped };
+                    dropped.store(true, std::sync::atomic::Ordering::Release);
                 }
+            }
 
-                /// Creates a new non-owning reference to the inner value.
-                pub fn new_ref(&self) -> $ref<T> {
-                    let inner = self.inner();
+            #[inline(always)]
+            fn is_dropped(this: *mut Self) -> bool {
+                Self::ALLOCATES
+                    && unsafe { (*this).dropped.load(std::sync::atomic::Ordering::Acquire) }
+            }
 
-                    inner.count.set(inner.count.get() + 1);
+            fn revoke(this: *mut Self) {
+                Self::check_open(this);
 
-                    $ref {
-                        ptr: self.ptr,
-                        phantom: Default::default(),
-                    }
+                if Self::ALLOCATES {
+                    let generation = unsafe { &(*this).generation };
+                    generation.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                 }
             }
 
-            impl<T: Send + 'static> From<$unsend<T>> for ErasedSnarc {
-                fn from(narc: $unsend<T>) -> Self {
-                    narc.into_send().into_erased()
+            fn set_generation(this: *mut Self, generation: u32) {
+                if Self::ALLOCATES {
+                    let g = unsafe { &(*this).generation };
+                    g.store(generation, std::sync::atomic::Ordering::Relaxed);
                 }
             }
 
-            impl<T: Send + 'static> From<$unsend<T>> for ErasedNarc {
-                fn from(narc: $unsend<T>) -> Self {
-                    narc.into_erased()
+            /// Zero-sized values are not counted, so they have a count of zero.
+            #[inline(always)]
+            fn ref_count(this: *mut Self) -> usize {
+                Self::check_open(this);
+
+                if Self::ALLOCATES {
+                    unsafe { (*this).balance.refs((*this).count.get()) }
+                } else {
+                    0
                 }
             }
 
-            impl<T> Deref for $unsend<T> {
-                type Target = T;
+            /// Zero-sized values are not counted, so they are assumed to have
+            /// references.
+            #[inline(always)]
+            fn has_refs(this: *mut Self) -> bool {
+                Self::check_open(this);
 
-                #[inline(always)]
-                fn deref(&self) -> &Self::Target {
-                    &self.inner().value
+                !Self::ALLOCATES || Self::ref_count(this) != 0
+            }
+
+            /// Counts a reference cloned outside of `enter`, given the
+            /// `atomic-clone` feature. Zero-sized values are not counted.
+            #[inline(always)]
+            fn try_increment_detached(this: *mut Self) -> bool {
+                if Self::ALLOCATES {
+                    unsafe { $crate::Detached::try_increment(&(*this).balance) }
+                } else {
+                    $crate::Detached::ENABLED
                 }
             }
 
-            impl<T> DerefMut for $unsend<T> {
-                #[inline(always)]
-                fn deref_mut(&mut self) -> &mut Self::Target {
-                    unsafe { Self::get_mut_unchecked(self) }
+            /// Queues a reference dropped outside of `enter`, freeing the
+            /// allocation if the owner is gone and this was the last reference.
+            /// Zero-sized values are not counted.
+            #[inline(always)]
+            fn decrement_detached(this: *mut Self) {
+                if Self::ALLOCATES && unsafe { (*this).balance.decrement() } {
+                    unsafe { Self::free(this) }
                 }
             }
 
-            impl<T> Drop for $unsend<T> {
-                fn drop(&mut self) {
-                    if !self.ptr.is_null() {
-                        THREAD_LOCAL.with(|c| {
-                            if c.get() == State::Entered {
-                                panic!(concat!(
-                                    "Another ",
-                                    stringify!($send),
-                                    " is already entered."
-                                ))
-                            }
+            /// Returns `true` once the owner is gone, after which the state of
+            /// the family no longer guards the count.
+            #[inline(always)]
+            fn is_orphaned(this: *mut Self) -> bool {
+                Self::ALLOCATES && unsafe { (*this).balance.is_orphaned() }
+            }
 
-                            c.set(State::Entered)
-                        });
+            #[inline(always)]
+            fn increment_orphaned(this: *mut Self) {
+                if Self::ALLOCATES {
+                    unsafe { (*this).balance.increment() }
+                }
+            }
 
-                        let _guard = $crate::scopeguard::guard((), |_| {
-                            THREAD_LOCAL.with(|c| c.set(State::Default));
-                        });
+            #[inline(always)]
+            fn decrement(this: *mut Self) {
+                if Self::ALLOCATES {
+                    let count = unsafe { &(*this).count };
+                    unsafe { (*this).balance.reconcile(count) };
+                    count.set(count.get() - 1);
+                }
+            }
 
-                        unsafe {
-                            // destroy the contained object
-                            ptr::drop_in_place(Self::get_mut_unchecked(self));
-                        }
+            #[inline(always)]
+            fn mark_dirty(this: *mut Self) {
+                Self::check_open(this);
 
-                        if self.inner().count.get() == 0 {
-                            unsafe {
-                                ptr::addr_of_mut!((*self.ptr).count).drop_in_place();
-                                let layout = alloc::Layout::for_value(&*self.ptr);
-                                alloc::dealloc(self.ptr.cast(), layout);
-                            }
-                        }
-                    }
+                if Self::ALLOCATES {
+                    unsafe { (*this).notifier.mark_dirty() }
                 }
             }
 
-            pub struct $ref<T> {
-                ptr: *mut SnarcBox<T>,
-                phantom: std::marker::PhantomData<SnarcBox<T>>,
+            /// Locks snapshots out until the value is entered and left again.
+            #[inline(always)]
+            fn lock_snapshots(this: *mut Self) {
+                if Self::ALLOCATES {
+                    unsafe { (*this).snapshots.lock() }
+                }
             }
 
-            unsafe impl<T> Send for $ref<T> {}
-            unsafe impl<T> Sync for $ref<T> {}
+            /// Locks snapshots out for good, before the value is dropped.
+            fn close_snapshots(this: *mut Self) {
+                if Self::ALLOCATES {
+                    unsafe { (*this).snapshots.close() }
+                }
+            }
 
-            impl<T> $ref<T> {
-                #[inline(always)]
-                fn inner(&self) -> &SnarcBox<T> {
-                    unsafe { &*self.ptr }
+            /// Begins a snapshot, which must be ended by `end_snapshot`.
+            /// Zero-sized values have no control block to coordinate with the
+            /// owner by, so they are never snapshotted.
+            #[allow(dead_code)] // without the `snapshot` feature
+            fn try_snapshot(this: *mut Self) -> bool {
+                Self::ALLOCATES && unsafe { (*this).snapshots.try_read() }
+            }
+
+            #[allow(dead_code)] // without the `snapshot` feature
+            fn end_snapshot(this: *mut Self) {
+                unsafe { (*this).snapshots.end_read() }
+            }
+
+            #[inline(always)]
+            fn notify(this: *mut Self) {
+                if Self::ALLOCATES {
+                    unsafe { (*this).notifier.notify() }
                 }
+            }
 
-                pub fn get(&self) -> Option<&T> {
-                    let inner = self.inner();
+            /// Zero-sized values have no control block to queue `f` in, so it
+            /// is dropped.
+            fn defer<F: FnOnce(&T) + Send + 'static>(this: *mut Self, f: F) {
+                if Self::ALLOCATES {
+                    unsafe { (*this).deferred.push(f) }
+                }
+            }
 
-                    if THREAD_LOCAL.with(|c| c.get().is_set()) {
-                        Some(&inner.value)
-                    } else {
-                        None
-                    }
+            /// Runs the tasks queued by `defer`, must be called within `enter`.
+            #[inline(always)]
+            fn drain(this: *mut Self) {
+                if Self::ALLOCATES {
+                    // the tasks are cleared whenever the value is mapped to
+                    // another type
+                    unsafe { (*this).deferred.drain(&*Self::value(this)) }
                 }
+            }
 
-                $(
-                    pub fn expect(&self) -> &T {
-                        self.get().expect($expect)
+            fn clear_deferred(this: *mut Self) {
+                if Self::ALLOCATES {
+                    unsafe {
+                        (*this).deferred.clear();
+                        (*this).mailbox.clear();
                     }
-                )?
+                }
             }
 
-            impl<T> Clone for $ref<T> {
-                fn clone(&self) -> Self {
-                    if THREAD_LOCAL.with(|c| c.get().is_set()) {
-                        let inner = self.inner();
+            /// Zero-sized values have no control block to queue `message` in,
+            /// so it is returned.
+            fn send<M: Send + 'static>(this: *mut Self, message: M) -> Result<(), M> {
+                if !Self::ALLOCATES {
+                    return Err(message);
+                }
 
-                        inner.count.set(inner.count.get() + 1);
+                unsafe { (*this).mailbox.send(message) };
+                Ok(())
+            }
 
-                        Self {
-                            ptr: self.ptr,
-                            phantom: Default::default(),
-                        }
-                    } else {
-                        panic!(concat!(
-                            stringify!($ref),
-                            "::clone() outside of ",
-                            stringify!($send),
-                            "::enter(…)"
-                        ))
-                    }
+            fn take_messages<M: Send + 'static>(this: *mut Self) -> Vec<M> {
+                if Self::ALLOCATES {
+                    unsafe { (*this).mailbox.take() }
+                } else {
+                    Vec::new()
                 }
             }
 
-            impl<T> Drop for $ref<T> {
-                fn drop(&mut self) {
-                    if THREAD_LOCAL.with(|c| c.get().is_set()) {
-                        let inner = self.inner();
+            #[inline(always)]
+            fn version(this: *mut Self) -> u64 {
+                Self::check_open(this);
 
-                        inner.count.set(inner.count.get() - 1);
-                    } else {
-                        #[cfg(debug_assertions)]
-                        panic!(concat!(
-                            stringify!($ref),
-                            "::drop() outside of ",
-                            stringify!($send),
-                            "::enter(…)"
-                        ))
-                    }
+                if Self::ALLOCATES {
+                    unsafe { (*this).notifier.version() }
+                } else {
+                    0
                 }
             }
-        }
-    };
-}
 
-#[cfg(test)]
-mod tests {
-    crate::snarc!(Snarc, Narc, SnarcRef, "expectation");
+            /// Zero-sized values have no control block to wait on, so their
+            /// waiters are woken immediately.
+            fn poll_entered(
+                this: *mut Self,
+                since: &mut Option<u64>,
+                cx: &mut std::task::Context<'_>,
+            ) -> std::task::Poll<()> {
+                if Self::ALLOCATES {
+                    unsafe { (*this).notifier.poll_entered(since, cx) }
+                } else {
+                    std::task::Poll::Ready(())
+                }
+            }
+
+            /// Zero-sized values never change, so their listeners are detached.
+            fn subscribe(this: *mut Self) -> $crate::ChangeListener {
+                Self::check_open(this);
+
+                if Self::ALLOCATES {
+                    unsafe { (*this).notifier.subscribe() }
+                } else {
+                    $crate::ChangeListener::detached()
+                }
+            }
+
+            /// Frees the allocation of an already dropped value, unless there
+            /// are references left. In that case, the last reference to be
+            /// dropped frees it.
+            fn release(this: *mut Self) {
+                if Self::ALLOCATES
+                    && unsafe { (*this).balance.orphan((*this).count.get()) }
+                {
+                    unsafe { Self::free(this) }
+                }
+            }
+
+            /// Frees the allocation of an already dropped value.
+            unsafe fn free(this: *mut Self) {
+                ptr::addr_of_mut!((*this).head).drop_in_place();
+                ptr::addr_of_mut!((*this).count).drop_in_place();
+                ptr::addr_of_mut!((*this).notifier).drop_in_place();
+                ptr::addr_of_mut!((*this).deferred).drop_in_place();
+                ptr::addr_of_mut!((*this).mailbox).drop_in_place();
+                ptr::addr_of_mut!((*this).tail).drop_in_place();
+                alloc::dealloc(this.cast(), alloc::Layout::new::<Self>());
+            }
+        }
+    };
+    (@context per_instance) => {
+        #[repr(C)]
+        struct SnarcBox<T> {
+            head: $crate::Canary,
+            count: std::cell::Cell<$crate::Count>,
+            balance: $crate::Balance,
+            generation: std::sync::atomic::AtomicU32,
+            dropped: std::sync::atomic::AtomicBool,
+            binding: $crate::Binding,
+            snapshots: $crate::SnapshotLock,
+            notifier: $crate::Notifier,
+            deferred: $crate::DeferQueue,
+            mailbox: $crate::Mailbox,
+            value: T,
+            tail: $crate::Canary,
+        }
+
+        impl<T> SnarcBox<T> {
+            fn new_ptr(value: T) -> *mut Self {
+                Box::leak(Box::new(Self {
+                    head: $crate::Canary::head(),
+                    count: std::cell::Cell::new(0),
+                    balance: Default::default(),
+                    generation: std::sync::atomic::AtomicU32::new(0),
+                    dropped: std::sync::atomic::AtomicBool::new(false),
+                    binding: Default::default(),
+                    snapshots: Default::default(),
+                    notifier: Default::default(),
+                    deferred: Default::default(),
+                    mailbox: Default::default(),
+                    value,
+                    tail: $crate::Canary::tail(),
+                }))
+            }
+
+            /// Allocates a control block whose value is written later, by
+            /// `init`.
+            fn new_uninit_ptr() -> *mut Self {
+                Self::try_new_uninit_ptr()
+                    .unwrap_or_else(|_| alloc::handle_alloc_error(alloc::Layout::new::<Self>()))
+            }
+
+            /// Like `new_uninit_ptr`, but fails instead of aborting if memory
+            /// cannot be allocated.
+            fn try_new_uninit_ptr() -> Result<*mut Self, $crate::AllocError> {
+                let this = unsafe { alloc::alloc(alloc::Layout::new::<Self>()) }.cast::<Self>();
+                if this.is_null() {
+                    return Err($crate::AllocError);
+                }
+
+                unsafe {
+                    ptr::addr_of_mut!((*this).head).write($crate::Canary::head());
+                    ptr::addr_of_mut!((*this).count).write(std::cell::Cell::new(0));
+                    ptr::addr_of_mut!((*this).balance).write(Default::default());
+                    ptr::addr_of_mut!((*this).generation).write(std::sync::atomic::AtomicU32::new(0));
+                    ptr::addr_of_mut!((*this).dropped).write(std::sync::atomic::AtomicBool::new(false));
+                    ptr::addr_of_mut!((*this).binding).write(Default::default());
+                    ptr::addr_of_mut!((*this).snapshots).write(Default::default());
+                    ptr::addr_of_mut!((*this).notifier).write(Default::default());
+                    ptr::addr_of_mut!((*this).deferred).write(Default::default());
+                    ptr::addr_of_mut!((*this).mailbox).write(Default::default());
+                    ptr::addr_of_mut!((*this).tail).write($crate::Canary::tail());
+                }
+
+                Ok(this)
+            }
+
+            unsafe fn init(this: *mut Self, value: T) {
+                Self::value(this).write(value);
+            }
+
+            /// Panics if `this` belongs to an owner that was closed, see
+            /// `close`.
+            #[inline(always)]
+            fn check_open(this: *mut Self) {
+                if this.is_null() {
+                    $crate::__panic!("Owner was closed.")
+                }
+            }
+
+            #[inline(always)]
+            fn check(this: *mut Self) {
+                Self::check_open(this);
+
+                unsafe { $crate::Canary::check(&(*this).head, &(*this).tail) }
+            }
+
+            #[inline(always)]
+            fn value(this: *mut Self) -> *mut T {
+                Self::check_open(this);
+
+                unsafe { ptr::addr_of_mut!((*this).value) }
+            }
+
+            #[inline(always)]
+            fn state(this: *mut Self) -> State {
+                unsafe { (*this).binding.get() }
+            }
+
+            #[inline(always)]
+            fn set_state(this: *mut Self, v: State) {
+                unsafe { (*this).binding.set(v) };
+
+                match v {
+                    State::Default => unsafe { (*this).snapshots.unlock() },
+                    State::Entered => unsafe {
+                        (*this).snapshots.lock();
+                        (*this).balance.reconcile(&(*this).count)
+                    },
+                    State::Unsend => unsafe { (*this).snapshots.lock() },
+                }
+            }
+
+            /// Registers a guard created by `borrow`, which the value must not
+            /// be unbound before, unless the value is borrowed mutably.
+            #[inline(always)]
+            fn borrow(this: *mut Self) -> bool {
+                unsafe { (*this).binding.borrows().try_borrow() }
+            }
+
+            /// Like `borrow`, but for a guard created by `get_mut`.
+            #[inline(always)]
+            fn borrow_mut(this: *mut Self) -> bool {
+                unsafe { (*this).binding.borrows().try_borrow_mut() }
+            }
+
+            #[inline(always)]
+            fn unborrow(this: *mut ()) {
+                unsafe { (*this.cast::<Self>()).binding.borrows().release() }
+            }
+
+            /// Enters the value, unless it is entered already on another thread.
+            /// Entering it again on this thread nests.
+            #[inline(always)]
+            fn try_bind(this: *mut Self) -> bool {
+                if !unsafe { (*this).binding.try_enter() } {
+                    return false;
+                }
+
+                unsafe {
+                    (*this).snapshots.lock();
+                    (*this).balance.reconcile(&(*this).count);
+                }
+                true
+            }
+
+            /// Undoes one `try_bind`.
+            #[inline(always)]
+            fn unbind(this: *mut Self) {
+                unsafe { (*this).binding.exit() };
+
+                if Self::state(this) == State::Default {
+                    unsafe { (*this).snapshots.unlock() }
+                }
+            }
+
+            #[inline(always)]
+            fn increment(this: *mut Self) {
+                Self::check_open(this);
+
+                $crate::increment(unsafe { &(*this).count });
+            }
+
+            #[inline(always)]
+            fn generation(this: *mut Self) -> u32 {
+                Self::check_open(this);
+
+                unsafe { (*this).generation.load(std::sync::atomic::Ordering::Relaxed) }
+            }
+
+            /// Records that the owner is gone.
+            fn mark_dropped(this: *mut Self) {
+                let dropped = unsafe { &(*this).dropped };
+                dropped.store(true, std::sync::atomic::Ordering::Release);
+            }
+
+            #[inline(always)]
+            fn is_dropped(this: *mut Self) -> bool {
+                unsafe { (*this).dropped.load(std::sync::atomic::Ordering::Acquire) }
+            }
+
+            fn revoke(this: *mut Self) {
+                Self::check_open(this);
+
+                let generation = unsafe { &(*this).generation };
+                generation.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+
+            fn set_generation(this: *mut Self, generation: u32) {
+                let g = unsafe { &(*this).generation };
+                g.store(generation, std::sync::atomic::Ordering::Relaxed);
+            }
+
+            #[inline(always)]
+            fn ref_count(this: *mut Self) -> usize {
+                Self::check_open(this);
+
+                unsafe { (*this).balance.refs((*this).count.get()) }
+            }
+
+            #[inline(always)]
+            fn has_refs(this: *mut Self) -> bool {
+                Self::ref_count(this) != 0
+            }
+
+            /// Counts a reference cloned outside of `enter`, given the
+            /// `atomic-clone` feature.
+            #[inline(always)]
+            fn try_increment_detached(this: *mut Self) -> bool {
+                unsafe { $crate::Detached::try_increment(&(*this).balance) }
+            }
+
+            /// Queues a reference dropped outside of `enter`, freeing the
+            /// allocation if the owner is gone and this was the last reference.
+            #[inline(always)]
+            fn decrement_detached(this: *mut Self) {
+                if unsafe { (*this).balance.decrement() } {
+                    unsafe { Self::free(this) }
+                }
+            }
+
+            #[inline(always)]
+            fn is_orphaned(this: *mut Self) -> bool {
+                unsafe { (*this).balance.is_orphaned() }
+            }
+
+            #[inline(always)]
+            fn increment_orphaned(this: *mut Self) {
+                unsafe { (*this).balance.increment() }
+            }
+
+            #[inline(always)]
+            fn decrement(this: *mut Self) {
+                let count = unsafe { &(*this).count };
+                unsafe { (*this).balance.reconcile(count) };
+                count.set(count.get() - 1);
+            }
+
+            #[inline(always)]
+            fn mark_dirty(this: *mut Self) {
+                Self::check_open(this);
+
+                unsafe { (*this).notifier.mark_dirty() }
+            }
+
+            /// Locks snapshots out until the value is entered and left again.
+            #[inline(always)]
+            fn lock_snapshots(this: *mut Self) {
+                unsafe { (*this).snapshots.lock() }
+            }
+
+            /// Locks snapshots out for good, before the value is dropped.
+            fn close_snapshots(this: *mut Self) {
+                unsafe { (*this).snapshots.close() }
+            }
+
+            /// Begins a snapshot, which must be ended by `end_snapshot`.
+            #[allow(dead_code)] // without the `snapshot` feature
+            fn try_snapshot(this: *mut Self) -> bool {
+                unsafe { (*this).snapshots.try_read() }
+            }
+
+            #[allow(dead_code)] // without the `snapshot` feature
+            fn end_snapshot(this: *mut Self) {
+                unsafe { (*this).snapshots.end_read() }
+            }
+
+            #[inline(always)]
+            fn notify(this: *mut Self) {
+                unsafe { (*this).notifier.notify() }
+            }
+
+            fn defer<F: FnOnce(&T) + Send + 'static>(this: *mut Self, f: F) {
+                unsafe { (*this).deferred.push(f) }
+            }
+
+            /// Runs the tasks queued by `defer`, must be called within `enter`.
+            #[inline(always)]
+            fn drain(this: *mut Self) {
+                // the tasks are cleared whenever the value is mapped to another
+                // type
+                unsafe { (*this).deferred.drain(&*Self::value(this)) }
+            }
+
+            fn clear_deferred(this: *mut Self) {
+                unsafe {
+                    (*this).deferred.clear();
+                    (*this).mailbox.clear();
+                }
+            }
+
+            fn send<M: Send + 'static>(this: *mut Self, message: M) -> Result<(), M> {
+                unsafe { (*this).mailbox.send(message) };
+                Ok(())
+            }
+
+            fn take_messages<M: Send + 'static>(this: *mut Self) -> Vec<M> {
+                unsafe { (*this).mailbox.take() }
+            }
+
+            fn subscribe(this: *mut Self) -> $crate::ChangeListener {
+                Self::check_open(this);
+
+                unsafe { (*this).notifier.subscribe() }
+            }
+
+            fn poll_entered(
+                this: *mut Self,
+                since: &mut Option<u64>,
+                cx: &mut std::task::Context<'_>,
+            ) -> std::task::Poll<()> {
+                unsafe { (*this).notifier.poll_entered(since, cx) }
+            }
+
+            #[inline(always)]
+            fn version(this: *mut Self) -> u64 {
+                Self::check_open(this);
+
+                unsafe { (*this).notifier.version() }
+            }
+
+            /// Frees the allocation of an already dropped value, unless there
+            /// are references left. In that case, the last reference to be
+            /// dropped frees it.
+            fn release(this: *mut Self) {
+                if unsafe { (*this).balance.orphan((*this).count.get()) } {
+                    unsafe { Self::free(this) }
+                }
+            }
+
+            /// Frees the allocation of an already dropped value.
+            unsafe fn free(this: *mut Self) {
+                ptr::addr_of_mut!((*this).head).drop_in_place();
+                ptr::addr_of_mut!((*this).count).drop_in_place();
+                ptr::addr_of_mut!((*this).binding).drop_in_place();
+                ptr::addr_of_mut!((*this).notifier).drop_in_place();
+                ptr::addr_of_mut!((*this).deferred).drop_in_place();
+                ptr::addr_of_mut!((*this).mailbox).drop_in_place();
+                ptr::addr_of_mut!((*this).tail).drop_in_place();
+                alloc::dealloc(this.cast(), alloc::Layout::new::<Self>());
+            }
+        }
+    };
+    (@erased $send:ident, $unsend:ident, $erased_send:ident, $erased_unsend:ident) => {
+        /// A `
+        #[doc = stringify!($send)]
+        /// <T>` whose type argument was erased.
+        ///
+        /// Unlike `ErasedSnarc`, a `
+        #[doc = stringify!($erased_send)]
+        /// ` can only be created from the structs defined alongside it.
+        pub struct $erased_send {
+            inner: $crate::ErasedOwner<'static>,
+        }
+
+        impl $erased_send {
+            /// Turn this into an unsendable `
+            #[doc = stringify!($erased_unsend)]
+            /// ` bound to the current thread.
+            pub fn into_unsend(mut self) -> $erased_unsend {
+                self.inner.set(State::Unsend);
+                $erased_unsend {
+                    inner: self.inner,
+                    _unsend: Default::default(),
+                }
+            }
+
+            /// Temporarily bind the inner value to this thread and evaluate `f`
+            /// within that context.
+            pub fn enter<F, R>(&mut self, f: F) -> R
+            where
+                F: FnOnce() -> R,
+            {
+                self.inner.set(State::Entered);
+
+                let _guard = $crate::scopeguard::guard((), |_| {
+                    self.inner.set(State::Default);
+                });
+
+                f()
+            }
+
+            /// Binds the inner value to this thread until the returned guard is
+            /// dropped.
+            ///
+            /// Unlike `enter`, this permits early returns and `?` within the
+            /// entered scope. Should the guard be leaked, the owner is leaked as
+            /// well and any further use of `self` panics.
+            pub fn enter_guard(&mut self) -> $crate::ErasedEnterGuard<'_> {
+                $crate::ErasedEnterGuard::new(&mut self.inner)
+            }
+
+            /// Returns `true` if the inner value is bound to the current thread,
+            /// i.e., within `enter`, be it of this or of the typed owner.
+            ///
+            /// Unless `per_instance;` is given, this is the case while any value
+            /// of this context is entered.
+            pub fn is_entered(&self) -> bool {
+                self.inner.is_entered()
+            }
+
+            /// Temporarily bind the inner value to this thread and evaluate `f`
+            /// with it, provided it is a `T`.
+            pub fn enter_as<T, F, R>(&mut self, f: F) -> Result<R, $crate::WrongType>
+            where
+                T: 'static,
+                F: FnOnce(&T) -> R,
+            {
+                let value = self.inner.value().expect("the owner was erased by `new`");
+
+                if !unsafe { &*value }.is::<T>() {
+                    return Err($crate::WrongType);
+                }
+
+                Ok(self.enter(|| f(unsafe { &*value.cast::<T>() })))
+            }
+
+            /// Temporarily bind the inner value to this thread and evaluate `f`
+            /// with it, whatever its type.
+            pub fn enter_any<F, R>(&mut self, f: F) -> R
+            where
+                F: FnOnce(&dyn std::any::Any) -> R,
+            {
+                let value = self.inner.value().expect("the owner was erased by `new`");
+
+                self.enter(|| f(unsafe { &*value }))
+            }
+
+            /// Returns the name of the inner value's type, as
+            /// `std::any::type_name` would.
+            pub fn type_name(&self) -> &'static str {
+                self.inner.type_name()
+            }
+
+            /// Recovers the `
+            #[doc = stringify!($send)]
+            /// <T>` whose type argument was erased, otherwise `self` is
+            /// returned unchanged.
+            pub fn downcast<T: Send + 'static>(self) -> Result<$send<T>, Self> {
+                self.inner.downcast().map_err(|inner| Self { inner })
+            }
+        }
+
+        impl<T: Send + 'static> From<$send<T>> for $erased_send {
+            fn from(snarc: $send<T>) -> Self {
+                Self {
+                    inner: $crate::ErasedOwner::new(snarc),
+                }
+            }
+        }
+
+        impl<T: Send + 'static> From<$unsend<T>> for $erased_send {
+            fn from(narc: $unsend<T>) -> Self {
+                narc.into_send().into()
+            }
+        }
+
+        impl std::fmt::Debug for $erased_send {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_tuple(stringify!($erased_send))
+                    .field(&format_args!("{}", self.type_name()))
+                    .finish()
+            }
+        }
+
+        impl From<$erased_unsend> for $erased_send {
+            fn from(narc: $erased_unsend) -> Self {
+                narc.into_send()
+            }
+        }
+
+        /// A unsendable `
+        #[doc = stringify!($send)]
+        /// <T>` whose type argument was erased.
+        pub struct $erased_unsend {
+            inner: $crate::ErasedOwner<'static>,
+            _unsend: std::marker::PhantomData<std::sync::MutexGuard<'static, ()>>,
+        }
+
+        impl $erased_unsend {
+            /// Turn this into a sendable `
+            #[doc = stringify!($erased_send)]
+            /// `.
+            pub fn into_send(mut self) -> $erased_send {
+                self.inner.set(State::Default);
+                $erased_send { inner: self.inner }
+            }
+
+            /// Returns the name of the inner value's type, as
+            /// `std::any::type_name` would.
+            pub fn type_name(&self) -> &'static str {
+                self.inner.type_name()
+            }
+
+            /// Recovers the `
+            #[doc = stringify!($unsend)]
+            /// <T>` whose type argument was erased, otherwise `self` is
+            /// returned unchanged.
+            pub fn downcast<T: Send + 'static>(self) -> Result<$unsend<T>, Self> {
+                self.into_send()
+                    .downcast()
+                    .map($send::into_unsend)
+                    .map_err($erased_send::into_unsend)
+            }
+        }
+
+        impl<T: Send + 'static> From<$send<T>> for $erased_unsend {
+            fn from(snarc: $send<T>) -> Self {
+                $erased_send::from(snarc).into_unsend()
+            }
+        }
+
+        impl<T: Send + 'static> From<$unsend<T>> for $erased_unsend {
+            fn from(narc: $unsend<T>) -> Self {
+                $erased_send::from(narc).into_unsend()
+            }
+        }
+
+        impl std::fmt::Debug for $erased_unsend {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_tuple(stringify!($erased_unsend))
+                    .field(&format_args!("{}", self.type_name()))
+                    .finish()
+            }
+        }
+
+        impl From<$erased_send> for $erased_unsend {
+            fn from(snarc: $erased_send) -> Self {
+                snarc.into_unsend()
+            }
+        }
+    };
+    (@define $context:ident;
+        $send:ident, $unsend:ident, $ref:ident $(, $expect:literal)?
+        $(=> $erased_send:ident, $erased_unsend:ident)?
+    ) => {
+        #[allow(non_snake_case)]
+        pub mod $send {
+            use std::alloc;
+            use std::ops::Deref;
+            use std::ops::DerefMut;
+            use std::ptr;
+
+            use $crate::Context;
+            use $crate::State;
+
+            $crate::snarc!(@context $context);
+
+            $(
+                $crate::snarc!(@erased $send, $unsend, $erased_send, $erased_unsend);
+            )?
+
+            pub struct $send<T> {
+                ptr: *mut SnarcBox<T>,
+                phantom: std::marker::PhantomData<SnarcBox<T>>,
+            }
+
+            unsafe impl<T: Send> Send for $send<T> {}
+            unsafe impl<T: Sync> Sync for $send<T> {}
+
+            impl<T> $send<T> {
+                /// Creates a new `
+                #[doc = stringify!($send)]
+                /// ` with the given inner `value`.
+                pub fn new(value: T) -> Self {
+                    Self {
+                        ptr: SnarcBox::new_ptr(value),
+                        phantom: std::marker::PhantomData,
+                    }
+                }
+
+                /// Creates a new `
+                #[doc = stringify!($send)]
+                /// ` with the given inner `value`, failing instead of aborting if
+                /// memory cannot be allocated.
+                pub fn try_new(value: T) -> Result<Self, $crate::AllocError> {
+                    let ptr = SnarcBox::try_new_uninit_ptr()?;
+                    unsafe { SnarcBox::init(ptr, value) };
+
+                    Ok(Self {
+                        ptr,
+                        phantom: std::marker::PhantomData,
+                    })
+                }
+
+                /// Creates a new `
+                #[doc = stringify!($send)]
+                /// ` with the inner value returned by `f`, or fails with its
+                /// error.
+                ///
+                /// The value is moved into the allocation, see `try_emplace` to
+                /// construct it in place.
+                pub fn try_new_with<E, F>(f: F) -> Result<Self, E>
+                where
+                    F: FnOnce() -> Result<T, E>,
+                {
+                    f().map(Self::new)
+                }
+
+                /// Creates a new `
+                #[doc = stringify!($send)]
+                /// ` whose inner value is written in place by `f`, so that large
+                /// values need not be moved.
+                ///
+                /// The allocation is freed again should `f` panic.
+                ///
+                /// # Safety
+                ///
+                /// `f` must initialize the value it is handed.
+                pub unsafe fn emplace<F>(f: F) -> Self
+                where
+                    F: FnOnce(&mut std::mem::MaybeUninit<T>),
+                {
+                    let ptr = SnarcBox::<T>::new_uninit_ptr();
+
+                    let release = $crate::scopeguard::guard_on_unwind((), |_| {
+                        SnarcBox::release(ptr);
+                    });
+                    f(&mut *SnarcBox::value(ptr).cast::<std::mem::MaybeUninit<T>>());
+                    $crate::scopeguard::ScopeGuard::into_inner(release);
+
+                    Self {
+                        ptr,
+                        phantom: std::marker::PhantomData,
+                    }
+                }
+
+                /// Like `emplace`, but `f` may fail, in which case the allocation
+                /// is freed and the error returned.
+                ///
+                /// # Safety
+                ///
+                /// `f` must initialize the value it is handed, unless it fails.
+                pub unsafe fn try_emplace<E, F>(f: F) -> Result<Self, E>
+                where
+                    F: FnOnce(&mut std::mem::MaybeUninit<T>) -> Result<(), E>,
+                {
+                    let ptr = SnarcBox::<T>::new_uninit_ptr();
+
+                    let release = $crate::scopeguard::guard((), |_| {
+                        SnarcBox::release(ptr);
+                    });
+                    f(&mut *SnarcBox::value(ptr).cast::<std::mem::MaybeUninit<T>>())?;
+                    $crate::scopeguard::ScopeGuard::into_inner(release);
+
+                    Ok(Self {
+                        ptr,
+                        phantom: std::marker::PhantomData,
+                    })
+                }
+
+                /// Creates a new `
+                #[doc = stringify!($send)]
+                /// ` whose inner value is returned by `f`, which is handed a weak
+                /// reference to it.
+                ///
+                /// `f` is evaluated within the `enter` context, so the reference
+                /// may be cloned, but it does not resolve until `f` returns.
+                pub fn new_cyclic<F>(f: F) -> Self
+                where
+                    F: FnOnce(&$ref<T>) -> T,
+                {
+                    let ptr = SnarcBox::<T>::new_uninit_ptr();
+
+                    // references minted before the value is written resolve
+                    // once the generations match again
+                    SnarcBox::set_generation(ptr, 1);
+                    SnarcBox::increment(ptr);
+
+                    let weak = $ref {
+                        ptr,
+                        generation: 0,
+                        phantom: Default::default(),
+                    };
+
+                    let state = SnarcBox::state(ptr);
+                    SnarcBox::set_state(ptr, State::Entered);
+
+                    let release = $crate::scopeguard::guard_on_unwind((), |_| {
+                        SnarcBox::release(ptr);
+                    });
+
+                    let value = {
+                        let weak = $crate::scopeguard::guard(weak, |weak| {
+                            drop(weak);
+                            SnarcBox::set_state(ptr, state);
+                        });
+
+                        f(&weak)
+                    };
+
+                    $crate::scopeguard::ScopeGuard::into_inner(release);
+
+                    unsafe { SnarcBox::init(ptr, value) };
+                    SnarcBox::set_generation(ptr, 0);
+
+                    Self {
+                        ptr,
+                        phantom: std::marker::PhantomData,
+                    }
+                }
+
+                /// Turn this `
+                #[doc = stringify!($send)]
+                /// ` into the `!Send` version `
+                #[doc = stringify!($unsend)]
+                /// `.
+                pub fn into_unsend(mut self) -> $unsend<T> {
+                    let narc = $unsend {
+                        ptr: self.ptr,
+                        phantom: self.phantom,
+                    };
+
+                    self.ptr = ptr::null_mut();
+
+                    narc
+                }
+
+                /// Puts this `
+                #[doc = stringify!($send)]
+                /// ` to rest, keeping the inner value and weak references to it
+                /// alive, see `Dormant`.
+                pub fn into_dormant(self) -> $crate::Dormant<Self> {
+                    $crate::Dormant::new(self)
+                }
+
+                /// Turn this parameterized `
+                #[doc = stringify!($send)]
+                /// ` the unparameterized `ErasedSnarc`.
+                pub fn into_erased(self) -> $crate::ErasedSnarc
+                where
+                    T: Send + 'static,
+                {
+                    $crate::ErasedSnarc::from($crate::ErasedOwner::new(self))
+                }
+
+                /// Like `into_erased`, but for values that borrow for `'a`.
+                ///
+                /// The inner value of the resulting `ScopedErasedSnarc` can
+                /// neither be accessed nor downcast, even if `'a` is `'static`.
+                pub fn into_erased_scoped<'a>(self) -> $crate::ScopedErasedSnarc<'a>
+                where
+                    T: Send + 'a,
+                {
+                    $crate::ScopedErasedSnarc::from($crate::ErasedOwner::new_scoped(self))
+                }
+
+                /// Splits the inner value into the two disjoint parts returned
+                /// by `f`.
+                ///
+                /// The parts share this `
+                #[doc = stringify!($send)]
+                /// `'s allocation, the inner value is dropped once both of them
+                /// are. They are turned back into an owner by `join`.
+                pub fn map_split<A, B>(
+                    self,
+                    f: fn(&mut T) -> (&mut A, &mut B),
+                ) -> ($crate::SnarcPart<A>, $crate::SnarcPart<B>)
+                where
+                    T: Send + 'static,
+                {
+                    // the inner value is boxed, so it does not move along with
+                    // `self`
+                    unsafe { $crate::SnarcPart::split(self, f) }
+                }
+
+                /// Joins the parts returned by `map_split` back into the `
+                #[doc = stringify!($send)]
+                /// ` they were split from.
+                ///
+                /// Returns the parts back if they were split from different
+                /// owners.
+                #[allow(clippy::type_complexity)]
+                pub fn join<A, B>(
+                    a: $crate::SnarcPart<A>,
+                    b: $crate::SnarcPart<B>,
+                ) -> Result<Self, ($crate::SnarcPart<A>, $crate::SnarcPart<B>)>
+                where
+                    T: Send + 'static,
+                {
+                    $crate::SnarcPart::join(a, b)
+                }
+
+                #[inline]
+                unsafe fn get_mut_unchecked(this: &mut Self) -> &mut T {
+                    SnarcBox::mark_dirty(this.ptr);
+                    SnarcBox::lock_snapshots(this.ptr);
+                    &mut *SnarcBox::value(this.ptr)
+                }
+
+                /// Returns a mutable reference to the inner value if there are no
+                /// weak references to it, like `Arc::get_mut`.
+                pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+                    if this.is_unique() {
+                        Some(unsafe { Self::get_mut_unchecked(this) })
+                    } else {
+                        None
+                    }
+                }
+
+                /// Returns the inner value if there are no weak references to it,
+                /// otherwise `this` is returned unchanged.
+                pub fn try_unwrap(this: Self) -> Result<T, Self> {
+                    if !this.is_unique() {
+                        return Err(this);
+                    }
+
+                    let this = std::mem::ManuallyDrop::new(this);
+                    let value = unsafe { SnarcBox::value(this.ptr).read() };
+                    unsafe { $crate::scrub(SnarcBox::value(this.ptr)) };
+                    SnarcBox::release(this.ptr);
+
+                    Ok(value)
+                }
+
+                /// Returns the inner value if there are no weak references to it,
+                /// otherwise `this` is dropped.
+                pub fn into_inner(this: Self) -> Option<T> {
+                    Self::try_unwrap(this).ok()
+                }
+
+                /// Consumes and leaks `this`, returning a reference to the inner
+                /// value, like `Box::leak`.
+                ///
+                /// Existing weak references are revoked, so that the value cannot
+                /// be accessed elsewhere. The value is never dropped, so it is
+                /// not scrubbed given the `zeroize` feature.
+                pub fn leak<'a>(this: Self) -> &'a T
+                where
+                    T: 'a,
+                {
+                    SnarcBox::mark_dropped(this.ptr);
+                    SnarcBox::revoke(this.ptr);
+
+                    let this = std::mem::ManuallyDrop::new(this);
+                    unsafe { &*SnarcBox::value(this.ptr) }
+                }
+
+                /// Replaces the inner value, returning the old one.
+                ///
+                /// Weak references remain valid and resolve to `value`.
+                pub fn replace(this: &mut Self, value: T) -> T {
+                    std::mem::replace(&mut **this, value)
+                }
+
+                /// Takes the inner value, leaving `T::default()` in its place.
+                pub fn take(this: &mut Self) -> T
+                where
+                    T: Default,
+                {
+                    std::mem::take(&mut **this)
+                }
+
+                /// Swaps the inner values of `this` and `other`.
+                ///
+                /// Weak references keep pointing to their owner, so they resolve to
+                /// the other value afterwards.
+                pub fn swap(this: &mut Self, other: &mut Self) {
+                    std::mem::swap(&mut **this, &mut **other)
+                }
+
+                /// Transforms the inner value with `f`, reusing the allocation if
+                /// `T` and `U` share a layout.
+                ///
+                /// Panics if there are weak references to the inner value, see
+                /// `is_unique`.
+                pub fn map<U, F>(this: Self, f: F) -> $send<U>
+                where
+                    F: FnOnce(T) -> U,
+                {
+                    if !this.is_unique() {
+                        $crate::__panic!(
+                            "{}::map() while references remain",
+                            stringify!($send)
+                        )
+                    }
+
+                    if std::alloc::Layout::new::<T>() != std::alloc::Layout::new::<U>() {
+                        let value = Self::try_unwrap(this).ok().unwrap();
+                        return $send::new(f(value));
+                    }
+
+                    let this = std::mem::ManuallyDrop::new(this);
+                    let ptr = this.ptr;
+
+                    let release = $crate::scopeguard::guard_on_unwind((), |_| {
+                        SnarcBox::release(ptr);
+                    });
+                    let value = unsafe { SnarcBox::value(ptr).read() };
+                    unsafe { $crate::scrub(SnarcBox::value(ptr)) };
+                    let value = f(value);
+                    $crate::scopeguard::ScopeGuard::into_inner(release);
+
+                    // queued tasks expect a `T`, so they are dropped like they
+                    // would be along with the allocation
+                    SnarcBox::clear_deferred(ptr);
+
+                    // the control blocks are `repr(C)`, so they share a layout too
+                    let ptr = ptr.cast::<SnarcBox<U>>();
+                    unsafe { SnarcBox::init(ptr, value) };
+
+                    $send {
+                        ptr,
+                        phantom: std::marker::PhantomData,
+                    }
+                }
+
+                /// Drops the inner value right away, weak references remain valid
+                /// but no longer resolve.
+                ///
+                /// This releases the resources held by the value even while weak
+                /// references remain. Afterwards, `this` may only be dropped, any
+                /// other use panics, see `is_closed`.
+                pub fn close(this: &mut Self) {
+                    if !this.ptr.is_null() {
+                        // closed up front, so that the value is not dropped
+                        // twice should its destructor panic
+                        let ptr = std::mem::replace(&mut this.ptr, ptr::null_mut());
+
+                        SnarcBox::check(ptr);
+
+                        {
+                            // the context may be entered already, e.g., if this
+                            // is owned by another value that is being dropped
+                            let state = SnarcBox::state(ptr);
+                            SnarcBox::set_state(ptr, State::Entered);
+
+                            let _guard = $crate::scopeguard::guard(state, |state| {
+                                SnarcBox::set_state(ptr, state);
+                            });
+
+                            SnarcBox::close_snapshots(ptr);
+
+                            unsafe {
+                                // destroy the contained object
+                                ptr::drop_in_place(SnarcBox::value(ptr));
+                                $crate::scrub(SnarcBox::value(ptr));
+                            }
+
+                            // the tasks and messages may hold references,
+                            // which are best dropped bound
+                            SnarcBox::clear_deferred(ptr);
+                        }
+
+                        // the context may remain entered, so the remaining
+                        // weak references must not resolve anymore
+                        SnarcBox::mark_dropped(ptr);
+                        SnarcBox::revoke(ptr);
+                        SnarcBox::release(ptr);
+                    }
+                }
+
+                /// Returns `true` if `this` was closed, see `close`.
+                pub fn is_closed(this: &Self) -> bool {
+                    this.ptr.is_null()
+                }
+
+                /// Returns `true` if `this` and `other` point to the same allocation, like
+                /// `Rc::ptr_eq`.
+                ///
+                /// Zero-sized values are not allocated for, so their owners always compare
+                /// equal, unless `per_instance;` is given.
+                pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+                    this.ptr == other.ptr
+                }
+
+                /// Returns `true` if there are no weak references to the inner value.
+                ///
+                /// Zero-sized values are never unique, unless `per_instance;` is
+                /// given.
+                pub fn is_unique(&self) -> bool {
+                    !SnarcBox::has_refs(self.ptr)
+                }
+
+                /// Returns `true` if there are weak references to the inner value.
+                pub fn has_refs(&self) -> bool {
+                    SnarcBox::has_refs(self.ptr)
+                }
+
+                /// Returns the number of weak references to the inner value.
+                ///
+                /// Zero-sized values are not counted, so their count is always
+                /// zero, unless `per_instance;` is given.
+                pub fn ref_count(&self) -> usize {
+                    SnarcBox::ref_count(self.ptr)
+                }
+
+                /// Returns `true` if the inner value is bound to the current thread,
+                /// i.e., within `enter`.
+                ///
+                /// Unless `per_instance;` is given, this is the case while any value
+                /// of this context is entered.
+                pub fn is_entered(&self) -> bool {
+                    SnarcBox::check(self.ptr);
+                    SnarcBox::state(self.ptr) == State::Entered
+                }
+
+                /// Returns the version of the inner value, which is incremented by
+                /// every mutable access.
+                pub fn version(&self) -> u64 {
+                    SnarcBox::version(self.ptr)
+                }
+
+                /// Creates a new `
+                #[doc = stringify!($send)]
+                /// ` whose inner `value` is pinned, it will not be moved until it
+                /// is dropped.
+                pub fn pin(value: T) -> std::pin::Pin<Self> {
+                    // the value is only ever moved by way of `&mut Self` or `Self`,
+                    // neither of which `Pin` hands out
+                    unsafe { std::pin::Pin::new_unchecked(Self::new(value)) }
+                }
+
+                /// Returns the pinned owner, e.g., to `enter` it.
+                ///
+                /// This is sound because none of the methods taking `&self` move
+                /// the inner value.
+                pub fn pinned_owner(this: &std::pin::Pin<Self>) -> &Self {
+                    // `Pin` is `repr(transparent)`
+                    unsafe { &*(this as *const std::pin::Pin<Self>).cast::<Self>() }
+                }
+
+                /// Creates a new non-owning reference to the pinned inner value.
+                pub fn new_pinned_ref(this: &std::pin::Pin<Self>) -> $crate::PinnedRef<$ref<T>> {
+                    let new_ref = Self::pinned_owner(this).new_ref();
+                    unsafe { $crate::PinnedRef::new_unchecked(new_ref) }
+                }
+
+                /// Creates a new non-owning reference to the inner value.
+                pub fn new_ref(&self) -> $ref<T> {
+                    SnarcBox::increment(self.ptr);
+                    $crate::trace_new_ref(self.ptr.cast());
+
+                    $ref {
+                        ptr: self.ptr,
+                        generation: SnarcBox::generation(self.ptr),
+                        phantom: Default::default(),
+                    }
+                }
+
+                /// Creates a new non-owning reference that grants mutable access
+                /// to the inner value, see `SnarcRefMut`.
+                pub fn new_ref_mut(&self) -> $crate::SnarcRefMut<$ref<T>> {
+                    $crate::SnarcRefMut::new(self.new_ref())
+                }
+
+                /// Revokes all existing weak references, they will never again
+                /// resolve.
+                ///
+                /// References created afterwards are unaffected. Zero-sized
+                /// values cannot be revoked, unless `per_instance;` is given.
+                pub fn revoke_refs(&mut self) {
+                    SnarcBox::revoke(self.ptr);
+                }
+
+                /// Temporarily bind the inner value to this thread and evaluate `f`
+                /// within that context.
+                ///
+                /// Values may be entered again from within `f`. Panics if this
+                /// value is entered on another thread, or if this context was
+                /// entered by way of an erased owner on this thread.
+                ///
+                /// The value is not recorded as entered, so neither `self_ref`
+                /// nor `with_current` can observe it, see `enter_with_self`.
+                pub fn enter<F, R>(&self, f: F) -> R
+                where
+                    F: FnOnce(&T) -> R,
+                {
+                    match self.try_enter(f) {
+                        Ok(r) => r,
+                        Err($crate::AlreadyEntered) => Self::already_entered(),
+                    }
+                }
+
+                /// Like `enter`, but fails instead of panicking if the context is
+                /// already entered.
+                pub fn try_enter<F, R>(&self, f: F) -> Result<R, $crate::AlreadyEntered>
+                where
+                    F: FnOnce(&T) -> R,
+                {
+                    self.try_enter_recorded(|ptr| $crate::record_entered_untyped(ptr.cast()), f)
+                }
+
+                /// Like `enter`, but records the value as entered, so that
+                /// `self_ref` and `with_current` observe it within `f`.
+                pub fn enter_with_self<F, R>(&self, f: F) -> R
+                where
+                    T: 'static,
+                    F: FnOnce(&T) -> R,
+                {
+                    let record = |ptr: *mut SnarcBox<T>| {
+                        $crate::record_entered(
+                            std::any::TypeId::of::<SnarcBox<T>>(),
+                            ptr.cast(),
+                            std::any::TypeId::of::<T>(),
+                            SnarcBox::value(ptr).cast(),
+                        )
+                    };
+
+                    match self.try_enter_recorded(record, f) {
+                        Ok(r) => r,
+                        Err($crate::AlreadyEntered) => Self::already_entered(),
+                    }
+                }
+
+                fn try_enter_recorded<E, F, R>(
+                    &self,
+                    record: E,
+                    f: F,
+                ) -> Result<R, $crate::AlreadyEntered>
+                where
+                    E: FnOnce(*mut SnarcBox<T>) -> $crate::EnteredRecord,
+                    F: FnOnce(&T) -> R,
+                {
+                    let ptr = self.ptr;
+
+                    SnarcBox::check(ptr);
+
+                    if !SnarcBox::try_bind(ptr) {
+                        return Err($crate::AlreadyEntered);
+                    }
+
+                    let _guard = $crate::scopeguard::guard((), |_| {
+                        SnarcBox::unbind(ptr);
+                    });
+
+                    let _entered = record(ptr);
+
+                    SnarcBox::notify(ptr);
+                    SnarcBox::drain(ptr);
+
+                    Ok(f(unsafe { &*SnarcBox::value(ptr) }))
+                }
+
+                fn already_entered() -> ! {
+                    $crate::__panic!(
+                        "Another {} is already entered.",
+                        stringify!($send)
+                    )
+                }
+
+                /// Like `enter`, but also hands `f` the messages sent by weak
+                /// references since, see `send`.
+                ///
+                /// Panics if messages of another type were sent.
+                pub fn enter_with_messages<M, F, R>(&self, f: F) -> R
+                where
+                    M: Send + 'static,
+                    F: FnOnce(&T, Vec<M>) -> R,
+                {
+                    self.enter(|value| f(value, SnarcBox::take_messages(self.ptr)))
+                }
+
+                /// Like `enter`, but grants mutable access to the inner value.
+                ///
+                /// Returns `None` if there are weak references to the inner
+                /// value. The value is not recorded as entered, so neither
+                /// `self_ref` nor `with_current` can observe it.
+                pub fn enter_mut<F, R>(&mut self, f: F) -> Option<R>
+                where
+                    F: FnOnce(&mut T) -> R,
+                {
+                    if !self.is_unique() {
+                        return None;
+                    }
+
+                    let ptr = self.ptr;
+
+                    SnarcBox::check(ptr);
+
+                    if !SnarcBox::try_bind(ptr) {
+                        $crate::__panic!(
+                            "Another {} is already entered.",
+                            stringify!($send)
+                        )
+                    }
+
+                    let _guard = $crate::scopeguard::guard((), |_| {
+                        SnarcBox::unbind(ptr);
+                    });
+
+                    SnarcBox::notify(ptr);
+                    SnarcBox::drain(ptr);
+
+                    // without weak references, only `f` can access the value
+                    Some(f(unsafe { Self::get_mut_unchecked(self) }))
+                }
+
+                /// Temporarily bind the inner value to this thread and invoke all
+                /// of `callbacks`' handlers within that context.
+                pub fn dispatch<Args>(
+                    &mut self,
+                    callbacks: &$crate::CallbackSet<Args>,
+                    args: &Args,
+                ) {
+                    self.enter(|_| callbacks.invoke(args))
+                }
+
+                /// Wraps `fut` so that every poll happens within the `enter`
+                /// context.
+                ///
+                /// The returned future borrows `self`, so the owner cannot be
+                /// dropped before the future completes or is dropped itself.
+                pub async fn scoped_future<F>(&mut self, fut: F) -> F::Output
+                where
+                    F: std::future::Future,
+                {
+                    let mut fut = std::pin::pin!(fut);
+                    std::future::poll_fn(|cx| self.enter(|_| fut.as_mut().poll(cx))).await
+                }
+
+                /// Evaluates the asynchronous teardown of the inner value within
+                /// the `enter` context, then drops it.
+                pub async fn dispose_async(self)
+                where
+                    T: $crate::AsyncDispose,
+                {
+                    // the future borrows the value by way of `self`, which outlives it
+                    let mut fut = std::pin::pin!((*self).dispose());
+                    std::future::poll_fn(|cx| self.enter(|_| std::future::Future::poll(fut.as_mut(), cx))).await;
+                }
+            }
+
+            impl<A, B> $send<(A, B)> {
+                /// Combines the inner values of `a` and `b`, so that they can be
+                /// entered together.
+                ///
+                /// Returns `a` and `b` back if there are weak references to
+                /// either value, see `is_unique`.
+                pub fn zip(a: $send<A>, b: $send<B>) -> Result<Self, ($send<A>, $send<B>)> {
+                    // both are checked up front, so that neither value is
+                    // unwrapped in vain
+                    if !a.is_unique() || !b.is_unique() {
+                        return Err((a, b));
+                    }
+
+                    let (Ok(a), Ok(b)) = ($send::try_unwrap(a), $send::try_unwrap(b)) else {
+                        unreachable!("both owners are unique")
+                    };
+
+                    Ok(Self::new((a, b)))
+                }
+            }
+
+            impl<T> $send<T> {
+                /// Creates a new `
+                #[doc = stringify!($send)]
+                /// ` with uninitialized contents, like `Arc::new_uninit`.
+                pub fn new_uninit() -> $send<std::mem::MaybeUninit<T>> {
+                    $send {
+                        ptr: SnarcBox::new_uninit_ptr(),
+                        phantom: std::marker::PhantomData,
+                    }
+                }
+
+                /// Creates a new `
+                #[doc = stringify!($send)]
+                /// ` with uninitialized contents, whose bytes are all zeros, like
+                /// `Arc::new_zeroed`.
+                pub fn new_zeroed() -> $send<std::mem::MaybeUninit<T>> {
+                    let ptr = SnarcBox::<std::mem::MaybeUninit<T>>::new_uninit_ptr();
+                    unsafe { SnarcBox::value(ptr).write_bytes(0, 1) };
+
+                    $send {
+                        ptr,
+                        phantom: std::marker::PhantomData,
+                    }
+                }
+            }
+
+            impl<T> $send<std::mem::MaybeUninit<T>> {
+
+                /// Converts to `
+                #[doc = stringify!($send)]
+                /// <T>`, like `Arc::assume_init`.
+                ///
+                /// Weak references remain typed `MaybeUninit<T>`.
+                ///
+                /// # Safety
+                ///
+                /// The inner value must be initialized.
+                pub unsafe fn assume_init(self) -> $send<T> {
+                    let this = std::mem::ManuallyDrop::new(self);
+
+                    // the control blocks are `repr(C)` and `MaybeUninit<T>` shares
+                    // the layout of `T`
+                    $send {
+                        ptr: this.ptr.cast(),
+                        phantom: std::marker::PhantomData,
+                    }
+                }
+            }
+
+            impl<T: Send + 'static> From<$send<T>> for $crate::ErasedSnarc {
+                fn from(snarc: $send<T>) -> Self {
+                    snarc.into_erased()
+                }
+            }
+
+            impl<T: Send + Sync + 'static> From<$send<T>> for $crate::SyncErasedSnarc {
+                fn from(snarc: $send<T>) -> Self {
+                    $crate::SyncErasedSnarc::new(snarc)
+                }
+            }
+
+            impl<T: Send + 'static> From<$send<T>> for $crate::ErasedNarc {
+                fn from(snarc: $send<T>) -> Self {
+                    snarc.into_unsend().into_erased()
+                }
+            }
+
+            unsafe impl<T> Context for $send<T> {
+                type Value = T;
+
+                fn into_raw(self) -> *mut () {
+                    std::mem::ManuallyDrop::new(self).ptr.cast()
+                }
+
+                unsafe fn from_raw(ptr: *mut ()) -> Self {
+                    Self {
+                        ptr: ptr.cast(),
+                        phantom: std::marker::PhantomData,
+                    }
+                }
+
+                fn set(&mut self, v: State) {
+                    if v == State::Entered && SnarcBox::state(self.ptr) == State::Entered {
+                        $crate::__panic!(
+                            "Another {} is already entered.",
+                            stringify!($send)
+                        )
+                    }
+
+                    SnarcBox::set_state(self.ptr, v);
+                }
+
+                fn value(&self) -> *const T {
+                    SnarcBox::value(self.ptr)
+                }
+
+                fn type_name(&self) -> &'static str {
+                    std::any::type_name::<T>()
+                }
+
+                fn is_entered(&self) -> bool {
+                    $send::is_entered(self)
+                }
+            }
+
+            impl<T> Deref for $send<T> {
+                type Target = T;
+
+                #[inline(always)]
+                fn deref(&self) -> &Self::Target {
+                    unsafe { &*SnarcBox::value(self.ptr) }
+                }
+            }
+
+            impl<T> DerefMut for $send<T> {
+                #[inline(always)]
+                fn deref_mut(&mut self) -> &mut Self::Target {
+                    unsafe { Self::get_mut_unchecked(self) }
+                }
+            }
+
+            impl<T> Drop for $send<T> {
+                fn drop(&mut self) {
+                    Self::close(self);
+                }
+            }
+
+            /// A non-sendable, owning reference-counted pointer to a `T`.
+            ///
+            /// When `
+            #[doc = stringify!($unsend)]
+            /// ` is used exclusively, i.e., never `
+            #[doc = stringify!($send)]
+            /// `, then `Rc` should
+            /// likely be used instead.
+            ///
+            /// Like `
+            #[doc = stringify!($send)]
+            /// `, it binds the inner value only within `enter`. Only the
+            /// erased counterparts, which cannot access the value themselves,
+            /// keep it bound for their whole lifetime.
+            pub struct $unsend<T> {
+                ptr: *mut SnarcBox<T>,
+                phantom: std::marker::PhantomData<SnarcBox<T>>,
+            }
+
+            unsafe impl<T: Sync> Sync for $unsend<T> {}
+
+            impl<T> $unsend<T> {
+                /// Creates a new `
+                #[doc = stringify!($unsend)]
+                /// ` with the given inner `value`.
+                pub fn new(value: T) -> Self {
+                    Self {
+                        ptr: SnarcBox::new_ptr(value),
+                        phantom: std::marker::PhantomData,
+                    }
+                }
+
+                /// Creates a new `
+                #[doc = stringify!($unsend)]
+                /// ` with the given inner `value`, failing instead of aborting if
+                /// memory cannot be allocated.
+                pub fn try_new(value: T) -> Result<Self, $crate::AllocError> {
+                    let ptr = SnarcBox::try_new_uninit_ptr()?;
+                    unsafe { SnarcBox::init(ptr, value) };
+
+                    Ok(Self {
+                        ptr,
+                        phantom: std::marker::PhantomData,
+                    })
+                }
+
+                /// Creates a new `
+                #[doc = stringify!($unsend)]
+                /// ` with the inner value returned by `f`, or fails with its
+                /// error.
+                pub fn try_new_with<E, F>(f: F) -> Result<Self, E>
+                where
+                    F: FnOnce() -> Result<T, E>,
+                {
+                    f().map(Self::new)
+                }
+
+                /// Turn this `
+                #[doc = stringify!($unsend)]
+                /// ` into the `Send` version `
+                #[doc = stringify!($send)]
+                /// `.
+                pub fn into_send(mut self) -> $send<T> {
+                    let snarc = $send {
+                        ptr: self.ptr,
+                        phantom: self.phantom,
+                    };
+
+                    self.ptr = ptr::null_mut();
+
+                    snarc
+                }
+
+                /// Turn this parameterized `
+                #[doc = stringify!($unsend)]
+                /// ` the unparameterized `ErasedNarc`.
+                pub fn into_erased(self) -> $crate::ErasedNarc
+                where
+                    T: Send + 'static,
+                {
+                    self.into_send().into_erased().into_unsend()
+                }
+
+                /// Like `into_erased`, but for values that are not `Send`.
+                ///
+                /// The resulting `ErasedNarc` cannot be turned into an
+                /// `ErasedSnarc`, see `ErasedNarc::try_into_send`.
+                pub fn into_erased_unsend(self) -> $crate::ErasedNarc
+                where
+                    T: 'static,
+                {
+                    $crate::ErasedNarc::from_unsend(self.into_send())
+                }
+
+                #[inline]
+                unsafe fn get_mut_unchecked(this: &mut Self) -> &mut T {
+                    SnarcBox::mark_dirty(this.ptr);
+                    SnarcBox::lock_snapshots(this.ptr);
+                    &mut *SnarcBox::value(this.ptr)
+                }
+
+                /// Returns a mutable reference to the inner value if there are no
+                /// weak references to it, like `Arc::get_mut`.
+                pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+                    if this.is_unique() {
+                        Some(unsafe { Self::get_mut_unchecked(this) })
+                    } else {
+                        None
+                    }
+                }
+
+                /// Returns the inner value if there are no weak references to it,
+                /// otherwise `this` is returned unchanged.
+                pub fn try_unwrap(this: Self) -> Result<T, Self> {
+                    if !this.is_unique() {
+                        return Err(this);
+                    }
+
+                    let this = std::mem::ManuallyDrop::new(this);
+                    let value = unsafe { SnarcBox::value(this.ptr).read() };
+                    unsafe { $crate::scrub(SnarcBox::value(this.ptr)) };
+                    SnarcBox::release(this.ptr);
+
+                    Ok(value)
+                }
+
+                /// Returns the inner value if there are no weak references to it,
+                /// otherwise `this` is dropped.
+                pub fn into_inner(this: Self) -> Option<T> {
+                    Self::try_unwrap(this).ok()
+                }
+
+                /// Consumes and leaks `this`, returning a reference to the inner
+                /// value, like `Box::leak`.
+                ///
+                /// Existing weak references are revoked, so that the value cannot
+                /// be accessed elsewhere. The value is never dropped, so it is
+                /// not scrubbed given the `zeroize` feature.
+                pub fn leak<'a>(this: Self) -> &'a T
+                where
+                    T: 'a,
+                {
+                    SnarcBox::mark_dropped(this.ptr);
+                    SnarcBox::revoke(this.ptr);
+
+                    let this = std::mem::ManuallyDrop::new(this);
+                    unsafe { &*SnarcBox::value(this.ptr) }
+                }
+
+                /// Replaces the inner value, returning the old one.
+                ///
+                /// Weak references remain valid and resolve to `value`.
+                pub fn replace(this: &mut Self, value: T) -> T {
+                    std::mem::replace(&mut **this, value)
+                }
+
+                /// Takes the inner value, leaving `T::default()` in its place.
+                pub fn take(this: &mut Self) -> T
+                where
+                    T: Default,
+                {
+                    std::mem::take(&mut **this)
+                }
+
+                /// Swaps the inner values of `this` and `other`.
+                ///
+                /// Weak references keep pointing to their owner, so they resolve to
+                /// the other value afterwards.
+                pub fn swap(this: &mut Self, other: &mut Self) {
+                    std::mem::swap(&mut **this, &mut **other)
+                }
+
+                /// Transforms the inner value with `f`, reusing the allocation if
+                /// `T` and `U` share a layout.
+                ///
+                /// Panics if there are weak references to the inner value, see
+                /// `is_unique`.
+                pub fn map<U, F>(this: Self, f: F) -> $unsend<U>
+                where
+                    F: FnOnce(T) -> U,
+                {
+                    if !this.is_unique() {
+                        $crate::__panic!(
+                            "{}::map() while references remain",
+                            stringify!($unsend)
+                        )
+                    }
+
+                    if std::alloc::Layout::new::<T>() != std::alloc::Layout::new::<U>() {
+                        let value = Self::try_unwrap(this).ok().unwrap();
+                        return $unsend::new(f(value));
+                    }
+
+                    let this = std::mem::ManuallyDrop::new(this);
+                    let ptr = this.ptr;
+
+                    let release = $crate::scopeguard::guard_on_unwind((), |_| {
+                        SnarcBox::release(ptr);
+                    });
+                    let value = unsafe { SnarcBox::value(ptr).read() };
+                    unsafe { $crate::scrub(SnarcBox::value(ptr)) };
+                    let value = f(value);
+                    $crate::scopeguard::ScopeGuard::into_inner(release);
+
+                    // queued tasks expect a `T`, so they are dropped like they
+                    // would be along with the allocation
+                    SnarcBox::clear_deferred(ptr);
+
+                    // the control blocks are `repr(C)`, so they share a layout too
+                    let ptr = ptr.cast::<SnarcBox<U>>();
+                    unsafe { SnarcBox::init(ptr, value) };
+
+                    $unsend {
+                        ptr,
+                        phantom: std::marker::PhantomData,
+                    }
+                }
+
+                /// Drops the inner value right away, weak references remain valid
+                /// but no longer resolve.
+                ///
+                /// This releases the resources held by the value even while weak
+                /// references remain. Afterwards, `this` may only be dropped, any
+                /// other use panics, see `is_closed`.
+                pub fn close(this: &mut Self) {
+                    if !this.ptr.is_null() {
+                        // closed up front, so that the value is not dropped
+                        // twice should its destructor panic
+                        let ptr = std::mem::replace(&mut this.ptr, ptr::null_mut());
+
+                        SnarcBox::check(ptr);
+
+                        {
+                            // the context may be entered already, e.g., if this
+                            // is owned by another value that is being dropped
+                            let state = SnarcBox::state(ptr);
+                            SnarcBox::set_state(ptr, State::Entered);
+
+                            let _guard = $crate::scopeguard::guard(state, |state| {
+                                SnarcBox::set_state(ptr, state);
+                            });
+
+                            SnarcBox::close_snapshots(ptr);
+
+                            unsafe {
+                                // destroy the contained object
+                                ptr::drop_in_place(SnarcBox::value(ptr));
+                                $crate::scrub(SnarcBox::value(ptr));
+                            }
+
+                            // the tasks and messages may hold references,
+                            // which are best dropped bound
+                            SnarcBox::clear_deferred(ptr);
+                        }
+
+                        // the context may remain entered, so the remaining
+                        // weak references must not resolve anymore
+                        SnarcBox::mark_dropped(ptr);
+                        SnarcBox::revoke(ptr);
+                        SnarcBox::release(ptr);
+                    }
+                }
+
+                /// Returns `true` if `this` was closed, see `close`.
+                pub fn is_closed(this: &Self) -> bool {
+                    this.ptr.is_null()
+                }
+
+                /// Returns `true` if `this` and `other` point to the same allocation, like
+                /// `Rc::ptr_eq`.
+                ///
+                /// Zero-sized values are not allocated for, so their owners always compare
+                /// equal, unless `per_instance;` is given.
+                pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+                    this.ptr == other.ptr
+                }
+
+                /// Returns `true` if there are no weak references to the inner value.
+                ///
+                /// Zero-sized values are never unique, unless `per_instance;` is
+                /// given.
+                pub fn is_unique(&self) -> bool {
+                    !SnarcBox::has_refs(self.ptr)
+                }
+
+                /// Returns `true` if there are weak references to the inner value.
+                pub fn has_refs(&self) -> bool {
+                    SnarcBox::has_refs(self.ptr)
+                }
+
+                /// Returns the number of weak references to the inner value.
+                ///
+                /// Zero-sized values are not counted, so their count is always
+                /// zero, unless `per_instance;` is given.
+                pub fn ref_count(&self) -> usize {
+                    SnarcBox::ref_count(self.ptr)
+                }
+
+                /// Returns the version of the inner value, which is incremented by
+                /// every mutable access.
+                pub fn version(&self) -> u64 {
+                    SnarcBox::version(self.ptr)
+                }
+
+                /// Creates a new non-owning reference to the inner value.
+                pub fn new_ref(&self) -> $ref<T> {
+                    SnarcBox::increment(self.ptr);
+                    $crate::trace_new_ref(self.ptr.cast());
+
+                    $ref {
+                        ptr: self.ptr,
+                        generation: SnarcBox::generation(self.ptr),
+                        phantom: Default::default(),
+                    }
+                }
+
+                /// Creates a new non-owning reference that grants mutable access
+                /// to the inner value, see `SnarcRefMut`.
+                pub fn new_ref_mut(&self) -> $crate::SnarcRefMut<$ref<T>> {
+                    $crate::SnarcRefMut::new(self.new_ref())
+                }
+
+                /// Revokes all existing weak references, they will never again
+                /// resolve.
+                ///
+                /// References created afterwards are unaffected. Zero-sized
+                /// values cannot be revoked, unless `per_instance;` is given.
+                pub fn revoke_refs(&mut self) {
+                    SnarcBox::revoke(self.ptr);
+                }
+
+                /// Temporarily bind the inner value to this thread and evaluate `f`
+                /// within that context, like `
+                #[doc = stringify!($send)]
+                /// ::enter`.
+                ///
+                /// Outside of `enter`, weak references do not resolve, since the
+                /// inner value may be mutated by way of `DerefMut`.
+                pub fn enter<F, R>(&self, f: F) -> R
+                where
+                    F: FnOnce(&T) -> R,
+                {
+                    self.as_send().enter(f)
+                }
+
+                /// Like `enter`, but records the value as entered, like `
+                #[doc = stringify!($send)]
+                /// ::enter_with_self`.
+                pub fn enter_with_self<F, R>(&self, f: F) -> R
+                where
+                    T: 'static,
+                    F: FnOnce(&T) -> R,
+                {
+                    self.as_send().enter_with_self(f)
+                }
+
+                fn as_send(&self) -> std::mem::ManuallyDrop<$send<T>> {
+                    // merely borrows the allocation, which `self` keeps owning
+                    std::mem::ManuallyDrop::new($send {
+                        ptr: self.ptr,
+                        phantom: self.phantom,
+                    })
+                }
+            }
+
+            impl<T: Send + 'static> From<$unsend<T>> for $crate::ErasedSnarc {
+                fn from(narc: $unsend<T>) -> Self {
+                    narc.into_send().into_erased()
+                }
+            }
+
+            impl<T: Send + 'static> From<$unsend<T>> for $crate::ErasedNarc {
+                fn from(narc: $unsend<T>) -> Self {
+                    narc.into_erased()
+                }
+            }
+
+            impl<T> Deref for $unsend<T> {
+                type Target = T;
+
+                #[inline(always)]
+                fn deref(&self) -> &Self::Target {
+                    unsafe { &*SnarcBox::value(self.ptr) }
+                }
+            }
+
+            impl<T> DerefMut for $unsend<T> {
+                #[inline(always)]
+                fn deref_mut(&mut self) -> &mut Self::Target {
+                    unsafe { Self::get_mut_unchecked(self) }
+                }
+            }
+
+            impl<T> Drop for $unsend<T> {
+                fn drop(&mut self) {
+                    Self::close(self);
+                }
+            }
+
+            pub struct $ref<T> {
+                ptr: *mut SnarcBox<T>,
+                generation: u32,
+                phantom: std::marker::PhantomData<SnarcBox<T>>,
+            }
+
+            unsafe impl<T> Send for $ref<T> {}
+            unsafe impl<T> Sync for $ref<T> {}
+
+            impl<T> $ref<T> {
+                /// Creates a reference without an owner, like `Weak::new`. It
+                /// never resolves and is dangling from the start.
+                ///
+                /// This allows for structs to hold a reference before the owner
+                /// exists.
+                pub fn new() -> Self {
+                    let ptr = SnarcBox::<T>::new_uninit_ptr();
+
+                    SnarcBox::mark_dropped(ptr);
+                    SnarcBox::increment(ptr);
+                    SnarcBox::release(ptr);
+
+                    // the value is never written, so the reference is revoked
+                    // from the start, even if zero-sized
+                    Self {
+                        ptr,
+                        generation: 1,
+                        phantom: Default::default(),
+                    }
+                }
+
+                #[inline(always)]
+                fn is_revoked(&self) -> bool {
+                    SnarcBox::generation(self.ptr) != self.generation
+                }
+
+                #[inline(always)]
+                fn is_accessible(&self) -> bool {
+                    SnarcBox::state(self.ptr).is_set() && !self.is_revoked()
+                }
+
+                /// Like `get`, but without recording a failure.
+                #[doc(hidden)]
+                pub fn try_debug(&self) -> Option<&T> {
+                    SnarcBox::check(self.ptr);
+
+                    if self.is_accessible() {
+                        Some(unsafe { &*SnarcBox::value(self.ptr) })
+                    } else {
+                        None
+                    }
+                }
+
+                #[track_caller]
+                pub fn get(&self) -> Option<&T> {
+                    SnarcBox::check(self.ptr);
+
+                    if self.is_accessible() {
+                        Some(unsafe { &*SnarcBox::value(self.ptr) })
+                    } else {
+                        $crate::failed_get();
+                        None
+                    }
+                }
+
+                /// Borrows the inner value for as long as the returned guard
+                /// exists.
+                ///
+                /// Returns `None` outside of `enter`, like `get`. Unlike the
+                /// reference returned by `get`, the guard cannot outlive `enter`,
+                /// leaving it before the guard is dropped panics. Returns `None`
+                /// as well while the value is borrowed mutably, see
+                /// `SnarcRefMut`.
+                #[track_caller]
+                pub fn borrow(&self) -> Option<$crate::RefGuard<'_, T>> {
+                    let value = self.get()?;
+
+                    if !SnarcBox::borrow(self.ptr) {
+                        return None;
+                    }
+
+                    Some(unsafe {
+                        $crate::RefGuard::new(value, self.ptr.cast(), SnarcBox::<T>::unborrow)
+                    })
+                }
+
+                /// Borrows the inner value mutably for as long as the returned
+                /// guard exists, like `RefCell::borrow_mut`.
+                ///
+                /// Returns `None` outside of `enter`, like `get`, and while the
+                /// value is borrowed by way of another weak reference.
+                ///
+                /// # Safety
+                ///
+                /// Only the borrows of weak references are tracked. Neither the
+                /// references handed out by the owner, e.g., to the closure
+                /// passed to `enter`, nor those returned by `get` may be used
+                /// while the guard exists.
+                #[track_caller]
+                pub unsafe fn borrow_mut(&self) -> Option<$crate::RefMut<'_, T>> {
+                    SnarcBox::check(self.ptr);
+
+                    if !self.is_accessible() {
+                        $crate::failed_get();
+                        return None;
+                    }
+
+                    if !SnarcBox::borrow_mut(self.ptr) {
+                        return None;
+                    }
+
+                    SnarcBox::mark_dirty(self.ptr);
+
+                    // other weak references cannot borrow the value while this
+                    // guard exists
+                    let value = unsafe { &mut *SnarcBox::value(self.ptr) };
+                    Some(unsafe {
+                        $crate::RefMut::new(value, self.ptr.cast(), SnarcBox::<T>::unborrow)
+                    })
+                }
+
+                /// Evaluates `f` with a reference to the inner value.
+                ///
+                /// Returns `None` outside of `enter`, like `get`.
+                #[track_caller]
+                pub fn with<F, R>(&self, f: F) -> Option<R>
+                where
+                    F: FnOnce(&T) -> R,
+                {
+                    self.get().map(f)
+                }
+
+                /// Gets a reference to a part of the inner value.
+                ///
+                /// Returns `None` outside of `enter`, like `get`.
+                #[track_caller]
+                pub fn map_get<U, F>(&self, f: F) -> Option<&U>
+                where
+                    U: ?Sized,
+                    F: FnOnce(&T) -> &U,
+                {
+                    self.get().map(f)
+                }
+
+                /// Evaluates `f` with a reference to the inner value, flattening the
+                /// result.
+                ///
+                /// Returns `None` outside of `enter`, like `get`.
+                #[track_caller]
+                pub fn and_then_get<F, R>(&self, f: F) -> Option<R>
+                where
+                    F: FnOnce(&T) -> Option<R>,
+                {
+                    self.get().and_then(f)
+                }
+
+                /// Gets a reference to the inner value, or the one returned by `f`
+                /// outside of `enter`.
+                #[track_caller]
+                pub fn get_or_else<'a, F>(&'a self, f: F) -> &'a T
+                where
+                    F: FnOnce() -> &'a T,
+                {
+                    self.get().unwrap_or_else(f)
+                }
+
+                $(
+                    #[track_caller]
+                    pub fn expect(&self) -> &T {
+                        match self.get() {
+                            Some(value) => value,
+                            None => $crate::__panic!("{}", $expect),
+                        }
+                    }
+                )?
+
+                /// Gets a reference to the inner value, panicking with `msg`
+                /// outside of `enter`.
+                #[track_caller]
+                pub fn expect_with(&self, msg: &str) -> &T {
+                    match self.get() {
+                        Some(value) => value,
+                        None => $crate::__panic!("{}", msg),
+                    }
+                }
+
+                /// Like `expect_with`, but panics with the message returned by
+                /// `f`, which is only evaluated outside of `enter`.
+                #[track_caller]
+                pub fn expect_with_fn<F, M>(&self, f: F) -> &T
+                where
+                    F: FnOnce() -> M,
+                    M: std::fmt::Display,
+                {
+                    match self.get() {
+                        Some(value) => value,
+                        None => $crate::__panic!(@display f()),
+                    }
+                }
+
+                /// Projects this reference to a part of the inner value, e.g., a
+                /// field.
+                pub fn map<U, F>(self, f: F) -> $crate::MappedSnarcRef<U>
+                where
+                    T: 'static,
+                    U: 'static,
+                    F: Fn(&T) -> &U + Send + Sync + 'static,
+                {
+                    $crate::MappedSnarcRef::new(self, f)
+                }
+
+                /// Returns `true` if `self` and `other` refer to the same value.
+                ///
+                /// Zero-sized values are not allocated for, so their references always
+                /// compare equal, unless `per_instance;` is given.
+                pub fn ptr_eq(&self, other: &Self) -> bool {
+                    self.ptr == other.ptr
+                }
+
+                /// Returns `true` if `owner` owns the value `self` refers to.
+                pub fn same_owner(&self, owner: &$send<T>) -> bool {
+                    self.ptr == owner.ptr
+                }
+
+                /// Returns `true` if the owner was dropped, closed or leaked, so
+                /// that this reference will never resolve again.
+                ///
+                /// Unlike `get`, this may be called outside of `enter`, which
+                /// lets long-lived references clean themselves up. Zero-sized
+                /// values never dangle, unless `per_instance;` is given.
+                pub fn is_dangling(&self) -> bool {
+                    SnarcBox::check(self.ptr);
+                    SnarcBox::is_dropped(self.ptr)
+                }
+
+                /// Returns `true` if `get` would currently succeed, i.e., within
+                /// `enter`, without accessing the inner value.
+                #[inline]
+                pub fn is_entered(&self) -> bool {
+                    SnarcBox::check(self.ptr);
+                    self.is_accessible()
+                }
+
+                /// Returns the number of weak references to the inner value,
+                /// including this one.
+                ///
+                /// Returns `None` outside of `enter`, like `get`. Zero-sized
+                /// values are not counted, see the owners' `ref_count`.
+                pub fn ref_count(&self) -> Option<usize> {
+                    SnarcBox::check(self.ptr);
+
+                    if self.is_accessible() {
+                        Some(SnarcBox::ref_count(self.ptr))
+                    } else {
+                        None
+                    }
+                }
+
+                /// Returns the version of the inner value.
+                ///
+                /// Returns `None` outside of `enter`, like `get`.
+                pub fn version(&self) -> Option<u64> {
+                    SnarcBox::check(self.ptr);
+
+                    if self.is_accessible() {
+                        Some(SnarcBox::version(self.ptr))
+                    } else {
+                        None
+                    }
+                }
+
+                /// Returns a future that resolves once the inner value is bound to
+                /// the current thread, or once the owner enters next.
+                ///
+                /// Zero-sized values have nothing to wait on, so the future
+                /// resolves right away, unless `per_instance;` is given.
+                pub fn when_entered(&self) -> impl std::future::Future<Output = ()> + '_ {
+                    let mut since = None;
+
+                    std::future::poll_fn(move |cx| {
+                        SnarcBox::check(self.ptr);
+
+                        if self.is_accessible() {
+                            std::task::Poll::Ready(())
+                        } else {
+                            SnarcBox::poll_entered(self.ptr, &mut since, cx)
+                        }
+                    })
+                }
+
+                /// Subscribes to changes of the inner value.
+                ///
+                /// Panics outside of `enter`, like `clone`.
+                pub fn subscribe(&self) -> $crate::ChangeListener {
+                    if SnarcBox::state(self.ptr).is_set() {
+                        SnarcBox::subscribe(self.ptr)
+                    } else {
+                        $crate::__panic!(
+                            "{}::subscribe() outside of {}::enter(…)",
+                            stringify!($ref),
+                            stringify!($send)
+                        )
+                    }
+                }
+
+                /// Schedules `f` to run at the start of the owner's next `enter`,
+                /// from any thread.
+                ///
+                /// `f` is dropped without running if `self` is revoked, or once
+                /// the owner is dropped. Zero-sized values do not queue `f`, it
+                /// is dropped right away, unless `per_instance;` is given.
+                pub fn defer<F>(&self, f: F)
+                where
+                    F: FnOnce(&T) + Send + 'static,
+                {
+                    SnarcBox::check(self.ptr);
+
+                    if !self.is_revoked() {
+                        SnarcBox::defer(self.ptr, f);
+                    }
+                }
+
+                /// Sends `message` to the owner, from any thread, which receives
+                /// it by way of `enter_with_messages`.
+                ///
+                /// Returns `message` if `self` is revoked, or once the owner is
+                /// dropped. Panics if messages of another type were sent to the
+                /// same value before. Zero-sized values have no mailbox, so
+                /// `message` is returned, unless `per_instance;` is given.
+                pub fn send<M: Send + 'static>(&self, message: M) -> Result<(), M> {
+                    SnarcBox::check(self.ptr);
+
+                    if self.is_revoked() {
+                        return Err(message);
+                    }
+
+                    SnarcBox::send(self.ptr, message)
+                }
+
+                /// Creates a new non-owning reference to the innermost value of type
+                /// `T` that is bound to this thread by `enter_with_self`.
+                ///
+                /// This allows methods of `T` to reference their own value without
+                /// it having to store a reference to itself.
+                pub fn self_ref() -> Option<Self>
+                where
+                    T: 'static,
+                {
+                    let key = std::any::TypeId::of::<SnarcBox<T>>();
+                    let ptr: *mut SnarcBox<T> = $crate::innermost_entered(key)?.cast();
+
+                    SnarcBox::increment(ptr);
+                    $crate::trace_new_ref(ptr.cast());
+
+                    Some(Self {
+                        ptr,
+                        generation: SnarcBox::generation(ptr),
+                        phantom: Default::default(),
+                    })
+                }
+
+                /// Moves this reference into the process-wide token registry.
+                pub fn into_token(self) -> $crate::FfiToken
+                where
+                    T: 'static,
+                {
+                    $crate::FfiToken::register(self)
+                }
+
+                /// Takes the reference that `token` stands for out of the registry.
+                ///
+                /// Returns `None` if `token` is stale or stands for another type.
+                pub fn from_token(token: $crate::FfiToken) -> Option<Self>
+                where
+                    T: 'static,
+                {
+                    token.resolve()
+                }
+
+                /// Turn this parameterized `
+                #[doc = stringify!($ref)]
+                /// ` the unparameterized `ErasedSnarcRef`.
+                pub fn into_erased(self) -> $crate::ErasedSnarcRef
+                where
+                    T: 'static,
+                {
+                    let snarc_ref: Box<dyn $crate::RefContext> = Box::new(self);
+                    $crate::ErasedSnarcRef::from(snarc_ref)
+                }
+            }
+
+            $crate::__impl_format!($send);
+            $crate::__impl_format!($unsend);
+            $crate::__impl_format!($ref);
+            $crate::__impl_serialize!($ref);
+            $crate::__impl_snapshot!($ref);
+            $crate::__impl_deref!($ref, $send);
+
+            impl<T> $crate::GetMut<T> for $ref<T> {
+                #[track_caller]
+                unsafe fn get_mut(&self) -> Option<$crate::RefMut<'_, T>> {
+                    unsafe { self.borrow_mut() }
+                }
+            }
+
+            impl<T> $crate::Get<T> for $ref<T> {
+                #[track_caller]
+                fn get(&self) -> Option<&T> {
+                    $ref::get(self)
+                }
+
+                fn version(&self) -> Option<u64> {
+                    $ref::version(self)
+                }
+            }
+
+            impl<T: 'static> $crate::Enter for $send<T> {
+                fn enter<F, R>(&self, f: F) -> R
+                where
+                    F: FnOnce(&T) -> R,
+                {
+                    $send::enter(self, f)
+                }
+            }
+
+            unsafe impl<T> $crate::graph::Node for $send<T> {
+                type Value = T;
+                type Ref = $ref<T>;
+
+                fn alloc_uninit() -> *mut () {
+                    let ptr = SnarcBox::<T>::new_uninit_ptr();
+                    // placeholders are revoked until the value is written, see `init`
+                    SnarcBox::set_generation(ptr, 1);
+
+                    ptr.cast()
+                }
+
+                fn placeholder(ptr: *mut ()) -> $ref<T> {
+                    SnarcBox::<T>::increment(ptr.cast());
+
+                    $ref {
+                        ptr: ptr.cast(),
+                        generation: 0,
+                        phantom: Default::default(),
+                    }
+                }
+
+                unsafe fn init(ptr: *mut (), value: T) -> Self {
+                    SnarcBox::init(ptr.cast(), value);
+                    SnarcBox::<T>::set_generation(ptr.cast(), 0);
+
+                    Self {
+                        ptr: ptr.cast(),
+                        phantom: std::marker::PhantomData,
+                    }
+                }
+            }
+
+            impl<T: 'static> From<$ref<T>> for $crate::SnarcOrArc<T> {
+                fn from(snarc_ref: $ref<T>) -> Self {
+                    $crate::SnarcOrArc::Snarc(Box::new(snarc_ref))
+                }
+            }
+
+            impl<T: 'static> From<$ref<T>> for $crate::ErasedSnarcRef {
+                fn from(snarc_ref: $ref<T>) -> Self {
+                    snarc_ref.into_erased()
+                }
+            }
+
+            impl<T: 'static> $crate::RefContext for $ref<T> {
+                fn is_entered(&self) -> bool {
+                    $ref::is_entered(self)
+                }
+
+                fn is_dangling(&self) -> bool {
+                    $ref::is_dangling(self)
+                }
+            }
+
+            /// References compare equal if they refer to the same value, see
+            /// `ptr_eq`.
+            impl<T> PartialEq for $ref<T> {
+                fn eq(&self, other: &Self) -> bool {
+                    self.ptr_eq(other)
+                }
+            }
+
+            impl<T> Eq for $ref<T> {}
+
+            impl<T> std::hash::Hash for $ref<T> {
+                fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                    self.ptr.hash(state)
+                }
+            }
+
+            impl<T> Default for $ref<T> {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+
+            impl<T> Clone for $ref<T> {
+                fn clone(&self) -> Self {
+                    if SnarcBox::is_orphaned(self.ptr) {
+                        // the owner is gone, the count is no longer used
+                        SnarcBox::increment_orphaned(self.ptr);
+                    } else if SnarcBox::state(self.ptr).is_set() {
+                        SnarcBox::increment(self.ptr);
+                    } else if !SnarcBox::try_increment_detached(self.ptr) {
+                        $crate::__panic!(
+                            "{}::clone() outside of {}::enter(…)",
+                            stringify!($ref),
+                            stringify!($send)
+                        )
+                    }
+
+                    Self {
+                        ptr: self.ptr,
+                        generation: self.generation,
+                        phantom: Default::default(),
+                    }
+                }
+            }
+
+            impl<T> Drop for $ref<T> {
+                fn drop(&mut self) {
+                    SnarcBox::check(self.ptr);
+
+                    if SnarcBox::state(self.ptr).is_set() && !SnarcBox::is_orphaned(self.ptr) {
+                        SnarcBox::decrement(self.ptr);
+                    } else {
+                        // reconciled on the next `enter`, or frees the
+                        // allocation once the owner is gone
+                        SnarcBox::decrement_detached(self.ptr);
+                    }
+                }
+            }
+        }
+    };
+}
+
+/// Implements a trait requested by a derive attribute of `snarc!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __derive {
+    (Debug; $send:ident, $unsend:ident, $ref:ident) => {
+        impl<T: std::fmt::Debug> std::fmt::Debug for $send::$send<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_tuple(stringify!($send)).field(&**self).finish()
+            }
+        }
+
+        impl<T: std::fmt::Debug> std::fmt::Debug for $send::$unsend<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_tuple(stringify!($unsend)).field(&**self).finish()
+            }
+        }
+
+        impl<T: std::fmt::Debug> std::fmt::Debug for $send::$ref<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self.try_debug() {
+                    Some(value) => f.debug_tuple(stringify!($ref)).field(value).finish(),
+                    None => f
+                        .debug_tuple(stringify!($ref))
+                        .field(&format_args!("<not entered>"))
+                        .finish(),
+                }
+            }
+        }
+    };
+    (Serialize; $send:ident, $unsend:ident, $ref:ident) => {
+        $crate::__derive_serialize!($send, $unsend, $ref);
+    };
+    ($other:ident; $send:ident, $unsend:ident, $ref:ident) => {
+        compile_error!(concat!("snarc! cannot derive ", stringify!($other)));
+    };
+}
+
+/// Implements `serde::Serialize` as requested by a derive attribute of
+/// `snarc!`, given the `serde` feature.
+#[doc(hidden)]
+#[cfg(not(feature = "serde"))]
+#[macro_export]
+macro_rules! __derive_serialize {
+    ($send:ident, $unsend:ident, $ref:ident) => {
+        compile_error!("snarc! derives Serialize only given the `serde` feature");
+    };
+}
+
+#[doc(hidden)]
+#[cfg(feature = "serde")]
+#[macro_export]
+macro_rules! __derive_serialize {
+    ($send:ident, $unsend:ident, $ref:ident) => {
+        impl<T: $crate::serde::Serialize> $crate::serde::Serialize for $send::$send<T> {
+            fn serialize<S: $crate::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                (**self).serialize(serializer)
+            }
+        }
+
+        impl<T: $crate::serde::Serialize> $crate::serde::Serialize for $send::$unsend<T> {
+            fn serialize<S: $crate::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                (**self).serialize(serializer)
+            }
+        }
+
+        impl<T: $crate::serde::Serialize> $crate::serde::Serialize for $send::$ref<T> {
+            fn serialize<S: $crate::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                self.serialize_value(serializer).map_err(|e| match e {
+                    $crate::AccessOrSerError::NotEntered => $crate::serde::ser::Error::custom(
+                        $crate::AccessOrSerError::<S::Error>::NotEntered,
+                    ),
+                    $crate::AccessOrSerError::Serialize(e) => e,
+                })
+            }
+        }
+    };
+}
+
+/// Implements `defmt::Format` for a struct defined by `snarc!`, given the
+/// `defmt` feature.
+#[doc(hidden)]
+#[cfg(not(feature = "defmt"))]
+#[macro_export]
+macro_rules! __impl_format {
+    ($name:ident) => {};
+}
+
+#[doc(hidden)]
+#[cfg(feature = "defmt")]
+#[macro_export]
+macro_rules! __impl_format {
+    ($name:ident) => {
+        impl<T> $crate::defmt::Format for $name<T> {
+            fn format(&self, fmt: $crate::defmt::Formatter<'_>) {
+                $crate::defmt::Format::format(stringify!($name), fmt)
+            }
+        }
+    };
+}
+
+/// Implements `Deref` for a weak reference defined by `snarc!`, panicking
+/// outside of `enter`, given the `deref-panicking` feature.
+#[doc(hidden)]
+#[cfg(not(feature = "deref-panicking"))]
+#[macro_export]
+macro_rules! __impl_deref {
+    ($ref:ident, $send:ident) => {};
+}
+
+#[doc(hidden)]
+#[cfg(feature = "deref-panicking")]
+#[macro_export]
+macro_rules! __impl_deref {
+    ($ref:ident, $send:ident) => {
+        impl<T> std::ops::Deref for $ref<T> {
+            type Target = T;
+
+            #[track_caller]
+            fn deref(&self) -> &T {
+                match self.get() {
+                    Some(value) => value,
+                    None => $crate::__panic!(
+                        "{}::deref() outside of {}::enter(…)",
+                        stringify!($ref),
+                        stringify!($send)
+                    ),
+                }
+            }
+        }
+    };
+}
+
+/// Adds `snapshot` to a weak reference defined by `snarc!`, given the
+/// `snapshot` feature.
+#[doc(hidden)]
+#[cfg(not(feature = "snapshot"))]
+#[macro_export]
+macro_rules! __impl_snapshot {
+    ($ref:ident) => {};
+}
+
+#[doc(hidden)]
+#[cfg(feature = "snapshot")]
+#[macro_export]
+macro_rules! __impl_snapshot {
+    ($ref:ident) => {
+        impl<T> $ref<T> {
+            /// Clones the inner value from any thread, while the owner
+            /// neither enters it nor accesses it mutably.
+            ///
+            /// Returns `None` if the value is entered, or if it was accessed
+            /// mutably since it was last entered, as well as if `self` is
+            /// revoked, or another snapshot is being taken. Meanwhile, the
+            /// owner waits before entering or mutating the value. Zero-sized
+            /// values are never snapshotted, unless `per_instance;` is
+            /// given.
+            ///
+            /// Requires the `snapshot` feature.
+            pub fn snapshot(&self) -> Option<T>
+            where
+                T: Sync + Clone,
+            {
+                SnarcBox::check(self.ptr);
+
+                if !SnarcBox::try_snapshot(self.ptr) {
+                    return None;
+                }
+
+                let ptr = self.ptr;
+                let _guard = $crate::scopeguard::guard((), |_| SnarcBox::end_snapshot(ptr));
+
+                // the owner cannot drop the value meanwhile, see `close`
+                if self.is_revoked() {
+                    return None;
+                }
+
+                Some(unsafe { (*SnarcBox::value(ptr)).clone() })
+            }
+        }
+    };
+}
+
+/// Adds `serialize_value` to a weak reference defined by `snarc!`, given the
+/// `serde` feature.
+#[doc(hidden)]
+#[cfg(not(feature = "serde"))]
+#[macro_export]
+macro_rules! __impl_serialize {
+    ($ref:ident) => {};
+}
+
+#[doc(hidden)]
+#[cfg(feature = "serde")]
+#[macro_export]
+macro_rules! __impl_serialize {
+    ($ref:ident) => {
+        impl<T: $crate::serde::Serialize> $ref<T> {
+            /// Serializes the inner value with `serializer`.
+            ///
+            /// Fails with `AccessOrSerError::NotEntered` if the corresponding
+            /// owning pointer did not currently bind the inner value to the
+            /// current thread.
+            pub fn serialize_value<S: $crate::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> Result<S::Ok, $crate::AccessOrSerError<S::Error>> {
+                $crate::serialize_value(self, serializer)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    crate::snarc!(Snarc, Narc, SnarcRef, "expectation");
 
     crate::tests::tests!(Snarc, Narc, SnarcRef);
+
+    #[test]
+    fn instances_are_entered_on_one_thread_at_a_time() {
+        let snarc = std::sync::Arc::new(Snarc::new(1));
+
+        snarc.enter(|_| {
+            let other = std::sync::Arc::clone(&snarc);
+            let result = std::thread::spawn(move || other.try_enter(|_| ()))
+                .join()
+                .unwrap();
+            assert_eq!(result, Err(crate::AlreadyEntered));
+        });
+
+        let other = std::sync::Arc::clone(&snarc);
+        let result = std::thread::spawn(move || other.try_enter(|v| *v))
+            .join()
+            .unwrap();
+        assert_eq!(result, Ok(1));
+    }
+
+    #[test]
+    fn distinct_instances_may_be_entered_nested() {
+        let a = Snarc::new(1);
+        let b = Snarc::new(2);
+
+        let a_ref = a.new_ref();
+        let b_ref = b.new_ref();
+
+        a.enter(|_| {
+            assert_eq!(b.enter(|b| *b + *a_ref.get().unwrap()), 3);
+            assert_eq!(a_ref.get(), Some(&1));
+            drop(a_ref);
+            drop(b_ref);
+        });
+    }
+
+    #[test]
+    fn try_enter_fails_while_erased_is_entered() {
+        let mut a = crate::ErasedSnarc::from(Snarc::new(1));
+        let b = Snarc::new(2);
+
+        let result = a.enter(|| b.try_enter(|b| *b));
+        assert_eq!(result, Err(crate::AlreadyEntered));
+        assert_eq!(b.try_enter(|b| *b), Ok(2));
+    }
+
+    #[test]
+    fn zero_sized_refs_are_not_tracked() {
+        let snarc = Snarc::new(());
+        let snarc_ref = snarc.new_ref();
+
+        assert_eq!(snarc.ref_count(), 0);
+        assert!(!snarc.is_unique());
+
+        drop(snarc);
+        assert!(!snarc_ref.is_dangling());
+
+        let other = Snarc::new(());
+        other.enter(|_| drop(snarc_ref));
+    }
+
+    #[test]
+    fn zero_sized_refs_neither_queue_nor_wait() {
+        let ran = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        let snarc = Snarc::new(());
+        let snarc_ref = snarc.new_ref();
+
+        let flag = std::sync::Arc::clone(&ran);
+        snarc_ref.defer(move |_| flag.store(true, std::sync::atomic::Ordering::Relaxed));
+        assert_eq!(snarc_ref.send(1), Err(1));
+        futures::executor::block_on(snarc_ref.when_entered());
+
+        snarc.enter(|_| drop(snarc_ref));
+        assert!(!ran.load(std::sync::atomic::Ordering::Relaxed));
+    }
+
+    #[test]
+    fn zero_sized_values_are_initialized_in_place() {
+        static DROPS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+        struct Zst;
+
+        impl Drop for Zst {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+
+        let mut snarc = Snarc::<Zst>::new_uninit();
+        snarc.write(Zst);
+        let snarc = unsafe { snarc.assume_init() };
+        assert_eq!(DROPS.load(std::sync::atomic::Ordering::Relaxed), 0);
+
+        drop(snarc);
+        assert_eq!(DROPS.load(std::sync::atomic::Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn refs_of_dropped_owners_do_not_resolve() {
+        let outer = Snarc::new(vec![Snarc::new(1)]);
+        let inner_ref = outer[0].new_ref();
+        drop(outer);
+
+        let other = Snarc::new(2);
+        other.enter(|_| {
+            assert_eq!(inner_ref.get(), None);
+            drop(inner_ref);
+        });
+    }
+
+    #[test]
+    fn mailboxes_and_defer_queues_are_a_pointer_until_used() {
+        assert_eq!(
+            std::mem::size_of::<crate::Mailbox>(),
+            std::mem::size_of::<usize>()
+        );
+        assert_eq!(
+            std::mem::size_of::<crate::DeferQueue>(),
+            std::mem::size_of::<usize>()
+        );
+
+        let snarc = Snarc::new(1);
+        let snarc_ref = snarc.new_ref();
+        snarc_ref.send("a").unwrap();
+        snarc_ref.send("b").unwrap();
+        let messages = snarc.enter_with_messages(|_, messages: Vec<&str>| messages);
+        assert_eq!(messages, ["a", "b"]);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        snarc_ref.defer(move |v| sender.send(*v).unwrap());
+        snarc.enter(|_| ());
+        assert_eq!(receiver.try_recv(), Ok(1));
+        drop(snarc_ref);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-canary")]
+    fn overruns_of_the_value_abort() {
+        let test = concat!(module_path!(), "::overruns_of_the_value_abort");
+        let test = test.split_once("::").unwrap().1;
+
+        let stderr = crate::tests::aborts(test, || {
+            let snarc = Snarc::new(std::cell::UnsafeCell::new(0u64));
+            snarc.enter(|v| unsafe { v.get().add(1).write(0) });
+            snarc.enter(|_| ());
+        });
+        // defmt does not log to stderr
+        assert!(
+            cfg!(feature = "defmt") || stderr.contains("memory corruption"),
+            "{stderr}"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "debug-canary")]
+    fn use_after_free_aborts() {
+        let test = concat!(module_path!(), "::use_after_free_aborts");
+        let test = test.split_once("::").unwrap().1;
+
+        let stderr = crate::tests::aborts(test, || {
+            let snarc = Snarc::new(0u64);
+            let freed = unsafe { std::ptr::read(&snarc) };
+            drop(snarc);
+            freed.enter(|_| ());
+        });
+        assert!(
+            cfg!(feature = "defmt") || stderr.contains("snarc: corrupted control block"),
+            "{stderr}"
+        );
+    }
+
+    mod per_instance {
+        crate::snarc!(per_instance; Snarc, Narc, SnarcRef, "expectation");
+
+        crate::tests::tests!(Snarc, Narc, SnarcRef);
+
+        #[test]
+        fn instances_are_entered_on_one_thread_at_a_time() {
+            let snarc = std::sync::Arc::new(Snarc::new(1));
+
+            snarc.enter(|_| {
+                let other = std::sync::Arc::clone(&snarc);
+                let result = std::thread::spawn(move || other.try_enter(|_| ()))
+                    .join()
+                    .unwrap();
+                assert_eq!(result, Err(crate::AlreadyEntered));
+            });
+        }
+
+        #[test]
+        fn distinct_instances_may_be_entered_at_the_same_time() {
+            let a = Snarc::new(1);
+            let b = Snarc::new(2);
+
+            let a_ref = a.new_ref();
+            let b_ref = b.new_ref();
+
+            a.enter(|_| {
+                b.enter(|_| {
+                    assert_eq!(a_ref.get(), Some(&1));
+                    assert_eq!(b_ref.get(), Some(&2));
+
+                    drop(b_ref);
+                });
+
+                assert_eq!(a_ref.get(), Some(&1));
+
+                drop(a_ref);
+            });
+        }
+
+        #[test]
+        fn snarc_ref_returns_none_on_other_threads() {
+            let snarc = Snarc::new(5);
+
+            let snarc_ref = snarc.new_ref();
+
+            snarc.enter(|_| {
+                std::thread::scope(|s| {
+                    s.spawn(|| assert_eq!(snarc_ref.get(), None));
+                });
+
+                drop(snarc_ref);
+            });
+        }
+    }
+
+    mod erased {
+        crate::snarc!(Snarc, Narc, SnarcRef => ErasedSnarc, ErasedNarc);
+
+        crate::tests::tests!(Snarc, Narc, SnarcRef);
+
+        #[test]
+        fn erased_snarc_binds_its_value() {
+            let snarc = Snarc::new(5);
+
+            let snarc_ref = snarc.new_ref();
+
+            let mut erased = ErasedSnarc::from(snarc);
+
+            erased.enter(|| {
+                assert_eq!(snarc_ref.get(), Some(&5));
+
+                drop(snarc_ref);
+            });
+        }
+
+        #[test]
+        fn erased_narc_binds_its_value() {
+            let narc = Narc::new(5);
+
+            let snarc_ref = narc.new_ref();
+
+            let erased = ErasedNarc::from(narc);
+
+            assert_eq!(snarc_ref.get(), Some(&5));
+
+            drop(snarc_ref);
+
+            let _send = erased.into_send();
+        }
+
+        #[test]
+        fn erased_snarc_enters_as_its_type() {
+            let mut erased = ErasedSnarc::from(Snarc::new(5));
+
+            assert_eq!(erased.enter_as(|v: &i32| *v), Ok(5));
+            assert_eq!(erased.enter_as(|v: &u8| *v), Err(crate::WrongType));
+        }
+
+        #[test]
+        fn erased_owners_name_their_type() {
+            let erased = ErasedSnarc::from(Snarc::new(5));
+
+            assert_eq!(erased.type_name(), "i32");
+            assert_eq!(format!("{erased:?}"), "ErasedSnarc(i32)");
+
+            let erased = erased.into_unsend();
+
+            assert_eq!(format!("{erased:?}"), "ErasedNarc(i32)");
+        }
+
+        #[test]
+        fn erased_snarc_is_entered_with_its_context() {
+            let snarc = Snarc::new(1);
+            let erased = ErasedSnarc::from(Snarc::new(2));
+
+            assert!(!erased.is_entered());
+            snarc.enter(|_| assert!(erased.is_entered()));
+        }
+
+        #[test]
+        fn erased_snarc_enter_guard_binds_until_dropped() {
+            let snarc = Snarc::new(5);
+            let snarc_ref = snarc.new_ref();
+            let mut erased = ErasedSnarc::from(snarc);
+
+            let guard = erased.enter_guard();
+            assert_eq!(snarc_ref.get(), Some(&5));
+            drop(guard);
+
+            assert_eq!(snarc_ref.get(), None);
+            erased.enter(|| drop(snarc_ref));
+        }
+
+        #[test]
+        fn erased_snarc_enters_as_any() {
+            let mut erased = ErasedSnarc::from(Snarc::new(5));
+
+            assert_eq!(
+                erased.enter_any(|v| v.downcast_ref::<i32>().copied()),
+                Some(5)
+            );
+        }
+
+        #[test]
+        fn erased_owners_downcast_to_their_type() {
+            let erased = ErasedSnarc::from(Snarc::new(5));
+
+            let erased = erased.downcast::<u8>().err().unwrap();
+            let mut snarc = erased.downcast::<i32>().ok().unwrap();
+
+            assert_eq!(Snarc::get_mut(&mut snarc), Some(&mut 5));
+
+            let erased = ErasedNarc::from(Narc::new(6));
+
+            let erased = erased.downcast::<u8>().err().unwrap();
+            let narc = erased.downcast::<i32>().ok().unwrap();
+
+            assert_eq!(*narc, 6);
+        }
+    }
+
+    mod families {
+        crate::snarc! {
+            Snarc, Narc, SnarcRef;
+            #[derive(Debug)]
+            OtherSnarc, OtherNarc, OtherSnarcRef;
+        }
+
+        crate::tests::tests!(Snarc, Narc, SnarcRef);
+
+        #[test]
+        fn families_have_independent_contexts() {
+            let snarc = Snarc::new(1);
+            let other: OtherSnarc<_> = OtherNarc::new(2).into_send();
+
+            let snarc_ref = snarc.new_ref();
+            let other_ref: OtherSnarcRef<_> = other.new_ref();
+
+            snarc.enter(|_| {
+                assert_eq!(other_ref.get(), None);
+
+                other.enter(|_| {
+                    assert_eq!(snarc_ref.get(), Some(&1));
+                    assert_eq!(other_ref.get(), Some(&2));
+
+                    drop(other_ref);
+                });
+
+                drop(snarc_ref);
+            });
+        }
+
+        #[test]
+        fn erased_snarcs_are_entered_with_their_context() {
+            let snarc = Snarc::new(1);
+            let erased = crate::ErasedSnarc::from(Snarc::new(2));
+            let other = crate::ErasedSnarc::from(OtherSnarc::new(3));
+
+            snarc.enter(|_| {
+                assert!(erased.is_entered());
+                assert!(!other.is_entered());
+            });
+
+            assert!(!erased.is_entered());
+        }
+
+        #[test]
+        fn erased_snarcs_are_left_when_entering_all_panics() {
+            use crate::EnterAll;
+
+            let mut owners = vec![
+                OtherSnarc::new(1).into_erased(),
+                Snarc::new(2).into_erased(),
+                Snarc::new(3).into_erased(),
+            ];
+
+            let result =
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| owners.enter_all(|| ())));
+            assert!(result.is_err());
+            assert!(owners.iter().all(|owner| !owner.is_entered()));
+        }
+
+        #[test]
+        fn derived_debug_forwards_within_context() {
+            let other = OtherSnarc::new(2);
+            let other_ref = other.new_ref();
+
+            assert_eq!(format!("{:?}", other), "OtherSnarc(2)");
+            assert_eq!(format!("{:?}", other_ref), "OtherSnarcRef(<not entered>)");
+
+            other.enter(|_| {
+                assert_eq!(format!("{:?}", other_ref), "OtherSnarcRef(2)");
+                drop(other_ref);
+            });
+        }
+    }
+
+    mod invocations {
+        crate::snarc!(Snarc, Narc, SnarcRef);
+        crate::snarc!(mod other_snarc_impl; per_instance; OtherSnarc, OtherNarc, OtherSnarcRef);
+
+        #[test]
+        fn invocations_in_one_module_name_their_own_modules() {
+            let snarc: Snarc<_> = Narc::new(1).into_send();
+            let other: OtherSnarc<_> = OtherNarc::new(2).into_send();
+            let snarc_ref: SnarcRef<_> = snarc.new_ref();
+            let other_ref: OtherSnarcRef<_> = other.new_ref();
+
+            snarc.enter(|_| {
+                assert_eq!(other_ref.get(), None);
+                other.enter(|_| assert_eq!(other_ref.get(), Some(&2)));
+                drop(snarc_ref);
+            });
+
+            other.enter(|_| drop(other_ref));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    mod serialized {
+        crate::snarc!(
+            #[derive(Debug, Serialize)]
+            Snarc,
+            Narc,
+            SnarcRef
+        );
+
+        crate::tests::tests!(Snarc, Narc, SnarcRef);
+
+        #[test]
+        fn derived_serialize_forwards_within_context() {
+            let snarc = Snarc::new(vec![1, 2]);
+            let narc = Narc::new("narc");
+            let snarc_ref = snarc.new_ref();
+
+            assert_eq!(serde_json::to_string(&snarc).unwrap(), "[1,2]");
+            assert_eq!(serde_json::to_string(&narc).unwrap(), "\"narc\"");
+
+            let error = serde_json::to_string(&snarc_ref).unwrap_err();
+            assert_eq!(error.to_string(), "value is not entered");
+
+            snarc.enter(|_| {
+                assert_eq!(serde_json::to_string(&snarc_ref).unwrap(), "[1,2]");
+                drop(snarc_ref);
+            });
+        }
+    }
 }
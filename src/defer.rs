@@ -0,0 +1,94 @@
+use crate::lazy_box::LazyBox;
+use std::any::Any;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+/// A task whose argument type is erased, so that queues do not depend on the
+/// type of the value they run against.
+struct Task {
+    f: Box<dyn Any + Send>,
+    run: unsafe fn(Box<dyn Any + Send>, *const ()),
+}
+
+impl Task {
+    fn new<T: ?Sized, F: FnOnce(&T) + Send + 'static>(f: F) -> Self {
+        Self {
+            f: Box::new(f),
+            run: run::<T, F>,
+        }
+    }
+}
+
+/// `value` must point to a `&T`.
+unsafe fn run<T: ?Sized, F: FnOnce(&T) + 'static>(f: Box<dyn Any + Send>, value: *const ()) {
+    if let Ok(f) = f.downcast::<F>() {
+        f(*value.cast::<&T>());
+    }
+}
+
+#[derive(Default)]
+struct Tasks {
+    pending: AtomicBool,
+    tasks: Mutex<Vec<Task>>,
+}
+
+impl Tasks {
+    fn lock(&self) -> MutexGuard<'_, Vec<Task>> {
+        self.tasks.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn take(&self) -> Vec<Task> {
+        if self.pending.swap(false, Ordering::AcqRel) {
+            std::mem::take(&mut *self.lock())
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Work scheduled by weak references, to be run at the start of the owner's
+/// next `enter`.
+///
+/// Tasks may be pushed from any thread, but are only drained by the owner,
+/// within its `enter` context. The queue is only allocated once the first
+/// task is pushed, until then it is a single null pointer.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct DeferQueue {
+    tasks: LazyBox<Tasks>,
+}
+
+impl DeferQueue {
+    pub fn push<T: ?Sized, F>(&self, f: F)
+    where
+        F: FnOnce(&T) + Send + 'static,
+    {
+        let tasks = self.tasks.get_or_init(Default::default);
+        tasks.lock().push(Task::new(f));
+        tasks.pending.store(true, Ordering::Release);
+    }
+
+    /// Runs the pending tasks. Tasks pushed meanwhile wait for the next call.
+    ///
+    /// # Safety
+    ///
+    /// All pending tasks must have been pushed for `T`, i.e., the queue must
+    /// be cleared whenever the value's type changes.
+    #[inline]
+    pub unsafe fn drain<T: ?Sized>(&self, value: &T) {
+        if let Some(tasks) = self.tasks.get() {
+            for task in tasks.take() {
+                (task.run)(task.f, (&value as *const &T).cast());
+            }
+        }
+    }
+
+    /// Drops the pending tasks without running them.
+    pub fn clear(&self) {
+        if let Some(tasks) = self.tasks.get() {
+            drop(tasks.take());
+        }
+    }
+}
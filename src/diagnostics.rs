@@ -0,0 +1,53 @@
+//! Records where weak references failed to access their values.
+//!
+//! Requires the `diagnostics` feature. Every call site of `get` that returned
+//! `None` is recorded once, repeated failures only increment its count. At
+//! most [`MAX_CALL_SITES`] distinct call sites are recorded.
+use std::panic::Location;
+use std::sync::Mutex;
+
+/// The maximum number of distinct call sites recorded.
+pub const MAX_CALL_SITES: usize = 256;
+
+static FAILED_GETS: Mutex<Vec<FailedGet>> = Mutex::new(Vec::new());
+
+/// A call site of `get` that returned `None`.
+#[derive(Clone, Debug)]
+pub struct FailedGet {
+    /// The location of the call.
+    pub location: &'static Location<'static>,
+    /// The name of the calling thread, as of the first failure.
+    pub thread: Option<String>,
+    /// The number of values entered on the calling thread, as of the first
+    /// failure.
+    ///
+    /// Zero suggests a missing `enter`, anything else that the wrong value was
+    /// entered.
+    pub entered: usize,
+    /// The number of failures at this call site.
+    pub count: usize,
+}
+
+/// Returns the call sites of `get` that returned `None`, in the order they
+/// first failed.
+pub fn failed_gets() -> Vec<FailedGet> {
+    FAILED_GETS
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .clone()
+}
+
+pub(crate) fn record(location: &'static Location<'static>) {
+    let mut failed_gets = FAILED_GETS.lock().unwrap_or_else(|p| p.into_inner());
+
+    if let Some(failed_get) = failed_gets.iter_mut().find(|f| f.location == location) {
+        failed_get.count += 1;
+    } else if failed_gets.len() < MAX_CALL_SITES {
+        failed_gets.push(FailedGet {
+            location,
+            thread: std::thread::current().name().map(String::from),
+            entered: crate::entered::entered_count(),
+            count: 1,
+        });
+    }
+}
@@ -0,0 +1,10 @@
+use std::future::Future;
+
+/// Values that shut down gracefully before they are dropped.
+///
+/// Used by the `dispose_async` method of owning pointers, which evaluates
+/// [`dispose`][AsyncDispose::dispose()] within the `enter` context.
+pub trait AsyncDispose {
+    /// Shuts down this value.
+    fn dispose(&self) -> impl Future<Output = ()>;
+}
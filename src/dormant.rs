@@ -0,0 +1,30 @@
+/// An owning pointer that was put to rest, created by `into_dormant`.
+///
+/// A dormant owner keeps its inner value, and with it the target of all weak
+/// references, alive. However, the value can neither be accessed nor entered
+/// until the owner is [`activate`][Dormant::activate()]d again. Therefore, a
+/// `Dormant` may be shared between threads even if the value may not.
+pub struct Dormant<S> {
+    owner: S,
+}
+
+// nothing is accessible through a shared reference
+unsafe impl<S: Send> Sync for Dormant<S> {}
+
+impl<S> Dormant<S> {
+    #[doc(hidden)]
+    pub fn new(owner: S) -> Self {
+        Self { owner }
+    }
+
+    /// Returns the owner, which may be entered again.
+    pub fn activate(self) -> S {
+        self.owner
+    }
+}
+
+impl<S> std::fmt::Debug for Dormant<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Dormant").finish_non_exhaustive()
+    }
+}
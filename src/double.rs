@@ -0,0 +1,53 @@
+/// A pair of buffers, one of which is written while the other is read.
+///
+/// Wrapped by an owning pointer, e.g., `Snarc<DoubleBuffer<Frame>>`, the owner
+/// writes to the [`back`][DoubleBuffer::back_mut()] buffer and
+/// [`publish`][DoubleBuffer::publish()]es it once complete. Weak references
+/// only ever read the [`front`][DoubleBuffer::front()] buffer. Because
+/// publishing requires a mutable reference, it can never happen within the
+/// `enter` context, so readers never observe a partially written buffer.
+#[derive(Clone, Debug, Default)]
+pub struct DoubleBuffer<T> {
+    buffers: [T; 2],
+    back: usize,
+}
+
+impl<T> DoubleBuffer<T> {
+    /// Creates a new `DoubleBuffer` from its initial `front` and `back`
+    /// buffers.
+    pub fn new(front: T, back: T) -> Self {
+        Self {
+            buffers: [back, front],
+            back: 0,
+        }
+    }
+
+    /// Returns the most recently published buffer.
+    pub fn front(&self) -> &T {
+        &self.buffers[1 - self.back]
+    }
+
+    /// Returns the buffer that will be published next.
+    pub fn back(&self) -> &T {
+        &self.buffers[self.back]
+    }
+
+    /// Returns the buffer that will be published next, for writing.
+    pub fn back_mut(&mut self) -> &mut T {
+        &mut self.buffers[self.back]
+    }
+
+    /// Swaps the buffers, turning the back buffer into the front buffer.
+    ///
+    /// The former front buffer becomes the back buffer, retaining its contents.
+    pub fn publish(&mut self) {
+        self.back = 1 - self.back;
+    }
+}
+
+impl<T: Clone> DoubleBuffer<T> {
+    /// Creates a new `DoubleBuffer` with both buffers set to `value`.
+    pub fn from_value(value: T) -> Self {
+        Self::new(value.clone(), value)
+    }
+}
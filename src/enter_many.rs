@@ -0,0 +1,190 @@
+use std::ops::Deref;
+
+use crate::ScopedErasedSnarc;
+
+/// Owning pointers that can temporarily bind their inner value to the
+/// current thread.
+///
+/// Implemented by the sendable owners, it allows several of them to be
+/// entered at once by way of [`EnterMany`] or [`enter_all`].
+pub trait Enter: Deref {
+    /// Temporarily bind the inner value to this thread and evaluate `f`
+    /// within that context.
+    fn enter<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Self::Target) -> R;
+}
+
+/// Tuples of references to owners that can be entered in one scope.
+///
+/// ```
+/// use snarc::EnterMany;
+///
+/// snarc::snarc!(Snarc, Narc, SnarcRef);
+///
+/// let (a, b) = (Snarc::new(1), Snarc::new("two"));
+/// let (a_ref, b_ref) = (a.new_ref(), b.new_ref());
+///
+/// (&a, &b).enter(|a, b| {
+///     assert_eq!((a_ref.get(), b_ref.get()), (Some(a), Some(b)));
+///     drop((a_ref, b_ref));
+/// });
+/// ```
+pub trait EnterMany<F, R> {
+    /// Temporarily bind all inner values to this thread and evaluate `f`
+    /// within that context.
+    fn enter(self, f: F) -> R;
+}
+
+macro_rules! impl_enter_many {
+    ($($s:ident $owner:ident),+) => {
+        impl<'a, $($s: Enter,)+ F, R> EnterMany<F, R> for ($(&'a $s,)+)
+        where
+            F: FnOnce($(&$s::Target),+) -> R,
+        {
+            fn enter(self, f: F) -> R {
+                let ($($owner,)+) = self;
+                impl_enter_many!(@nest f; $($owner)+; $($owner)+)
+            }
+        }
+    };
+    (@nest $f:ident; $($all:ident)+;) => {
+        $f($(&**$all),+)
+    };
+    (@nest $f:ident; $($all:ident)+; $head:ident $($tail:ident)*) => {
+        Enter::enter($head, |_| impl_enter_many!(@nest $f; $($all)+; $($tail)*))
+    };
+}
+
+impl_enter_many!(A a, B b);
+impl_enter_many!(A a, B b, C c);
+impl_enter_many!(A a, B b, C c, D d);
+impl_enter_many!(A a, B b, C c, D d, E e);
+impl_enter_many!(A a, B b, C c, D d, E e, G g);
+
+/// Temporarily bind the inner values of all `owners` to this thread and
+/// evaluate `f` within that context.
+///
+/// The owners are entered in order and left in reverse order.
+pub fn enter_all<S, F, R>(owners: &[S], f: F) -> R
+where
+    S: Enter,
+    F: FnOnce(&[&S::Target]) -> R,
+{
+    fn nest<S: Enter, R>(owners: &[S], f: impl FnOnce() -> R) -> R {
+        match owners.split_first() {
+            Some((first, rest)) => first.enter(|_| nest(rest, f)),
+            None => f(),
+        }
+    }
+
+    nest(owners, || {
+        f(&owners.iter().map(|o| &**o).collect::<Vec<_>>())
+    })
+}
+
+/// Erased owners that can be entered in one scope.
+///
+/// Implemented by all collections and iterators of mutable references to
+/// erased owners, e.g., `&mut Vec<ErasedSnarc>`.
+pub trait EnterAll {
+    /// Temporarily bind the inner values of all owners to this thread and
+    /// evaluate `f` within that context.
+    ///
+    /// The owners are entered in order and left in reverse order, also when
+    /// entering one of them panics.
+    fn enter_all<F, R>(self, f: F) -> R
+    where
+        F: FnOnce() -> R;
+}
+
+impl<'a, 'b: 'a, I> EnterAll for I
+where
+    I: IntoIterator<Item = &'a mut ScopedErasedSnarc<'b>>,
+{
+    fn enter_all<F, R>(self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        fn nest<'a, 'b: 'a, R>(
+            mut owners: impl Iterator<Item = &'a mut ScopedErasedSnarc<'b>>,
+            f: impl FnOnce() -> R,
+        ) -> R {
+            match owners.next() {
+                Some(owner) => owner.enter(|| nest(owners, f)),
+                None => f(),
+            }
+        }
+
+        nest(self.into_iter(), f)
+    }
+}
+
+/// A bundle of erased owners that are entered in one scope.
+///
+/// The owners are entered in the order they were added and left in reverse
+/// order, also when unwinding. Owners defined by the same `snarc!` family share
+/// a context, so at most one of them may be part of a set.
+///
+/// Owners of values that borrow for `'a` may be added by way of
+/// `into_erased_scoped`.
+#[derive(Default)]
+pub struct EnterSet<'a> {
+    owners: Vec<ScopedErasedSnarc<'a>>,
+}
+
+impl<'a> EnterSet<'a> {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `owner` to the set, it will be entered after all current members.
+    pub fn push(&mut self, owner: impl Into<ScopedErasedSnarc<'a>>) {
+        self.owners.push(owner.into());
+    }
+
+    /// Returns the number of owners in the set.
+    pub fn len(&self) -> usize {
+        self.owners.len()
+    }
+
+    /// Returns `true` if the set contains no owners.
+    pub fn is_empty(&self) -> bool {
+        self.owners.is_empty()
+    }
+
+    /// Temporarily bind the inner values of all owners to this thread and
+    /// evaluate `f` within that context.
+    pub fn enter<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        self.owners.enter_all(f)
+    }
+
+    /// Returns the owners, in the order they were added.
+    pub fn into_inner(self) -> Vec<ScopedErasedSnarc<'a>> {
+        self.owners
+    }
+}
+
+impl<'a> From<Vec<ScopedErasedSnarc<'a>>> for EnterSet<'a> {
+    fn from(owners: Vec<ScopedErasedSnarc<'a>>) -> Self {
+        Self { owners }
+    }
+}
+
+impl<'a> FromIterator<ScopedErasedSnarc<'a>> for EnterSet<'a> {
+    fn from_iter<I: IntoIterator<Item = ScopedErasedSnarc<'a>>>(iter: I) -> Self {
+        Self {
+            owners: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> Extend<ScopedErasedSnarc<'a>> for EnterSet<'a> {
+    fn extend<I: IntoIterator<Item = ScopedErasedSnarc<'a>>>(&mut self, iter: I) {
+        self.owners.extend(iter);
+    }
+}
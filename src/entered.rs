@@ -0,0 +1,112 @@
+use std::any::TypeId;
+use std::cell::RefCell;
+
+struct Entered {
+    key: Option<TypeId>,
+    ptr: *mut (),
+    value_type: Option<TypeId>,
+    value: *const (),
+}
+
+thread_local!(static ENTERED: RefCell<Vec<Entered>> = Default::default());
+
+/// Records that the control block at `ptr` is entered on the current thread.
+///
+/// The record is removed when the returned guard is dropped. `key` is the
+/// `TypeId` of the control block, which distinguishes both the value type and
+/// the family of structs. `value` points to the inner value of type
+/// `value_type`.
+///
+/// Only values recorded this way are found by `self_ref` and `with_current`,
+/// see `record_entered_untyped`.
+#[doc(hidden)]
+pub fn record_entered(
+    key: TypeId,
+    ptr: *mut (),
+    value_type: TypeId,
+    value: *const (),
+) -> EnteredRecord {
+    ENTERED.with(|e| {
+        e.borrow_mut().push(Entered {
+            key: Some(key),
+            ptr,
+            value_type: Some(value_type),
+            value,
+        })
+    });
+    crate::recorder::trace_enter(ptr);
+    EnteredRecord { ptr }
+}
+
+/// Like `record_entered`, but for values that are not `'static` and thus have
+/// no `TypeId`.
+#[doc(hidden)]
+pub fn record_entered_untyped(ptr: *mut ()) -> EnteredRecord {
+    ENTERED.with(|e| {
+        e.borrow_mut().push(Entered {
+            key: None,
+            ptr,
+            value_type: None,
+            value: std::ptr::null(),
+        })
+    });
+    crate::recorder::trace_enter(ptr);
+    EnteredRecord { ptr }
+}
+
+/// Returns the innermost control block with the given `key` that is entered on
+/// the current thread.
+#[doc(hidden)]
+pub fn innermost_entered(key: TypeId) -> Option<*mut ()> {
+    ENTERED.with(|e| {
+        e.borrow()
+            .iter()
+            .rev()
+            .find(|e| e.key == Some(key))
+            .map(|e| e.ptr)
+    })
+}
+
+/// Evaluates `f` with the innermost value of type `T` that is bound to the
+/// current thread by `enter_with_self`.
+///
+/// Returns `None` if no such value is entered.
+pub fn with_current<T, F, R>(f: F) -> Option<R>
+where
+    T: 'static,
+    F: FnOnce(&T) -> R,
+{
+    let value = ENTERED.with(|e| {
+        e.borrow()
+            .iter()
+            .rev()
+            .find(|e| e.value_type == Some(TypeId::of::<T>()))
+            .map(|e| e.value)
+    })?;
+
+    // the value stays entered, and thus alive, while `f` is evaluated
+    Some(f(unsafe { &*value.cast::<T>() }))
+}
+
+#[doc(hidden)]
+pub struct EnteredRecord {
+    ptr: *mut (),
+}
+
+impl Drop for EnteredRecord {
+    fn drop(&mut self) {
+        // records are usually dropped in reverse order, but guards need not be
+        ENTERED.with(|e| {
+            let mut e = e.borrow_mut();
+            if let Some(i) = e.iter().rposition(|e| e.ptr == self.ptr) {
+                e.remove(i);
+            }
+        });
+    }
+}
+
+/// Returns the number of control blocks entered on the current thread.
+#[cfg(feature = "diagnostics")]
+pub(crate) fn entered_count() -> usize {
+    ENTERED.with(|e| e.borrow().len())
+}
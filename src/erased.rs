@@ -0,0 +1,245 @@
+use std::any::Any;
+use std::any::TypeId;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+
+use crate::Context;
+use crate::State;
+
+/// An owning pointer whose type was erased, for use by the erased owners.
+///
+/// Owners are but a pointer to their control block, so the pointer is stored
+/// inline, next to a table of the functions that operate on it. Unlike a
+/// `Box<dyn Context>`, erasing an owner thus does not allocate.
+///
+/// The owner may hold a value that borrows for `'a`. Only values that are
+/// `'static` have a `TypeId`, so only they may be accessed or downcast.
+#[doc(hidden)]
+pub struct ErasedOwner<'a> {
+    ptr: *mut (),
+    vtable: &'static VTable,
+    _lifetime: PhantomData<&'a ()>,
+}
+
+// owners that are not `Send` are only erased by way of `new_unsend`
+unsafe impl Send for ErasedOwner<'_> {}
+
+struct VTable {
+    send: bool,
+    any: Option<AnyVTable>,
+    type_name: unsafe fn(*mut ()) -> &'static str,
+    set: unsafe fn(*mut (), State),
+    is_entered: unsafe fn(*mut ()) -> bool,
+    drop: unsafe fn(*mut ()),
+}
+
+/// The part of a `VTable` that only owners of `'static` values have.
+struct AnyVTable {
+    owner: fn() -> TypeId,
+    value: unsafe fn(*mut ()) -> *const dyn Any,
+}
+
+struct VTableOf<S>(PhantomData<S>);
+
+impl<S: Context> VTableOf<S> {
+    const SCOPED: VTable = Self::vtable(true, None);
+
+    const fn vtable(send: bool, any: Option<AnyVTable>) -> VTable {
+        VTable {
+            send,
+            any,
+            type_name: |ptr| unsafe { with::<S, _>(ptr, |owner| owner.type_name()) },
+            set: |ptr, v| unsafe { with::<S, _>(ptr, |owner| owner.set(v)) },
+            is_entered: |ptr| unsafe { with::<S, _>(ptr, |owner| owner.is_entered()) },
+            drop: |ptr| drop(unsafe { S::from_raw(ptr) }),
+        }
+    }
+}
+
+impl<S: Context + 'static> VTableOf<S>
+where
+    S::Value: 'static,
+{
+    const SEND: VTable = Self::vtable(true, Some(Self::ANY));
+    const UNSEND: VTable = Self::vtable(false, Some(Self::ANY));
+
+    const ANY: AnyVTable = AnyVTable {
+        owner: TypeId::of::<S>,
+        value: |ptr| unsafe { with::<S, _>(ptr, |owner| owner.value() as *const dyn Any) },
+    };
+}
+
+/// Evaluates `f` with the owner `ptr` was obtained from, without dropping it.
+unsafe fn with<S: Context, R>(ptr: *mut (), f: impl FnOnce(&mut S) -> R) -> R {
+    let mut owner = ManuallyDrop::new(unsafe { S::from_raw(ptr) });
+    f(&mut owner)
+}
+
+impl ErasedOwner<'static> {
+    pub fn new<S>(owner: S) -> Self
+    where
+        S: Context + Send + 'static,
+        S::Value: 'static,
+    {
+        Self {
+            ptr: owner.into_raw(),
+            vtable: &VTableOf::<S>::SEND,
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Erases an owner that need not be `Send`.
+    ///
+    /// # Safety
+    ///
+    /// Unless `S` is `Send`, the erased owner must be kept on the current
+    /// thread, see `is_send`.
+    pub unsafe fn new_unsend<S>(owner: S) -> Self
+    where
+        S: Context + 'static,
+        S::Value: 'static,
+    {
+        Self {
+            ptr: owner.into_raw(),
+            vtable: &VTableOf::<S>::UNSEND,
+            _lifetime: PhantomData,
+        }
+    }
+}
+
+impl<'a> ErasedOwner<'a> {
+    /// Erases an owner whose value borrows for `'a`, which can then neither be
+    /// accessed nor downcast.
+    pub fn new_scoped<S: Context + Send + 'a>(owner: S) -> Self {
+        Self {
+            ptr: owner.into_raw(),
+            vtable: &VTableOf::<S>::SCOPED,
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Returns `false` if the owner was erased by way of `new_unsend`.
+    pub fn is_send(&self) -> bool {
+        self.vtable.send
+    }
+
+    /// Returns `true` if the owner is an `S`.
+    pub fn is<S: Context + 'static>(&self) -> bool {
+        match &self.vtable.any {
+            Some(any) => (any.owner)() == TypeId::of::<S>(),
+            None => false,
+        }
+    }
+
+    pub fn set(&mut self, v: State) {
+        unsafe { (self.vtable.set)(self.ptr, v) }
+    }
+
+    /// Returns a pointer to the inner value as a `dyn Any`, unless the value
+    /// is not `'static`.
+    pub fn value(&self) -> Option<*const dyn Any> {
+        let any = self.vtable.any.as_ref()?;
+        Some(unsafe { (any.value)(self.ptr) })
+    }
+
+    /// Returns the name of the inner value's type.
+    pub fn type_name(&self) -> &'static str {
+        unsafe { (self.vtable.type_name)(self.ptr) }
+    }
+
+    /// Returns `true` if the inner value is bound to the current thread by way
+    /// of `enter`.
+    pub fn is_entered(&self) -> bool {
+        unsafe { (self.vtable.is_entered)(self.ptr) }
+    }
+
+    /// Recovers the owner, provided it is an `S`.
+    pub fn downcast<S: Context + 'static>(self) -> Result<S, Self> {
+        if !self.is::<S>() {
+            return Err(self);
+        }
+
+        let this = ManuallyDrop::new(self);
+        Ok(unsafe { S::from_raw(this.ptr) })
+    }
+}
+
+impl Drop for ErasedOwner<'_> {
+    fn drop(&mut self) {
+        unsafe { (self.vtable.drop)(self.ptr) }
+    }
+}
+
+/// Stands in for an owner that was moved into an `ErasedEnterGuard`, which
+/// was then leaked.
+struct Leaked;
+
+unsafe impl Context for Leaked {
+    type Value = ();
+
+    fn into_raw(self) -> *mut () {
+        std::ptr::null_mut()
+    }
+
+    unsafe fn from_raw(_: *mut ()) -> Self {
+        Leaked
+    }
+
+    fn set(&mut self, _: State) {
+        crate::__panic!("Erased owner used after its EnterGuard was leaked.")
+    }
+
+    fn value(&self) -> *const Self::Value {
+        crate::__panic!("Erased owner used after its EnterGuard was leaked.")
+    }
+
+    fn type_name(&self) -> &'static str {
+        "<leaked>"
+    }
+
+    fn is_entered(&self) -> bool {
+        false
+    }
+}
+
+/// A guard that keeps the inner value of an erased owner bound to the current
+/// thread until it is dropped.
+///
+/// Created by [`ErasedSnarc::enter_guard`](crate::ErasedSnarc::enter_guard).
+/// The guard holds the owner while it exists. Should it be leaked, the owner
+/// is leaked as well and any further use of the erased owner panics.
+pub struct ErasedEnterGuard<'a> {
+    slot: &'a mut ErasedOwner<'static>,
+    owner: ManuallyDrop<ErasedOwner<'static>>,
+    _unsend: PhantomData<std::sync::MutexGuard<'static, ()>>,
+}
+
+impl<'a> ErasedEnterGuard<'a> {
+    #[doc(hidden)]
+    pub fn new(slot: &'a mut ErasedOwner<'static>) -> Self {
+        slot.set(State::Entered);
+
+        let owner = std::mem::replace(slot, ErasedOwner::new(Leaked));
+
+        Self {
+            slot,
+            owner: ManuallyDrop::new(owner),
+            _unsend: PhantomData,
+        }
+    }
+
+    /// Returns the name of the inner value's type, as `std::any::type_name`
+    /// would.
+    pub fn type_name(&self) -> &'static str {
+        self.owner.type_name()
+    }
+}
+
+impl Drop for ErasedEnterGuard<'_> {
+    fn drop(&mut self) {
+        let mut owner = unsafe { ManuallyDrop::take(&mut self.owner) };
+        owner.set(State::Default);
+
+        *self.slot = owner;
+    }
+}
@@ -0,0 +1,60 @@
+use crate::ErasedSnarc;
+use crate::Get;
+
+/// Convenience methods for the weak references of all `Snarc`s.
+pub trait SnarcRefExt<T> {
+    /// Evaluates `f` with a reference to the inner value.
+    ///
+    /// Returns `None` if the inner value is not bound to the current thread.
+    fn with<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&T) -> R;
+
+    /// Gets a reference to the inner value, panicking with `msg` if it is not
+    /// bound to the current thread.
+    fn expect_msg(&self, msg: &str) -> &T;
+
+    /// Gets a reference to a part of the inner value.
+    ///
+    /// Returns `None` if the inner value is not bound to the current thread.
+    fn map<'a, F, U>(&'a self, f: F) -> Option<&'a U>
+    where
+        T: 'a,
+        F: FnOnce(&'a T) -> &'a U;
+}
+
+impl<T, R: Get<T>> SnarcRefExt<T> for R {
+    #[track_caller]
+    fn with<F, U>(&self, f: F) -> Option<U>
+    where
+        F: FnOnce(&T) -> U,
+    {
+        self.get().map(f)
+    }
+
+    #[track_caller]
+    fn expect_msg(&self, msg: &str) -> &T {
+        self.get().expect(msg)
+    }
+
+    #[track_caller]
+    fn map<'a, F, U>(&'a self, f: F) -> Option<&'a U>
+    where
+        T: 'a,
+        F: FnOnce(&'a T) -> &'a U,
+    {
+        self.get().map(f)
+    }
+}
+
+/// Owning pointers that can be turned into an `ErasedSnarc`.
+pub trait IntoErased {
+    /// Erases the type argument of this owning pointer.
+    fn into_erased(self) -> ErasedSnarc;
+}
+
+impl<S: Into<ErasedSnarc>> IntoErased for S {
+    fn into_erased(self) -> ErasedSnarc {
+        self.into()
+    }
+}
@@ -0,0 +1,93 @@
+use std::any::Any;
+use std::sync::Mutex;
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    slots: Vec::new(),
+    free: Vec::new(),
+});
+
+struct Registry {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+}
+
+struct Slot {
+    generation: u32,
+    entry: Option<Box<dyn Any + Send>>,
+}
+
+/// A small integer token standing in for a weak reference.
+///
+/// Tokens are meant to be handed to foreign code in place of raw pointers. The
+/// weak reference is kept in a process-wide registry until the token is
+/// resolved. Every slot of the registry carries a generation, so that stale or
+/// forged tokens resolve to `None` rather than to a dangling reference.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FfiToken(u64);
+
+impl FfiToken {
+    #[doc(hidden)]
+    pub fn register<R: Any + Send>(r: R) -> Self {
+        let mut registry = lock();
+
+        let index = match registry.free.pop() {
+            Some(index) => index,
+            None => {
+                let index = u32::try_from(registry.slots.len()).expect("Too many tokens.");
+                registry.slots.push(Slot {
+                    generation: 1,
+                    entry: None,
+                });
+                index
+            }
+        };
+
+        let slot = &mut registry.slots[index as usize];
+        slot.entry = Some(Box::new(r));
+
+        Self(u64::from(slot.generation) << 32 | u64::from(index))
+    }
+
+    /// Removes the weak reference from the registry and returns it.
+    ///
+    /// Returns `None` if the token was already resolved, was never issued or
+    /// refers to a weak reference of a type other than `R`. In the latter case
+    /// the weak reference remains registered.
+    pub fn resolve<R: Any>(self) -> Option<R> {
+        let mut registry = lock();
+
+        let index = self.0 as u32;
+        let generation = (self.0 >> 32) as u32;
+
+        let slot = registry.slots.get_mut(index as usize)?;
+
+        if slot.generation != generation || !slot.entry.as_ref()?.is::<R>() {
+            return None;
+        }
+
+        let entry = slot.entry.take()?;
+        slot.generation = slot.generation.checked_add(1).unwrap_or(1);
+        registry.free.push(index);
+
+        entry.downcast().ok().map(|r| *r)
+    }
+
+    /// Returns the raw integer representation of this token.
+    pub fn into_raw(self) -> u64 {
+        self.0
+    }
+
+    /// Reconstitutes a token from its raw integer representation.
+    ///
+    /// Any integer is accepted; invalid tokens simply resolve to `None`.
+    pub fn from_raw(raw: u64) -> Self {
+        Self(raw)
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Registry> {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,137 @@
+//! Builds graphs of owners whose values reference each other.
+//!
+//! Nodes are [`declare`][Builder::declare()]d first, which allocates them and
+//! allows weak references to be created before their values exist. Once every
+//! value has been [`define`][Builder::define()]d, [`finish`][Builder::finish()]
+//! returns the owners. Weak references to a node do not resolve before its
+//! value is defined.
+//!
+//! ```
+//! use snarc::graph::Builder;
+//!
+//! snarc::snarc!(Snarc, Narc, SnarcRef);
+//!
+//! struct Node {
+//!     next: SnarcRef<Node>,
+//! }
+//!
+//! let mut builder = Builder::<Snarc<Node>>::new();
+//! let a = builder.declare();
+//! let b = builder.declare();
+//!
+//! let (a_ref, b_ref) = (builder.new_ref(&a), builder.new_ref(&b));
+//! builder.define(a, Node { next: b_ref });
+//! builder.define(b, Node { next: a_ref });
+//!
+//! let mut nodes = builder.finish();
+//! nodes[0].enter(|a| assert!(a.next.get().is_some()));
+//! ```
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+/// Implemented by owners that can be allocated before their value exists.
+///
+/// # Safety
+///
+/// `alloc_uninit` must return a pointer that stays valid until `init` turns
+/// it into an owner, and `placeholder` must return weak references to it.
+#[doc(hidden)]
+pub unsafe trait Node: Sized {
+    type Value;
+    type Ref;
+
+    fn alloc_uninit() -> *mut ();
+
+    /// Creates a weak reference to the uninitialized value at `ptr`.
+    fn placeholder(ptr: *mut ()) -> Self::Ref;
+
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `alloc_uninit` and not been
+    /// initialized before.
+    unsafe fn init(ptr: *mut (), value: Self::Value) -> Self;
+}
+
+/// A node that was declared, but whose value is yet to be defined.
+pub struct Declared {
+    builder: usize,
+    index: usize,
+}
+
+/// Allocates owners of type `S` up front and wires them once their values
+/// are defined.
+///
+/// Weak references to declared nodes are revoked until their values are
+/// defined. Nodes that are never defined are leaked, references to them never
+/// resolve.
+pub struct Builder<S: Node> {
+    id: usize,
+    nodes: Vec<(*mut (), Option<S>)>,
+}
+
+impl<S: Node> Builder<S> {
+    /// Creates an empty builder.
+    pub fn new() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Declares a new node, its value is defined later.
+    pub fn declare(&mut self) -> Declared {
+        self.nodes.push((S::alloc_uninit(), None));
+
+        Declared {
+            builder: self.id,
+            index: self.nodes.len() - 1,
+        }
+    }
+
+    /// Creates a weak reference to the value of the `declared` node.
+    ///
+    /// Panics if `declared` was declared by another builder.
+    pub fn new_ref(&self, declared: &Declared) -> S::Ref {
+        self.check(declared);
+
+        S::placeholder(self.nodes[declared.index].0)
+    }
+
+    /// Defines the `value` of the `declared` node.
+    ///
+    /// Panics if `declared` was declared by another builder.
+    pub fn define(&mut self, declared: Declared, value: S::Value) {
+        self.check(&declared);
+
+        let (ptr, owner) = &mut self.nodes[declared.index];
+        debug_assert!(owner.is_none(), "`Declared` is consumed by `define`.");
+
+        *owner = Some(unsafe { S::init(*ptr, value) });
+    }
+
+    /// Returns the owners, in the order they were declared.
+    ///
+    /// Panics if a declared node was not defined.
+    pub fn finish(self) -> Vec<S> {
+        self.nodes
+            .into_iter()
+            .map(|(_, owner)| owner.expect("Declared node was not defined."))
+            .collect()
+    }
+
+    fn check(&self, declared: &Declared) {
+        assert_eq!(
+            declared.builder, self.id,
+            "Node was declared by another Builder."
+        );
+    }
+}
+
+impl<S: Node> Default for Builder<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,218 @@
+use std::cell::Cell;
+use std::marker::PhantomPinned;
+use std::mem::ManuallyDrop;
+use std::pin::Pin;
+
+use crate::Balance;
+use crate::Binding;
+use crate::Canary;
+use crate::Count;
+use crate::State;
+
+/// A sendable, owning pointer that stores its `T` inline, without allocating.
+///
+/// Weak references point into the owner, so it must be pinned before they are
+/// created. Like the structs defined by `snarc!(per_instance; …)`, each
+/// `InlineSnarc` has a context of its own.
+///
+/// Because its storage cannot outlive it, dropping an `InlineSnarc` while weak
+/// references to it remain panics and then aborts the process.
+pub struct InlineSnarc<T> {
+    head: Canary,
+    count: Cell<Count>,
+    balance: Balance,
+    binding: Binding,
+    value: ManuallyDrop<T>,
+    tail: Canary,
+    _pinned: PhantomPinned,
+}
+
+unsafe impl<T: Send> Send for InlineSnarc<T> {}
+unsafe impl<T: Sync> Sync for InlineSnarc<T> {}
+
+impl<T> InlineSnarc<T> {
+    /// Creates a new `InlineSnarc` with the given inner `value`.
+    pub fn new(value: T) -> Self {
+        Self {
+            head: Canary::head(),
+            count: Cell::new(0),
+            balance: Default::default(),
+            binding: Default::default(),
+            value: ManuallyDrop::new(value),
+            tail: Canary::tail(),
+            _pinned: PhantomPinned,
+        }
+    }
+
+    #[inline(always)]
+    fn check(&self) {
+        Canary::check(&self.head, &self.tail)
+    }
+
+    /// Gets a mutable reference to the inner value.
+    pub fn value_mut(self: Pin<&mut Self>) -> &mut T {
+        // the value itself is not pinned, only the owner is
+        unsafe { &mut self.get_unchecked_mut().value }
+    }
+
+    /// Creates a new non-owning reference to the inner value.
+    pub fn new_ref(self: Pin<&Self>) -> InlineSnarcRef<T> {
+        crate::increment(&self.count);
+
+        InlineSnarcRef {
+            ptr: self.get_ref(),
+        }
+    }
+
+    /// Temporarily bind the inner value to this thread and evaluate `f` within
+    /// that context.
+    pub fn enter<F, R>(self: Pin<&mut Self>, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let this = self.into_ref().get_ref();
+        this.check();
+
+        if this.binding.get() == State::Entered {
+            crate::__panic!("Another InlineSnarc is already entered.")
+        }
+
+        this.binding.set(State::Entered);
+        this.balance.reconcile(&this.count);
+
+        let _guard = scopeguard::guard((), |_| this.binding.set(State::Default));
+
+        f(&this.value)
+    }
+}
+
+impl<T> std::ops::Deref for InlineSnarc<T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> Drop for InlineSnarc<T> {
+    fn drop(&mut self) {
+        self.check();
+
+        {
+            self.binding.set(State::Entered);
+
+            let _guard = scopeguard::guard((), |_| self.binding.set(State::Default));
+
+            unsafe {
+                // destroy the contained object
+                ManuallyDrop::drop(&mut self.value);
+            }
+        }
+
+        self.balance.reconcile(&self.count);
+
+        if self.count.get() != 0 {
+            // the references would dangle, so unwinding must not get past here
+            let _abort = scopeguard::guard((), |_| std::process::abort());
+
+            crate::__panic!("InlineSnarc dropped while references to it remain");
+        }
+    }
+}
+
+/// A sendable, non-owning pointer to the `T` of an [`InlineSnarc`].
+pub struct InlineSnarcRef<T> {
+    ptr: *const InlineSnarc<T>,
+}
+
+unsafe impl<T> Send for InlineSnarcRef<T> {}
+unsafe impl<T> Sync for InlineSnarcRef<T> {}
+
+impl<T> InlineSnarcRef<T> {
+    #[inline(always)]
+    fn inner(&self) -> &InlineSnarc<T> {
+        unsafe { &*self.ptr }
+    }
+
+    /// Gets a reference to the inner value.
+    ///
+    /// Returns `None` if the owner did not currently bind the inner value to
+    /// the current thread.
+    #[track_caller]
+    pub fn get(&self) -> Option<&T> {
+        let inner = self.inner();
+        inner.check();
+
+        if inner.binding.get().is_set() {
+            Some(&inner.value)
+        } else {
+            crate::failed_get();
+            None
+        }
+    }
+}
+
+impl<T> Clone for InlineSnarcRef<T> {
+    fn clone(&self) -> Self {
+        let inner = self.inner();
+
+        if inner.binding.get().is_set() {
+            crate::increment(&inner.count);
+
+            Self { ptr: self.ptr }
+        } else {
+            crate::__panic!("InlineSnarcRef::clone() outside of InlineSnarc::enter(…)")
+        }
+    }
+}
+
+impl<T> Drop for InlineSnarcRef<T> {
+    fn drop(&mut self) {
+        let inner = self.inner();
+        inner.check();
+
+        if inner.binding.get().is_set() {
+            inner.count.set(inner.count.get() - 1);
+        } else {
+            // reconciled on the next `enter`
+            inner.balance.decrement();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::pin;
+
+    use super::InlineSnarc;
+
+    #[test]
+    fn inline_refs_get_within_enter_only() {
+        let mut snarc = pin!(InlineSnarc::new(1));
+        *snarc.as_mut().value_mut() += 1;
+
+        let snarc_ref = snarc.as_ref().new_ref();
+        assert_eq!(snarc_ref.get(), None);
+
+        snarc.as_mut().enter(|_| {
+            assert_eq!(snarc_ref.get(), Some(&2));
+            drop(snarc_ref.clone());
+            drop(snarc_ref);
+        });
+    }
+
+    #[test]
+    fn inline_refs_are_dropped_outside_enter() {
+        let mut snarc = pin!(InlineSnarc::new(1));
+
+        let snarc_ref = snarc.as_ref().new_ref();
+        std::thread::scope(|s| {
+            s.spawn(move || drop(snarc_ref));
+        });
+
+        let snarc_ref = snarc.as_ref().new_ref();
+        snarc.as_mut().enter(|_| drop(snarc_ref.clone()));
+        drop(snarc_ref);
+    }
+}
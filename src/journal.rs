@@ -0,0 +1,99 @@
+use std::cell::Ref;
+use std::cell::RefCell;
+
+/// A reversible mutation of a `T`.
+pub trait Command<T> {
+    /// Applies this command to `target`.
+    fn apply(&mut self, target: &mut T);
+
+    /// Reverts the effects of the preceding [`apply`][Command::apply()].
+    fn undo(&mut self, target: &mut T);
+}
+
+/// A value that is mutated exclusively through recorded commands.
+///
+/// Wrapped by an owning pointer, e.g., `Snarc<Journal<Document, Edit>>`, weak
+/// references may [`execute`][Journal::execute()] commands within the `enter`
+/// context. Executed commands can be undone, redone and replayed.
+///
+/// Commands must not access the journal they are applied by.
+pub struct Journal<T, C> {
+    value: RefCell<T>,
+    done: RefCell<Vec<C>>,
+    undone: RefCell<Vec<C>>,
+}
+
+impl<T, C: Command<T>> Journal<T, C> {
+    /// Creates a new `Journal` with an empty history.
+    pub fn new(value: T) -> Self {
+        Self {
+            value: RefCell::new(value),
+            done: Default::default(),
+            undone: Default::default(),
+        }
+    }
+
+    /// Immutably borrows the current value.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.value.borrow()
+    }
+
+    /// Applies `command` and records it, discarding any undone commands.
+    pub fn execute(&self, mut command: C) {
+        command.apply(&mut self.value.borrow_mut());
+        self.done.borrow_mut().push(command);
+        self.undone.borrow_mut().clear();
+    }
+
+    /// Undoes the most recently applied command.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    pub fn undo(&self) -> bool {
+        let command = self.done.borrow_mut().pop();
+
+        match command {
+            Some(mut command) => {
+                command.undo(&mut self.value.borrow_mut());
+                self.undone.borrow_mut().push(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reapplies the most recently undone command.
+    ///
+    /// Returns `false` if there was nothing to redo.
+    pub fn redo(&self) -> bool {
+        let command = self.undone.borrow_mut().pop();
+
+        match command {
+            Some(mut command) => {
+                command.apply(&mut self.value.borrow_mut());
+                self.done.borrow_mut().push(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the applied commands, oldest first.
+    pub fn history(&self) -> Ref<'_, [C]> {
+        Ref::map(self.done.borrow(), |done| done.as_slice())
+    }
+
+    /// Applies copies of all applied commands, oldest first, to `target`.
+    pub fn replay(&self, target: &mut T)
+    where
+        C: Clone,
+    {
+        for command in self.done.borrow().iter() {
+            command.clone().apply(target);
+        }
+    }
+
+    /// Consumes the journal, returning the current value.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
@@ -0,0 +1,57 @@
+use std::ptr;
+use std::sync::atomic::AtomicPtr;
+use std::sync::atomic::Ordering;
+
+/// A box that is only allocated on first use, a single null pointer until
+/// then.
+pub(crate) struct LazyBox<T> {
+    ptr: AtomicPtr<T>,
+}
+
+impl<T> Default for LazyBox<T> {
+    fn default() -> Self {
+        Self {
+            ptr: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+impl<T> LazyBox<T> {
+    pub(crate) fn get(&self) -> Option<&T> {
+        // non-null pointers stem from `Box::into_raw` and are only freed on drop
+        unsafe { self.ptr.load(Ordering::Acquire).as_ref() }
+    }
+
+    /// Allocates the box unless it is already, racing threads drop their
+    /// value.
+    pub(crate) fn get_or_init(&self, init: impl FnOnce() -> T) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+
+        let new = Box::into_raw(Box::new(init()));
+        match self
+            .ptr
+            .compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => unsafe { &*new },
+            Err(current) => {
+                // `new` was never published
+                drop(unsafe { Box::from_raw(new) });
+                unsafe { &*current }
+            }
+        }
+    }
+}
+
+impl<T> Drop for LazyBox<T> {
+    fn drop(&mut self) {
+        let ptr = *self.ptr.get_mut();
+        if !ptr.is_null() {
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+    }
+}
+
+unsafe impl<T: Send + Sync> Send for LazyBox<T> {}
+unsafe impl<T: Send + Sync> Sync for LazyBox<T> {}
@@ -20,13 +20,18 @@
 //!   references. By invoking the `enter` method of the strong/owning reference
 //!   its value may be temporarily bound to the current thread.
 //!
-//! - Weak references may only be created and dropped within the `enter` context
-//!   of a strong/owning reference. This ensures that the required counter
-//!   increments and decrements are race-free.
+//! - Weak references may only be created within the `enter` context of a
+//!   strong/owning reference. This ensures that the required counter increments
+//!   are race-free. Dropping them elsewhere queues the decrement until the next
+//!   `enter`.
 //!
 //! - Calling the `get` method on a weak reference returns an `Option<&T>`, that
 //!   is `Some(&t)` iff called from within the `enter` context of a strong
-//!   reference.
+//!   reference. Prefer `borrow`, whose guard cannot outlive that context.
+//!
+//! - `Narc`, the unsendable owner, binds its value only within `enter` as well,
+//!   since it grants mutable access otherwise. `ErasedNarc` cannot access the
+//!   value, so it keeps the value bound for its whole lifetime instead.
 //!
 //! # What is it good for?
 //!
@@ -96,7 +101,11 @@
 //! **Drawbacks**
 //!  - inefficient, due to locking overhead
 //!
-//! ### Use `Snarc<RefCell<T>` and `SnarcRef<RefCell<T>>`
+//! ### Use `Snarc<T>` and `SnarcRef<T>`
+//!
+//! Weak references borrow the value like a `RefCell` would, by way of `borrow`.
+//! Mutable access requires interior mutability, e.g., a `Cell`, or the
+//! `unsafe` `borrow_mut`, which does not track the owner's own references.
 //!
 //! **Advantages**
 //!  - highly efficient, minor overhead of reference counting
@@ -111,10 +120,56 @@
 #![warn(clippy::wildcard_imports)]
 #![warn(missing_docs)]
 
+mod callback;
+
 #[doc(hidden)]
 #[cfg(feature = "default")]
 pub mod default;
 
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+
+mod defer;
+mod dispose;
+mod dormant;
+mod double;
+
+mod enter_many;
+mod entered;
+mod erased;
+mod ext;
+mod inline;
+mod journal;
+mod lazy_box;
+mod mailbox;
+mod mapped;
+mod notify;
+mod once_ref;
+mod or_arc;
+mod part;
+mod pin;
+mod ref_guard;
+mod ref_mut;
+#[cfg(feature = "serde")]
+mod serialize;
+mod slot;
+mod static_cell;
+mod static_slot;
+mod type_map;
+mod watch;
+
+mod ffi;
+
+pub mod graph;
+
+pub mod prelude;
+
+pub mod recorder;
+
+pub mod registry;
+
+pub mod sync;
+
 #[cfg(feature = "thread_local")]
 pub mod thread_local;
 
@@ -125,14 +180,597 @@ pub use default::*;
 #[cfg(test)]
 mod tests;
 
+#[cfg(all(kani, feature = "default"))]
+mod proofs;
+
+pub use callback::CallbackSet;
+#[doc(hidden)]
+pub use defer::DeferQueue;
+#[doc(hidden)]
+#[cfg(feature = "defmt")]
+pub use defmt;
+pub use dispose::AsyncDispose;
+pub use dormant::Dormant;
+pub use double::DoubleBuffer;
+pub use enter_many::enter_all;
+pub use enter_many::Enter;
+pub use enter_many::EnterAll;
+pub use enter_many::EnterMany;
+pub use enter_many::EnterSet;
+#[doc(hidden)]
+pub use entered::innermost_entered;
+#[doc(hidden)]
+pub use entered::record_entered;
+#[doc(hidden)]
+pub use entered::record_entered_untyped;
+pub use entered::with_current;
+#[doc(hidden)]
+pub use entered::EnteredRecord;
+pub use erased::ErasedEnterGuard;
+#[doc(hidden)]
+pub use erased::ErasedOwner;
+pub use ext::IntoErased;
+pub use ext::SnarcRefExt;
+pub use ffi::FfiToken;
+pub use inline::InlineSnarc;
+pub use inline::InlineSnarcRef;
+pub use journal::Command;
+pub use journal::Journal;
+#[doc(hidden)]
+pub use mailbox::Mailbox;
+pub use mapped::MappedSnarcRef;
+pub use notify::ChangeListener;
+pub use notify::Changed;
+#[doc(hidden)]
+pub use notify::Notifier;
+pub use once_ref::OnceRef;
+#[doc(hidden)]
+pub use or_arc::Get;
+pub use or_arc::SnarcOrArc;
+#[doc(hidden)]
+pub use part::SnarcPart;
+pub use pin::PinnedRef;
+pub use recorder::trace_new_ref;
+pub use ref_guard::RefGuard;
+pub use ref_guard::RefMut;
+pub use ref_mut::GetMut;
+pub use ref_mut::SnarcRefMut;
+#[doc(hidden)]
+#[cfg(feature = "serde")]
+pub use serde;
+#[doc(hidden)]
+#[cfg(feature = "serde")]
+pub use serialize::serialize_value;
+#[cfg(feature = "serde")]
+pub use serialize::AccessOrSerError;
+#[cfg(feature = "serde")]
+pub use serialize::SerializeEntered;
+pub use slot::ErasedSlot;
+pub use static_cell::StaticSnarcCell;
+pub use static_cell::StaticSnarcRef;
+pub use static_slot::SnarcStaticSlot;
+pub use type_map::SnarcTypeMap;
+pub use watch::SnarcWatch;
+pub use watch::SnarcWatchRef;
+
+/// Owning pointers that may be erased.
+///
+/// # Safety
+///
+/// An owner must consist of nothing but the pointer returned by `into_raw`,
+/// which `from_raw` turns back into the very same owner.
 #[doc(hidden)]
-pub trait Context {
+pub unsafe trait Context {
+    /// The type of the inner value.
+    type Value;
+
+    fn into_raw(self) -> *mut ();
+
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `into_raw` of the same type.
+    unsafe fn from_raw(ptr: *mut ()) -> Self;
+
     fn set(&mut self, v: State);
+
+    /// Returns a pointer to the inner value.
+    fn value(&self) -> *const Self::Value;
+
+    /// Returns the name of the inner value's type.
+    fn type_name(&self) -> &'static str;
+
+    /// Returns `true` if the inner value is bound to the current thread by way
+    /// of `enter`.
+    fn is_entered(&self) -> bool;
 }
 
-#[doc(hidden)]
+/// The error returned when accessing an erased value as the wrong type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WrongType;
+
+impl std::fmt::Display for WrongType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the erased value is of another type")
+    }
+}
+
+impl std::error::Error for WrongType {}
+
+/// The error returned when entering a context that is already entered.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AlreadyEntered;
+
+impl std::fmt::Display for AlreadyEntered {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("another value of the same context is already entered")
+    }
+}
+
+impl std::error::Error for AlreadyEntered {}
+
+/// The error returned when memory could not be allocated.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AllocError;
+
+impl std::fmt::Display for AllocError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("memory allocation failed")
+    }
+}
+
+impl std::error::Error for AllocError {}
+
+/// The integer type used to count weak references.
+///
+/// Defaults to `usize`. The `count-u32` and `count-u16` features shrink the
+/// allocations at the cost of limiting the number of weak references per
+/// value. Should both be enabled, the narrower one is used.
+#[doc(hidden)]
+#[cfg(feature = "count-u16")]
+pub type Count = u16;
+
+#[doc(hidden)]
+#[cfg(all(feature = "count-u32", not(feature = "count-u16")))]
+pub type Count = u32;
+
+#[doc(hidden)]
+#[cfg(not(any(feature = "count-u32", feature = "count-u16")))]
+pub type Count = usize;
+
+/// Panics like `panic!`, but through `defmt::panic!` given the `defmt`
+/// feature, so the arguments must suit both.
+#[doc(hidden)]
+#[cfg(not(feature = "defmt"))]
+#[macro_export]
+macro_rules! __panic {
+    (@display $msg:expr) => {
+        panic!("{}", $msg)
+    };
+    ($($arg:tt)*) => {
+        panic!($($arg)*)
+    };
+}
+
+#[doc(hidden)]
+#[cfg(feature = "defmt")]
+#[macro_export]
+macro_rules! __panic {
+    (@display $msg:expr) => {
+        $crate::defmt::panic!("{}", $crate::defmt::Display2Format(&$msg))
+    };
+    ($($arg:tt)*) => {
+        $crate::defmt::panic!($($arg)*)
+    };
+}
+
+/// Guards a control block against corruption.
+///
+/// The tail canary follows the value in the control blocks of `snarc!`, so
+/// that overruns of the value are detected. In the `thread_local` module, the
+/// value may be unsized and must come last, so the tail canary precedes it and
+/// only corruption of the header is detected.
+///
+/// Without the `debug-canary` feature, canaries are zero-sized and checking
+/// them is a no-op.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Canary {
+    #[cfg(feature = "debug-canary")]
+    magic: u64,
+}
+
+impl Canary {
+    #[cfg(feature = "debug-canary")]
+    const HEAD: u64 = u64::from_be_bytes(*b"snarc<<<");
+    #[cfg(feature = "debug-canary")]
+    const TAIL: u64 = u64::from_be_bytes(*b">>>snarc");
+    #[cfg(feature = "debug-canary")]
+    const DEAD: u64 = u64::from_be_bytes(*b"deadbeef");
+
+    pub fn head() -> Self {
+        Self {
+            #[cfg(feature = "debug-canary")]
+            magic: Self::HEAD,
+        }
+    }
+
+    pub fn tail() -> Self {
+        Self {
+            #[cfg(feature = "debug-canary")]
+            magic: Self::TAIL,
+        }
+    }
+
+    /// Aborts the process unless `head` and `tail` are intact.
+    #[inline(always)]
+    pub fn check(head: &Self, tail: &Self) {
+        #[cfg(feature = "debug-canary")]
+        if head.magic != Self::HEAD || tail.magic != Self::TAIL {
+            let cause = if head.magic == Self::DEAD || tail.magic == Self::DEAD {
+                "use after free"
+            } else {
+                "memory corruption or mismatched layouts"
+            };
+
+            #[cfg(not(feature = "defmt"))]
+            eprintln!(
+                "snarc: corrupted control block (head {:#018x}, tail {:#018x}), likely due to {}",
+                head.magic, tail.magic, cause
+            );
+            #[cfg(feature = "defmt")]
+            defmt::error!(
+                "snarc: corrupted control block (head {=u64:#x}, tail {=u64:#x}), likely due to {=str}",
+                head.magic,
+                tail.magic,
+                cause
+            );
+            std::process::abort();
+        }
+
+        #[cfg(not(feature = "debug-canary"))]
+        let _ = (head, tail);
+    }
+}
+
+#[cfg(feature = "debug-canary")]
+impl Drop for Canary {
+    fn drop(&mut self) {
+        unsafe { std::ptr::write_volatile(&mut self.magic, Self::DEAD) };
+    }
+}
+
+/// Widens `count` to a `usize`, whichever width it has.
+#[doc(hidden)]
+#[inline(always)]
+#[allow(clippy::unnecessary_cast)]
+pub fn widen(count: Count) -> usize {
+    count as usize
+}
+
+/// Counts the weak references cloned outside of `enter`, given the
+/// `atomic-clone` feature.
+///
+/// Those references cannot touch the non-atomic count, so they are added to
+/// the `Balance`, just like drops outside of `enter`. `Detached` itself is
+/// zero-sized; without the feature, cloning outside of `enter` panics and
+/// `try_increment` folds to `false`.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct Detached;
+
+impl Detached {
+    pub const ENABLED: bool = cfg!(feature = "atomic-clone");
+
+    /// Counts a reference cloned outside of `enter`, unless the feature is
+    /// disabled.
+    #[inline(always)]
+    pub fn try_increment(balance: &Balance) -> bool {
+        #[cfg(feature = "atomic-clone")]
+        balance.increment();
+
+        #[cfg(not(feature = "atomic-clone"))]
+        let _ = balance;
+
+        Self::ENABLED
+    }
+}
+
+/// Queues the weak references dropped, or cloned by way of `Detached`,
+/// outside of `enter`.
+///
+/// Those references cannot touch the non-atomic count, so their balance is
+/// kept here, lock-free, until it is reconciled with the count on the next
+/// `enter`.
+///
+/// Once the owner is gone, the count is handed over to the balance, see
+/// `orphan`, so that whichever reference is dropped last frees the control
+/// block.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct Balance {
+    balance: std::sync::atomic::AtomicIsize,
+}
+
+impl Balance {
+    /// Added to the balance once the owner is gone, far beyond any number of
+    /// references.
+    const ORPHANED: isize = 1 << (isize::BITS - 2);
+
+    /// Counts a reference cloned outside of `enter`, or from another to an
+    /// orphaned control block, whose references are counted here alone, see
+    /// `orphan`.
+    #[inline(always)]
+    pub fn increment(&self) {
+        self.balance
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Queues a reference dropped outside of `enter`.
+    ///
+    /// Returns `true` if it was the last reference to an orphaned control
+    /// block, which the caller must then free. Otherwise this must be the last
+    /// access to the control block, as it may be freed by another thread.
+    #[inline(always)]
+    pub fn decrement(&self) -> bool {
+        self.balance
+            .fetch_sub(1, std::sync::atomic::Ordering::AcqRel)
+            == Self::ORPHANED + 1
+    }
+
+    /// Hands the `count` over to the balance, as the owner is gone.
+    ///
+    /// Returns `true` if no references remain, in which case the caller must
+    /// free the control block. Otherwise the last reference frees it.
+    pub fn orphan(&self, count: Count) -> bool {
+        let count = isize::try_from(widen(count))
+            .unwrap_or_else(|_| crate::__panic!("Too many references."));
+        let balance = self
+            .balance
+            .fetch_add(Self::ORPHANED + count, std::sync::atomic::Ordering::AcqRel);
+
+        balance + count == 0
+    }
+
+    /// Returns `true` once `orphan` was called, after which the count must no
+    /// longer be used.
+    #[inline(always)]
+    pub fn is_orphaned(&self) -> bool {
+        self.balance.load(std::sync::atomic::Ordering::Acquire) >= Self::ORPHANED
+    }
+
+    /// Moves the queued clones and drops to `count`, which must only be
+    /// accessed by the current thread.
+    #[inline(always)]
+    pub fn reconcile(&self, count: &std::cell::Cell<Count>) {
+        let balance = self.balance.swap(0, std::sync::atomic::Ordering::Acquire);
+
+        if balance != 0 {
+            let refs = widen(count.get())
+                .checked_add_signed(balance)
+                .unwrap_or_else(|| crate::__panic!("Too many references."));
+            count.set(
+                Count::try_from(refs).unwrap_or_else(|_| crate::__panic!("Too many references.")),
+            );
+        }
+    }
+
+    /// Returns the number of references, given the not yet reconciled `count`.
+    #[inline(always)]
+    pub fn refs(&self, count: Count) -> usize {
+        let balance = self.balance.load(std::sync::atomic::Ordering::Acquire);
+
+        if balance >= Self::ORPHANED {
+            return (balance - Self::ORPHANED).unsigned_abs();
+        }
+
+        widen(count)
+            .checked_add_signed(balance)
+            .unwrap_or_else(|| crate::__panic!("Too many references."))
+    }
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn increment(count: &std::cell::Cell<Count>) {
+    count.set(
+        count
+            .get()
+            .checked_add(1)
+            .unwrap_or_else(|| crate::__panic!("Too many references.")),
+    );
+}
+
+/// Overwrites the bytes of the dropped or moved out value at `value` with
+/// zeros, given the `zeroize` feature.
+#[doc(hidden)]
+#[inline(always)]
+pub unsafe fn scrub<T: ?Sized>(value: *mut T) {
+    // the size of unsized values only depends on the pointer's metadata
+    scrub_bytes(value.cast(), std::mem::size_of_val(&*value));
+}
+
+/// Overwrites `len` bytes at `bytes` with zeros, given the `zeroize` feature.
+#[inline(always)]
+pub(crate) unsafe fn scrub_bytes(bytes: *mut u8, len: usize) {
+    #[cfg(feature = "zeroize")]
+    {
+        let bytes = std::slice::from_raw_parts_mut(bytes.cast::<std::mem::MaybeUninit<u8>>(), len);
+        zeroize::Zeroize::zeroize(bytes);
+    }
+
+    #[cfg(not(feature = "zeroize"))]
+    let _ = (bytes, len);
+}
+
+/// Records the caller of a `get` that returned `None`, given the
+/// `diagnostics` feature.
+#[doc(hidden)]
+#[inline(always)]
+#[track_caller]
+pub fn failed_get() {
+    #[cfg(feature = "diagnostics")]
+    diagnostics::record(std::panic::Location::caller());
+}
+
+/// Panics unless all guards created by `borrow` and `get_mut` were dropped, as
+/// a context is left.
+#[doc(hidden)]
+#[inline(always)]
+pub fn check_borrows(borrows: usize) {
+    // guards created within the context are dropped while unwinding
+    if borrows != 0 && !std::thread::panicking() {
+        crate::__panic!("A borrow guard outlived the enter(…) context.")
+    }
+}
+
+/// Counts the guards borrowing a value, like `RefCell` does.
+///
+/// Positive counts are shared borrows, `-1` is an exclusive one.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct BorrowFlag(std::cell::Cell<isize>);
+
+impl BorrowFlag {
+    /// Registers a shared borrow, unless the value is borrowed exclusively.
+    pub fn try_borrow(&self) -> bool {
+        let flag = self.0.get();
+
+        if flag < 0 {
+            return false;
+        }
+
+        self.0.set(flag + 1);
+        true
+    }
+
+    /// Registers an exclusive borrow, unless the value is borrowed at all.
+    pub fn try_borrow_mut(&self) -> bool {
+        if self.0.get() != 0 {
+            return false;
+        }
+
+        self.0.set(-1);
+        true
+    }
+
+    /// Undoes a `try_borrow` or `try_borrow_mut`.
+    pub fn release(&self) {
+        let flag = self.0.get();
+        self.0.set(if flag < 0 { 0 } else { flag - 1 });
+    }
+
+    /// Returns the number of guards.
+    pub fn count(&self) -> usize {
+        self.0.get().unsigned_abs()
+    }
+}
+
+/// Lets weak references clone the value from any thread, while the owner
+/// neither enters nor mutates it, see `SnarcRef::snapshot`.
+///
+/// Entering the value, or accessing it mutably, locks snapshots out until the
+/// context is left again, which no mutable reference can outlive.
+///
+/// Without the `snapshot` feature, locks are zero-sized and never taken.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct SnapshotLock {
+    #[cfg(feature = "snapshot")]
+    state: std::sync::atomic::AtomicU8,
+}
+
+impl SnapshotLock {
+    #[cfg(feature = "snapshot")]
+    const IDLE: u8 = 0;
+    #[cfg(feature = "snapshot")]
+    const READING: u8 = 1;
+    #[cfg(feature = "snapshot")]
+    const WRITING: u8 = 2;
+    #[cfg(feature = "snapshot")]
+    const CLOSED: u8 = 3;
+
+    /// Begins a snapshot, unless the value is entered, mutated or dropped.
+    #[cfg(not(feature = "snapshot"))]
+    pub fn try_read(&self) -> bool {
+        false
+    }
+
+    /// Begins a snapshot, unless the value is entered, mutated or dropped.
+    #[cfg(feature = "snapshot")]
+    pub fn try_read(&self) -> bool {
+        use std::sync::atomic::Ordering;
+
+        self.state
+            .compare_exchange(
+                Self::IDLE,
+                Self::READING,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+
+    /// Ends a snapshot begun by `try_read`.
+    pub fn end_read(&self) {
+        #[cfg(feature = "snapshot")]
+        self.state
+            .store(Self::IDLE, std::sync::atomic::Ordering::Release);
+    }
+
+    /// Locks snapshots out, waiting for one in progress to end.
+    #[inline]
+    pub fn lock(&self) {
+        #[cfg(feature = "snapshot")]
+        {
+            use std::sync::atomic::Ordering;
+
+            loop {
+                match self.state.compare_exchange_weak(
+                    Self::IDLE,
+                    Self::WRITING,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) | Err(Self::WRITING) | Err(Self::CLOSED) => return,
+                    Err(Self::READING) => std::thread::yield_now(),
+                    Err(_) => {}
+                }
+            }
+        }
+    }
+
+    /// Undoes `lock`, unless snapshots were locked out for good.
+    #[inline]
+    pub fn unlock(&self) {
+        #[cfg(feature = "snapshot")]
+        {
+            use std::sync::atomic::Ordering;
+
+            let _ = self.state.compare_exchange(
+                Self::WRITING,
+                Self::IDLE,
+                Ordering::Release,
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    /// Locks snapshots out for good, before the value is dropped.
+    #[inline]
+    pub fn close(&self) {
+        #[cfg(feature = "snapshot")]
+        {
+            self.lock();
+            self.state
+                .store(Self::CLOSED, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub enum State {
+    #[default]
     Default,
     Unsend,
     Entered,
@@ -148,27 +786,152 @@ impl State {
     }
 }
 
-impl Default for State {
-    fn default() -> Self {
-        State::Default
+/// Binds a `State` to a single thread.
+///
+/// Used by `snarc!(per_instance; …)` to give every control block its own
+/// context. Threads other than the bound one observe `State::Default`.
+///
+/// The second field counts nested `try_enter`s, the third tracks the guards
+/// created by `borrow` and `get_mut`. They are only accessed by the thread the
+/// value is entered on.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct Binding(
+    std::sync::atomic::AtomicUsize,
+    std::cell::Cell<usize>,
+    BorrowFlag,
+);
+
+impl Binding {
+    const STATE_BITS: u32 = 2;
+
+    pub fn get(&self) -> State {
+        let binding = self.0.load(std::sync::atomic::Ordering::Acquire);
+
+        if binding >> Self::STATE_BITS != thread_id() {
+            State::Default
+        } else if binding & 1 == 1 {
+            State::Unsend
+        } else {
+            State::Entered
+        }
+    }
+
+    /// Binds the value as entered to the current thread, unless it is bound
+    /// otherwise already. Entering again on the same thread nests.
+    pub fn try_enter(&self) -> bool {
+        let entered = thread_id() << Self::STATE_BITS | 2;
+
+        match self.0.compare_exchange(
+            0,
+            entered,
+            std::sync::atomic::Ordering::AcqRel,
+            std::sync::atomic::Ordering::Acquire,
+        ) {
+            Ok(_) => {}
+            Err(binding) if binding == entered => {}
+            Err(_) => return false,
+        }
+
+        self.1.set(self.1.get() + 1);
+        true
+    }
+
+    /// Undoes one `try_enter`, unbinding the value once the outermost one is
+    /// undone.
+    pub fn exit(&self) {
+        let depth = self.1.get() - 1;
+        self.1.set(depth);
+
+        if depth == 0 {
+            self.set(State::Default);
+        }
+    }
+
+    pub fn borrows(&self) -> &BorrowFlag {
+        &self.2
+    }
+
+    pub fn set(&self, v: State) {
+        if v == State::Default {
+            check_borrows(self.2.count());
+        }
+
+        let binding = match v {
+            State::Default => 0,
+            State::Unsend => thread_id() << Self::STATE_BITS | 1,
+            State::Entered => thread_id() << Self::STATE_BITS | 2,
+        };
+
+        self.0.store(binding, std::sync::atomic::Ordering::Release);
+    }
+}
+
+/// The thread an instance is entered on.
+///
+/// Ensures that an instance is not entered on two threads at once where the
+/// context does not, i.e., in the `thread_local` module and for `snarc!`
+/// without `per_instance;`, whose context is shared by a family.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct Holder(std::sync::atomic::AtomicUsize);
+
+impl Holder {
+    /// Claims the instance for the current thread.
+    ///
+    /// Returns `None` if another thread holds it, `Some(false)` if the current
+    /// thread holds it already.
+    pub fn claim(&self) -> Option<bool> {
+        use std::sync::atomic::Ordering;
+
+        let thread_id = thread_id();
+        match self
+            .0
+            .compare_exchange(0, thread_id, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => Some(true),
+            Err(holder) if holder == thread_id => Some(false),
+            Err(_) => None,
+        }
+    }
+
+    /// Undoes a `claim` that returned `Some(true)`.
+    pub fn release(&self) {
+        self.0.store(0, std::sync::atomic::Ordering::Release);
     }
 }
 
+/// Returns a non-zero id that is unique to the current thread.
+fn thread_id() -> usize {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+    thread_local!(static ID: usize = NEXT_ID.fetch_add(1, Ordering::Relaxed));
+
+    ID.with(|id| *id)
+}
+
 /// A `Snarc<T>` whose type argument was erased.
 ///
 /// `ErasedSnarc`s cannot be used to access the (type-erased) inner value. They
 /// can only be used to temporarily bind the value to the current thread using
-/// the [`enter`][ErasedSnarc::enter()] method.
-pub struct ErasedSnarc {
-    inner: Box<dyn Context + Send + 'static>,
-}
+/// the [`enter`][ScopedErasedSnarc::enter()] method.
+pub type ErasedSnarc = ScopedErasedSnarc<'static>;
 
-unsafe impl Send for ErasedSnarc {}
-unsafe impl Sync for ErasedSnarc {}
+/// A `Snarc<T>` whose type argument was erased, where `T` may borrow for `'a`.
+///
+/// Created by `into_erased_scoped`, so that owners of values that borrow from
+/// an enclosing scope can be entered alongside others. As their type is not
+/// `'static`, the inner value can neither be accessed nor downcast.
+pub struct ScopedErasedSnarc<'a> {
+    inner: ErasedOwner<'a>,
+}
 
-impl ErasedSnarc {
+impl<'a> ScopedErasedSnarc<'a> {
     /// Turn this into an unsendable `ErasedNarc` bound to the current thread.
-    pub fn into_unsend(self) -> ErasedNarc {
+    pub fn into_unsend(self) -> ScopedErasedNarc<'a> {
         self.inner.into()
     }
 
@@ -186,38 +949,274 @@ impl ErasedSnarc {
 
         f()
     }
+
+    /// Like `enter`, but catches a panic of `f` rather than propagating it.
+    ///
+    /// The inner value is unbound once unwinding finished, so that the owner
+    /// may be entered again, e.g., by the next iteration of an event loop.
+    pub fn enter_catch<F, R>(&mut self, f: F) -> std::thread::Result<R>
+    where
+        F: FnOnce() -> R + std::panic::UnwindSafe,
+    {
+        self.inner.set(State::Entered);
+
+        let result = std::panic::catch_unwind(f);
+
+        self.inner.set(State::Default);
+
+        result
+    }
+
+    /// Returns `true` if the inner value is bound to the current thread, i.e.,
+    /// within `enter`, be it of this or of the typed owner.
+    pub fn is_entered(&self) -> bool {
+        self.inner.is_entered()
+    }
+
+    /// Temporarily bind the inner value to this thread and invoke all of
+    /// `callbacks`' handlers within that context.
+    pub fn dispatch<Args>(&mut self, callbacks: &CallbackSet<Args>, args: &Args) {
+        self.enter(|| callbacks.invoke(args))
+    }
+
+    /// Returns the name of the inner value's type, as `std::any::type_name`
+    /// would.
+    pub fn type_name(&self) -> &'static str {
+        self.inner.type_name()
+    }
+}
+
+impl ErasedSnarc {
+    /// Binds the inner value to this thread until the returned guard is
+    /// dropped.
+    ///
+    /// Unlike `enter`, this permits early returns and `?` within the entered
+    /// scope. Should the guard be leaked, the owner is leaked as well and any
+    /// further use of `self` panics.
+    pub fn enter_guard(&mut self) -> ErasedEnterGuard<'_> {
+        ErasedEnterGuard::new(&mut self.inner)
+    }
+
+    /// Temporarily bind the inner value to this thread and evaluate `f` with
+    /// it, provided it is a `T`.
+    pub fn enter_as<T, F, R>(&mut self, f: F) -> Result<R, WrongType>
+    where
+        T: 'static,
+        F: FnOnce(&T) -> R,
+    {
+        match self.inner.value() {
+            Some(value) if unsafe { &*value }.is::<T>() => {
+                Ok(self.enter(|| f(unsafe { &*value.cast::<T>() })))
+            }
+            _ => Err(WrongType),
+        }
+    }
+
+    /// Temporarily bind the inner value to this thread and evaluate `f` with
+    /// it, whatever its type.
+    ///
+    /// Panics if the owner was erased by way of `into_erased_scoped`.
+    pub fn enter_any<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&dyn std::any::Any) -> R,
+    {
+        let value = match self.inner.value() {
+            Some(value) => value,
+            None => crate::__panic!("The erased {} is scoped.", self.type_name()),
+        };
+
+        self.enter(|| f(unsafe { &*value }))
+    }
+
+    /// Recovers the `Snarc<T>` whose type argument was erased, otherwise
+    /// `self` is returned unchanged.
+    #[cfg(feature = "thread_local")]
+    pub fn downcast<T: Send + 'static>(self) -> Result<thread_local::Snarc<T>, Self> {
+        self.downcast_owner()
+    }
+
+    /// Recovers the owning pointer `S` whose type argument was erased,
+    /// otherwise `self` is returned unchanged.
+    pub fn downcast_owner<S: Context + 'static>(self) -> Result<S, Self> {
+        self.inner.downcast().map_err(|inner| Self { inner })
+    }
 }
 
-impl From<Box<dyn Context + Send + 'static>> for ErasedSnarc {
-    fn from(inner: Box<dyn Context + Send + 'static>) -> Self {
+impl<'a> From<ErasedOwner<'a>> for ScopedErasedSnarc<'a> {
+    fn from(inner: ErasedOwner<'a>) -> Self {
         Self { inner }
     }
 }
 
-impl From<ErasedNarc> for ErasedSnarc {
-    fn from(narc: ErasedNarc) -> Self {
+impl std::fmt::Debug for ScopedErasedSnarc<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ErasedSnarc")
+            .field(&format_args!("{}", self.type_name()))
+            .finish()
+    }
+}
+
+impl<'a> From<ScopedErasedNarc<'a>> for ScopedErasedSnarc<'a> {
+    fn from(narc: ScopedErasedNarc<'a>) -> Self {
         narc.into_send()
     }
 }
 
+impl From<SyncErasedSnarc> for ErasedSnarc {
+    fn from(snarc: SyncErasedSnarc) -> Self {
+        snarc.inner
+    }
+}
+
+/// An `ErasedSnarc` that may be shared between threads.
+///
+/// An `ErasedSnarc` is merely `Send`, as the type of its inner value is
+/// unknown. A `SyncErasedSnarc` can only be created from an owner of a value
+/// that is `Sync`, so that it may be `Sync` as well.
+pub struct SyncErasedSnarc {
+    inner: ErasedSnarc,
+}
+
+// created from owners of `Sync` values only
+unsafe impl Sync for SyncErasedSnarc {}
+
+impl SyncErasedSnarc {
+    /// Erases the type argument of `owner`.
+    pub fn new<S>(owner: S) -> Self
+    where
+        S: Context + Send + 'static,
+        S::Value: Sync + 'static,
+    {
+        Self {
+            inner: ErasedSnarc::from(ErasedOwner::new(owner)),
+        }
+    }
+
+    /// Temporarily bind the inner value to this thread and evaluate `f` within
+    /// that context.
+    pub fn enter<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        self.inner.enter(f)
+    }
+
+    /// Binds the inner value to this thread until the returned guard is
+    /// dropped, see `ErasedSnarc::enter_guard`.
+    pub fn enter_guard(&mut self) -> ErasedEnterGuard<'_> {
+        self.inner.enter_guard()
+    }
+
+    /// Temporarily bind the inner value to this thread and evaluate `f` with
+    /// it, provided it is a `T`.
+    pub fn enter_as<T, F, R>(&mut self, f: F) -> Result<R, WrongType>
+    where
+        T: 'static,
+        F: FnOnce(&T) -> R,
+    {
+        self.inner.enter_as(f)
+    }
+
+    /// Returns `true` if the inner value is bound to the current thread, i.e.,
+    /// within `enter`, be it of this or of the typed owner.
+    pub fn is_entered(&self) -> bool {
+        self.inner.is_entered()
+    }
+
+    /// Returns the name of the inner value's type, as `std::any::type_name`
+    /// would.
+    pub fn type_name(&self) -> &'static str {
+        self.inner.type_name()
+    }
+
+    /// Turn this into a plain `ErasedSnarc`, which is not `Sync`.
+    pub fn into_erased(self) -> ErasedSnarc {
+        self.inner
+    }
+}
+
+impl std::fmt::Debug for SyncErasedSnarc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SyncErasedSnarc")
+            .field(&format_args!("{}", self.type_name()))
+            .finish()
+    }
+}
+
 /// A unsendable `Snarc<T>` whose type argument was erased.
 ///
-/// `ErasedNarc`s cannot be used to access the (type-erased) inner value.
-pub struct ErasedNarc {
-    inner: Box<dyn Context + Send + 'static>,
+/// `ErasedNarc`s cannot be used to access the (type-erased) inner value. In
+/// turn, they keep it bound to the current thread for their whole lifetime, so
+/// that weak references resolve without `enter`.
+pub type ErasedNarc = ScopedErasedNarc<'static>;
+
+/// An unsendable `Snarc<T>` whose type argument was erased, where `T` may
+/// borrow for `'a`, see `ScopedErasedSnarc`.
+pub struct ScopedErasedNarc<'a> {
+    inner: ErasedOwner<'a>,
     _unsend: std::marker::PhantomData<std::sync::MutexGuard<'static, ()>>,
 }
 
-impl ErasedNarc {
+impl<'a> ScopedErasedNarc<'a> {
     /// Turn this into a sendable `ErasedSnarc`.
-    pub fn into_send(mut self) -> ErasedSnarc {
+    ///
+    /// Panics if the inner value is not `Send`, see `try_into_send`.
+    pub fn into_send(self) -> ScopedErasedSnarc<'a> {
+        match self.try_into_send() {
+            Ok(snarc) => snarc,
+            Err(narc) => crate::__panic!("The erased {} is not Send.", narc.type_name()),
+        }
+    }
+
+    /// Turn this into a sendable `ErasedSnarc`, unless it was erased from a
+    /// `Narc` whose inner value is not `Send`.
+    pub fn try_into_send(mut self) -> Result<ScopedErasedSnarc<'a>, Self> {
+        if !self.inner.is_send() {
+            return Err(self);
+        }
+
         self.inner.set(State::Default);
-        self.inner.into()
+        Ok(self.inner.into())
+    }
+
+    /// Returns the name of the inner value's type, as `std::any::type_name`
+    /// would.
+    pub fn type_name(&self) -> &'static str {
+        self.inner.type_name()
+    }
+}
+
+impl ErasedNarc {
+    #[doc(hidden)]
+    pub fn from_unsend<S>(owner: S) -> Self
+    where
+        S: Context + 'static,
+        S::Value: 'static,
+    {
+        // an `ErasedNarc` is never sent and only turned into an `ErasedSnarc`
+        // if `is_send`
+        unsafe { ErasedOwner::new_unsend(owner) }.into()
+    }
+
+    /// Recovers the `Narc<T>` whose type argument was erased, otherwise
+    /// `self` is returned unchanged.
+    #[cfg(feature = "thread_local")]
+    pub fn downcast<T: 'static>(mut self) -> Result<thread_local::Narc<T>, Self> {
+        if !self.inner.is::<thread_local::Snarc<T>>() {
+            return Err(self);
+        }
+
+        self.inner.set(State::Default);
+
+        match self.inner.downcast::<thread_local::Snarc<T>>() {
+            Ok(snarc) => Ok(snarc.into_unsend()),
+            Err(_) => unreachable!("the owner's type was checked"),
+        }
     }
 }
 
-impl From<Box<dyn Context + Send + 'static>> for ErasedNarc {
-    fn from(mut inner: Box<dyn Context + Send + 'static>) -> Self {
+impl<'a> From<ErasedOwner<'a>> for ScopedErasedNarc<'a> {
+    fn from(mut inner: ErasedOwner<'a>) -> Self {
         inner.set(State::Unsend);
         Self {
             inner,
@@ -226,8 +1225,71 @@ impl From<Box<dyn Context + Send + 'static>> for ErasedNarc {
     }
 }
 
-impl From<ErasedSnarc> for ErasedNarc {
-    fn from(snarc: ErasedSnarc) -> Self {
+impl std::fmt::Debug for ScopedErasedNarc<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ErasedNarc")
+            .field(&format_args!("{}", self.type_name()))
+            .finish()
+    }
+}
+
+impl<'a> From<ScopedErasedSnarc<'a>> for ScopedErasedNarc<'a> {
+    fn from(snarc: ScopedErasedSnarc<'a>) -> Self {
         snarc.into_unsend()
     }
 }
+
+#[doc(hidden)]
+pub trait RefContext: std::any::Any + Send + Sync {
+    fn is_entered(&self) -> bool;
+
+    fn is_dangling(&self) -> bool;
+}
+
+/// A weak reference whose type argument was erased.
+///
+/// `ErasedSnarcRef`s cannot be used to access the (type-erased) inner value,
+/// they must be downcast to their typed counterpart first.
+pub struct ErasedSnarcRef {
+    inner: Box<dyn RefContext>,
+}
+
+impl ErasedSnarcRef {
+    /// Returns `true` if the inner value is bound to the current thread, i.e.,
+    /// the typed reference would resolve.
+    pub fn is_entered(&self) -> bool {
+        self.inner.is_entered()
+    }
+
+    /// Returns `true` if the owner was dropped, closed or leaked, so that this
+    /// reference will never resolve again.
+    pub fn is_dangling(&self) -> bool {
+        self.inner.is_dangling()
+    }
+
+    /// Recovers the `SnarcRef<T>` whose type argument was erased, otherwise
+    /// `self` is returned unchanged.
+    #[cfg(feature = "thread_local")]
+    pub fn downcast<T: 'static>(self) -> Result<thread_local::SnarcRef<T>, Self> {
+        self.downcast_weak()
+    }
+
+    /// Recovers the weak reference `R` whose type argument was erased,
+    /// otherwise `self` is returned unchanged.
+    pub fn downcast_weak<R: 'static>(self) -> Result<R, Self> {
+        if std::any::Any::type_id(&*self.inner) != std::any::TypeId::of::<R>() {
+            return Err(self);
+        }
+
+        let inner: Box<dyn std::any::Any> = self.inner;
+        Ok(*inner
+            .downcast()
+            .expect("the reference's `TypeId` was checked"))
+    }
+}
+
+impl From<Box<dyn RefContext>> for ErasedSnarcRef {
+    fn from(inner: Box<dyn RefContext>) -> Self {
+        Self { inner }
+    }
+}
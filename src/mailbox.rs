@@ -0,0 +1,69 @@
+use crate::lazy_box::LazyBox;
+use std::any::Any;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+trait Queue: Send + Sync {
+    fn clear(&self);
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<M: Send + 'static> Queue for Mutex<Vec<M>> {
+    fn clear(&self) {
+        let messages = std::mem::take(&mut *lock(self));
+        drop(messages);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Messages sent by weak references, to be handed to the owner within
+/// `enter`.
+///
+/// The queue is only allocated once the first message is sent, its type is
+/// fixed from then on. Until then, the mailbox is a single null pointer.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct Mailbox {
+    // boxed twice to keep the pointer thin
+    queue: LazyBox<Box<dyn Queue>>,
+}
+
+impl Mailbox {
+    /// Panics if messages of another type were sent before.
+    pub fn send<M: Send + 'static>(&self, message: M) {
+        lock(self.queue::<M>()).push(message);
+    }
+
+    /// Takes all messages sent so far.
+    ///
+    /// Panics if messages of another type were sent before.
+    pub fn take<M: Send + 'static>(&self) -> Vec<M> {
+        match self.queue.get() {
+            Some(_) => std::mem::take(&mut *lock(self.queue::<M>())),
+            None => Vec::new(),
+        }
+    }
+
+    /// Drops all messages sent so far.
+    pub fn clear(&self) {
+        if let Some(queue) = self.queue.get() {
+            queue.clear();
+        }
+    }
+
+    fn queue<M: Send + 'static>(&self) -> &Mutex<Vec<M>> {
+        self.queue
+            .get_or_init(|| Box::new(Mutex::new(Vec::<M>::new())))
+            .as_any()
+            .downcast_ref()
+            .expect("The mailbox holds messages of another type.")
+    }
+}
+
+fn lock<M>(queue: &Mutex<Vec<M>>) -> MutexGuard<'_, Vec<M>> {
+    queue.lock().unwrap_or_else(|p| p.into_inner())
+}
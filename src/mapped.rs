@@ -0,0 +1,86 @@
+use crate::Get;
+
+/// Projects the value of the weak reference `R` to a part of it.
+struct Projection<R, T, F> {
+    inner: R,
+    f: F,
+    phantom: std::marker::PhantomData<fn(&T)>,
+}
+
+impl<R, T, U, F> Get<U> for Projection<R, T, F>
+where
+    R: Get<T>,
+    F: Fn(&T) -> &U,
+{
+    #[track_caller]
+    fn get(&self) -> Option<&U> {
+        self.inner.get().map(&self.f)
+    }
+
+    fn version(&self) -> Option<u64> {
+        self.inner.version()
+    }
+}
+
+/// A weak reference to a part of a value, created by `map`.
+///
+/// It keeps counting against the allocation of the value it was mapped from
+/// and resolves under the same conditions, i.e., within `enter`.
+pub struct MappedSnarcRef<U> {
+    inner: Box<dyn Get<U> + Send + Sync>,
+}
+
+impl<U> MappedSnarcRef<U> {
+    #[doc(hidden)]
+    pub fn new<R, T, F>(inner: R, f: F) -> Self
+    where
+        R: Get<T> + Send + Sync + 'static,
+        T: 'static,
+        U: 'static,
+        F: Fn(&T) -> &U + Send + Sync + 'static,
+    {
+        Self {
+            inner: Box::new(Projection {
+                inner,
+                f,
+                phantom: std::marker::PhantomData,
+            }),
+        }
+    }
+
+    /// Gets a reference to the part of the inner value.
+    ///
+    /// Returns `None` if the inner value is not bound to the current thread.
+    #[track_caller]
+    pub fn get(&self) -> Option<&U> {
+        self.inner.get()
+    }
+
+    /// Returns the version of the inner value, the whole of it.
+    ///
+    /// Returns `None` outside of `enter`, like `get`.
+    pub fn version(&self) -> Option<u64> {
+        self.inner.version()
+    }
+
+    /// Projects this reference further, to a part of the part.
+    pub fn map<V, F>(self, f: F) -> MappedSnarcRef<V>
+    where
+        U: 'static,
+        V: 'static,
+        F: Fn(&U) -> &V + Send + Sync + 'static,
+    {
+        MappedSnarcRef::new(self, f)
+    }
+}
+
+impl<U> Get<U> for MappedSnarcRef<U> {
+    #[track_caller]
+    fn get(&self) -> Option<&U> {
+        MappedSnarcRef::get(self)
+    }
+
+    fn version(&self) -> Option<u64> {
+        MappedSnarcRef::version(self)
+    }
+}
@@ -0,0 +1,165 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+
+#[derive(Default)]
+struct Shared {
+    changed: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+/// Learns of changes to the value of an owning pointer.
+///
+/// Obtained from the `subscribe` method of a weak reference. Mutable access by
+/// the owner marks the value as changed, but listeners are only notified at
+/// the start of the owner's next `enter`.
+pub struct ChangeListener {
+    shared: Arc<Shared>,
+}
+
+impl ChangeListener {
+    /// Creates a listener that is never notified.
+    pub fn detached() -> Self {
+        Self {
+            shared: Default::default(),
+        }
+    }
+
+    /// Returns `true` if a change was delivered since the last call to
+    /// [`take_changed`][ChangeListener::take_changed()].
+    pub fn has_changed(&self) -> bool {
+        self.shared.changed.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` if a change was delivered since the last call, resetting
+    /// the flag.
+    pub fn take_changed(&self) -> bool {
+        self.shared.changed.swap(false, Ordering::AcqRel)
+    }
+
+    /// Returns a future that resolves once a change is delivered, resetting
+    /// the flag.
+    pub fn changed(&mut self) -> Changed<'_> {
+        Changed { listener: self }
+    }
+}
+
+/// Future returned by [`ChangeListener::changed`].
+pub struct Changed<'a> {
+    listener: &'a mut ChangeListener,
+}
+
+impl Future for Changed<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let shared = &self.listener.shared;
+
+        if shared.changed.swap(false, Ordering::AcqRel) {
+            return Poll::Ready(());
+        }
+
+        *shared.waker.lock().unwrap_or_else(|p| p.into_inner()) = Some(cx.waker().clone());
+
+        if shared.changed.swap(false, Ordering::AcqRel) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Tracks changes of a value, counting them in its version, and the listeners
+/// interested in them.
+///
+/// Must only be accessed by the owner, or within its `enter` context, except
+/// for [`poll_entered`][Notifier::poll_entered()].
+#[doc(hidden)]
+#[derive(Default)]
+pub struct Notifier {
+    version: Cell<u64>,
+    dirty: Cell<bool>,
+    listeners: RefCell<Vec<Weak<Shared>>>,
+    // may be accessed from any thread
+    enters: AtomicU64,
+    entered: Mutex<Vec<Waker>>,
+}
+
+impl Notifier {
+    #[inline(always)]
+    pub fn mark_dirty(&self) {
+        self.version.set(self.version.get().wrapping_add(1));
+        self.dirty.set(true);
+    }
+
+    #[inline(always)]
+    pub fn version(&self) -> u64 {
+        self.version.get()
+    }
+
+    pub fn subscribe(&self) -> ChangeListener {
+        let listener = ChangeListener::detached();
+        self.listeners
+            .borrow_mut()
+            .push(Arc::downgrade(&listener.shared));
+        listener
+    }
+
+    /// Resolves once the owner enters after the first poll.
+    pub fn poll_entered(&self, since: &mut Option<u64>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut entered = self.entered.lock().unwrap_or_else(|p| p.into_inner());
+        let enters = self.enters.load(Ordering::Acquire);
+
+        match since {
+            Some(since) if *since != enters => Poll::Ready(()),
+            _ => {
+                since.get_or_insert(enters);
+                entered.push(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Wakes the tasks waiting for an `enter` and delivers pending changes to
+    /// all listeners that are still alive.
+    #[inline]
+    pub fn notify(&self) {
+        self.enters.fetch_add(1, Ordering::AcqRel);
+
+        let entered = std::mem::take(&mut *self.entered.lock().unwrap_or_else(|p| p.into_inner()));
+        for waker in entered {
+            waker.wake();
+        }
+
+        if self.dirty.replace(false) {
+            self.listeners.borrow_mut().retain(|listener| {
+                let Some(shared) = listener.upgrade() else {
+                    return false;
+                };
+
+                shared.changed.store(true, Ordering::Release);
+
+                let waker = shared
+                    .waker
+                    .lock()
+                    .unwrap_or_else(|p| p.into_inner())
+                    .take();
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+
+                true
+            });
+        }
+    }
+}
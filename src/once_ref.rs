@@ -0,0 +1,59 @@
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+use crate::Get;
+
+/// A weak reference paired with a projection of its value.
+///
+/// The projection is evaluated by the first successful
+/// [`get`][OnceRef::get()] and cached until the value is mutated, which is
+/// detected by its version. Later calls only check that the value is bound to
+/// the current thread and compare versions.
+pub struct OnceRef<R, T, U, F> {
+    snarc_ref: R,
+    project: F,
+    cached: Cell<Option<(u64, *const U)>>,
+    phantom: PhantomData<fn(&T) -> &U>,
+}
+
+unsafe impl<R: Send, T, U, F: Send> Send for OnceRef<R, T, U, F> {}
+
+impl<R, T, U, F> OnceRef<R, T, U, F>
+where
+    R: Get<T>,
+    F: Fn(&T) -> &U,
+{
+    /// Creates a new `OnceRef` projecting the value of `snarc_ref` by
+    /// `project`.
+    pub fn new(snarc_ref: R, project: F) -> Self {
+        Self {
+            snarc_ref,
+            project,
+            cached: Cell::new(None),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Gets a reference to the projected value.
+    ///
+    /// Returns `None` if the value is not bound to the current thread.
+    pub fn get(&self) -> Option<&U> {
+        let version = self.snarc_ref.version()?;
+
+        if let Some((cached, projected)) = self.cached.get() {
+            if cached == version {
+                return Some(unsafe { &*projected });
+            }
+        }
+
+        let projected = (self.project)(self.snarc_ref.get()?);
+        self.cached.set(Some((version, projected)));
+
+        Some(projected)
+    }
+
+    /// Consumes this `OnceRef`, returning the weak reference.
+    pub fn into_inner(self) -> R {
+        self.snarc_ref
+    }
+}
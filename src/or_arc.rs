@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+/// Implemented by the weak references of all `Snarc`s.
+#[doc(hidden)]
+pub trait Get<T> {
+    fn get(&self) -> Option<&T>;
+
+    fn version(&self) -> Option<u64>;
+}
+
+/// Either a weak reference to a `Snarc<T>` or an `Arc<T>`.
+///
+/// Allows libraries to accept values of either ownership model and leave the
+/// choice to their callers.
+pub enum SnarcOrArc<T> {
+    /// A weak reference, its value is accessible within `enter` only.
+    Snarc(Box<dyn Get<T> + Send + Sync>),
+    /// A shared value, which is always accessible.
+    Arc(Arc<T>),
+}
+
+impl<T> SnarcOrArc<T> {
+    /// Evaluates `f` with a reference to the value.
+    ///
+    /// Returns `None` if the value is referenced weakly and not bound to the
+    /// current thread.
+    pub fn with<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        match self {
+            SnarcOrArc::Snarc(snarc_ref) => snarc_ref.get().map(f),
+            SnarcOrArc::Arc(arc) => Some(f(arc)),
+        }
+    }
+}
+
+impl<T> From<Arc<T>> for SnarcOrArc<T> {
+    fn from(arc: Arc<T>) -> Self {
+        SnarcOrArc::Arc(arc)
+    }
+}
@@ -0,0 +1,90 @@
+use std::any::Any;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::sync::Arc;
+
+/// Keeps the original owner alive until the parts are dropped or joined.
+struct Shared<S>(S);
+
+unsafe impl<S: Send> Sync for Shared<S> {}
+
+/// Owns a part of the value of a split owner.
+///
+/// Created by `map_split`. The value it was split from is dropped once all of
+/// its parts are, unless they are joined again.
+pub struct SnarcPart<A> {
+    ptr: *mut A,
+    owner: Arc<dyn Any + Send + Sync>,
+}
+
+unsafe impl<A: Send> Send for SnarcPart<A> {}
+unsafe impl<A: Sync> Sync for SnarcPart<A> {}
+
+impl<A> SnarcPart<A> {
+    /// Splits the value of `owner` into the disjoint parts returned by `f`.
+    ///
+    /// # Safety
+    ///
+    /// The value must not move when `owner` does.
+    #[doc(hidden)]
+    pub unsafe fn split<S, B>(
+        mut owner: S,
+        f: fn(&mut S::Target) -> (&mut A, &mut B),
+    ) -> (Self, SnarcPart<B>)
+    where
+        S: DerefMut + Send + 'static,
+    {
+        let (a, b) = f(&mut owner);
+        let (a, b) = (a as *mut A, b as *mut B);
+
+        let owner: Arc<dyn Any + Send + Sync> = Arc::new(Shared(owner));
+
+        (
+            Self {
+                ptr: a,
+                owner: Arc::clone(&owner),
+            },
+            SnarcPart { ptr: b, owner },
+        )
+    }
+
+    /// Returns the owner that `a` and `b` were split from.
+    ///
+    /// Returns the parts back unless they were split from the same owner of
+    /// type `S`.
+    #[doc(hidden)]
+    pub fn join<S, B>(a: Self, b: SnarcPart<B>) -> Result<S, (Self, SnarcPart<B>)>
+    where
+        S: Send + 'static,
+    {
+        if !Arc::ptr_eq(&a.owner, &b.owner) || !(*a.owner).is::<Shared<S>>() {
+            return Err((a, b));
+        }
+
+        drop(b);
+
+        // `split` creates exactly two parts, so `a` holds the last clone
+        let owner = a
+            .owner
+            .downcast::<Shared<S>>()
+            .expect("the type was checked");
+        match Arc::try_unwrap(owner) {
+            Ok(Shared(owner)) => Ok(owner),
+            Err(_) => unreachable!("parts are not cloned"),
+        }
+    }
+}
+
+impl<A> Deref for SnarcPart<A> {
+    type Target = A;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.ptr }
+    }
+}
+
+impl<A> DerefMut for SnarcPart<A> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.ptr }
+    }
+}
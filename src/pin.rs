@@ -0,0 +1,49 @@
+use std::pin::Pin;
+
+use crate::Get;
+
+/// A weak reference to a pinned value, created by `new_pinned_ref`.
+///
+/// Owners pinned by `pin` never move their inner value, not even by way of
+/// `replace` or `swap`, which require it to be unpinned. Therefore, weak
+/// references created from them may hand out pinned references.
+pub struct PinnedRef<R> {
+    inner: R,
+}
+
+impl<R> PinnedRef<R> {
+    /// # Safety
+    ///
+    /// `inner` must refer to a value that is pinned.
+    #[doc(hidden)]
+    pub unsafe fn new_unchecked(inner: R) -> Self {
+        Self { inner }
+    }
+
+    /// Gets a pinned reference to the inner value.
+    ///
+    /// Returns `None` if the inner value is not bound to the current thread.
+    #[track_caller]
+    pub fn get_pinned<T>(&self) -> Option<Pin<&T>>
+    where
+        R: Get<T>,
+    {
+        // the value was pinned by its owner
+        self.inner
+            .get()
+            .map(|value| unsafe { Pin::new_unchecked(value) })
+    }
+
+    /// Returns the unpinned weak reference.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Clone> Clone for PinnedRef<R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Re-exports the main types and extension traits.
+//!
+//! ```
+//! use snarc::prelude::*;
+//! ```
+#[cfg(feature = "thread_local")]
+pub use crate::thread_local::Narc;
+#[cfg(feature = "thread_local")]
+pub use crate::thread_local::Snarc;
+#[cfg(feature = "thread_local")]
+pub use crate::thread_local::SnarcRef;
+pub use crate::ChangeListener;
+pub use crate::Enter;
+pub use crate::EnterAll;
+pub use crate::EnterMany;
+pub use crate::EnterSet;
+pub use crate::ErasedNarc;
+pub use crate::ErasedSlot;
+pub use crate::ErasedSnarc;
+pub use crate::ErasedSnarcRef;
+pub use crate::IntoErased;
+pub use crate::OnceRef;
+pub use crate::ScopedErasedNarc;
+pub use crate::ScopedErasedSnarc;
+pub use crate::SnarcOrArc;
+pub use crate::SnarcRefExt;
+pub use crate::SnarcTypeMap;
+pub use crate::SyncErasedSnarc;
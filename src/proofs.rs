@@ -0,0 +1,94 @@
+//! Proof harnesses for `cargo kani`.
+//!
+//! They check the counting protocol over bounded sequences of operations: the
+//! count never underflows (overflow checks are on by default), values are
+//! dropped exactly once and the allocation is freed only after the value was
+//! dropped and the last reference is gone, which Kani's memory safety checks
+//! would otherwise flag as a use after free.
+//!
+//! The module is only compiled by Kani, run `cargo kani` to check the
+//! harnesses; the `Kani` workflow does so for every push.
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+crate::snarc!(Snarc, Narc, SnarcRef);
+
+const MAX_OPS: usize = 4;
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+struct Counted(#[allow(dead_code)] u8);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[kani::proof]
+#[kani::unwind(6)]
+fn counting_protocol() {
+    DROPS.store(0, Ordering::SeqCst);
+
+    let mut owner = Some(Snarc::new(Counted(kani::any())));
+    let mut other = Snarc::new(());
+    let mut refs: Vec<SnarcRef<Counted>> = Vec::new();
+
+    for _ in 0..MAX_OPS {
+        match kani::any::<u8>() % 4 {
+            0 => {
+                if let Some(owner) = &owner {
+                    refs.push(owner.new_ref());
+                }
+            }
+            1 => {
+                if let Some(snarc_ref) = refs.pop() {
+                    match &mut owner {
+                        Some(owner) => owner.enter(|_| drop(snarc_ref)),
+                        // all values of a `snarc!` share their context
+                        None => other.enter(|_| drop(snarc_ref)),
+                    }
+                }
+            }
+            2 => {
+                if let Some(owner) = &mut owner {
+                    owner.enter(|_| {
+                        for snarc_ref in &refs {
+                            assert!(snarc_ref.get().is_some());
+                        }
+                    });
+                }
+            }
+            _ => {
+                drop(owner.take());
+                assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+            }
+        }
+    }
+
+    drop(owner);
+    other.enter(|_| refs.clear());
+
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+}
+
+#[kani::proof]
+#[kani::unwind(4)]
+fn narc_and_snarc_conversions_preserve_the_value() {
+    DROPS.store(0, Ordering::SeqCst);
+
+    let value: u8 = kani::any();
+    let mut snarc = Snarc::new(Counted(value));
+    let snarc_ref = snarc.new_ref();
+
+    let narc: Narc<Counted> = snarc.into_unsend();
+    snarc = narc.into_send();
+
+    snarc.enter(|v| {
+        assert_eq!(snarc_ref.get().map(|c| c.0), Some(v.0));
+        drop(snarc_ref);
+    });
+
+    drop(snarc);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+}
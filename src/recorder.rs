@@ -0,0 +1,208 @@
+//! Records the sequence of enters on a thread for replaying it later.
+//!
+//! While a [`Recorder`] is active, every `enter` and every creation of a weak
+//! reference on its thread is logged, along with the commands passed to
+//! [`record_command`]. The resulting [`Recording`] can be replayed against a
+//! fresh owner to reproduce bugs that depend on the order of enters.
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The number of active recorders, across all threads.
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+thread_local!(static RECORDER: RefCell<Option<Log>> = const { RefCell::new(None) });
+
+/// Something that happened while recording.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event<C> {
+    /// An owner entered its value.
+    Enter {
+        /// Identifies the value, in the order values were first seen.
+        target: usize,
+        /// The time since recording started.
+        at: Duration,
+    },
+    /// A weak reference was created.
+    NewRef {
+        /// Identifies the value, in the order values were first seen.
+        target: usize,
+        /// The time since recording started.
+        at: Duration,
+    },
+    /// A command was recorded.
+    Command {
+        /// The recorded command.
+        command: C,
+        /// The time since recording started.
+        at: Duration,
+    },
+}
+
+struct Log {
+    started: Instant,
+    targets: HashMap<*mut (), usize>,
+    events: Vec<Event<Box<dyn Any>>>,
+}
+
+impl Log {
+    fn push_target(&mut self, ptr: *mut (), event: fn(usize, Duration) -> Event<Box<dyn Any>>) {
+        let next = self.targets.len();
+        let target = *self.targets.entry(ptr).or_insert(next);
+        let at = self.started.elapsed();
+        self.events.push(event(target, at));
+    }
+}
+
+/// Records events on the current thread until it is
+/// [`finish`][Recorder::finish()]ed or dropped.
+pub struct Recorder<C> {
+    _unsend: std::marker::PhantomData<*mut C>,
+}
+
+impl<C: 'static> Recorder<C> {
+    /// Starts recording on the current thread.
+    ///
+    /// Panics if a recorder is already active on the current thread.
+    pub fn start() -> Self {
+        RECORDER.with(|r| {
+            let mut r = r.borrow_mut();
+            assert!(r.is_none(), "Another Recorder is already active.");
+
+            *r = Some(Log {
+                started: Instant::now(),
+                targets: HashMap::new(),
+                events: Vec::new(),
+            });
+        });
+        ACTIVE.fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            _unsend: Default::default(),
+        }
+    }
+
+    /// Stops recording, returning what was recorded.
+    ///
+    /// Commands of types other than `C` are discarded.
+    pub fn finish(self) -> Recording<C> {
+        let log = stop().expect("Recorder was removed.");
+        std::mem::forget(self);
+
+        let events = log
+            .events
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Enter { target, at } => Some(Event::Enter { target, at }),
+                Event::NewRef { target, at } => Some(Event::NewRef { target, at }),
+                Event::Command { command, at } => {
+                    command.downcast().ok().map(|command| Event::Command {
+                        command: *command,
+                        at,
+                    })
+                }
+            })
+            .collect();
+
+        Recording { events }
+    }
+}
+
+impl<C> Drop for Recorder<C> {
+    fn drop(&mut self) {
+        stop();
+    }
+}
+
+fn stop() -> Option<Log> {
+    let log = RECORDER.with(|r| r.borrow_mut().take());
+    ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    log
+}
+
+fn with_log(f: impl FnOnce(&mut Log)) {
+    if ACTIVE.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+
+    RECORDER.with(|r| {
+        if let Some(log) = r.borrow_mut().as_mut() {
+            f(log)
+        }
+    })
+}
+
+pub(crate) fn trace_enter(ptr: *mut ()) {
+    with_log(|log| log.push_target(ptr, |target, at| Event::Enter { target, at }))
+}
+
+/// Logs the creation of a weak reference to the value at `ptr`.
+#[doc(hidden)]
+#[inline]
+pub fn trace_new_ref(ptr: *mut ()) {
+    with_log(|log| log.push_target(ptr, |target, at| Event::NewRef { target, at }))
+}
+
+/// Records `command` if a `Recorder<C>` is active on the current thread.
+pub fn record_command<C: 'static>(command: C) {
+    with_log(|log| {
+        let at = log.started.elapsed();
+        log.events.push(Event::Command {
+            command: Box::new(command),
+            at,
+        });
+    })
+}
+
+/// The events recorded by a [`Recorder`].
+#[derive(Clone, Debug)]
+pub struct Recording<C> {
+    events: Vec<Event<C>>,
+}
+
+impl<C> Recording<C> {
+    /// Returns the recorded events, oldest first.
+    pub fn events(&self) -> &[Event<C>] {
+        &self.events
+    }
+
+    /// Replays the recorded enters of `target`, oldest first.
+    ///
+    /// `enter` is invoked once per recorded enter, with the commands recorded
+    /// until the next enter of any value. It is expected to enter a fresh
+    /// owner and apply the commands to its value.
+    pub fn replay<F>(&self, target: usize, mut enter: F)
+    where
+        F: FnMut(&[&C]),
+    {
+        let mut commands = Vec::new();
+        let mut replaying = false;
+
+        for event in &self.events {
+            match event {
+                Event::Enter { target: t, .. } => {
+                    if replaying {
+                        enter(&commands);
+                    }
+
+                    commands.clear();
+                    replaying = *t == target;
+                }
+                Event::NewRef { .. } => {}
+                Event::Command { command, .. } => {
+                    if replaying {
+                        commands.push(command);
+                    }
+                }
+            }
+        }
+
+        if replaying {
+            enter(&commands);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::ops::Deref;
+use std::ops::DerefMut;
+
+/// A reference to the inner value of a weak reference, created by `borrow`.
+///
+/// The guard registers with the context its value is entered by. Leaving that
+/// context while the guard exists panics, so that, unlike the reference
+/// returned by `get`, it cannot outlive `enter`.
+pub struct RefGuard<'a, T: ?Sized> {
+    value: &'a T,
+    ptr: *mut (),
+    release: fn(*mut ()),
+}
+
+impl<'a, T: ?Sized> RefGuard<'a, T> {
+    /// # Safety
+    ///
+    /// The borrow must be registered already, `release` is passed `ptr` to
+    /// undo that once the guard is dropped.
+    #[doc(hidden)]
+    pub unsafe fn new(value: &'a T, ptr: *mut (), release: fn(*mut ())) -> Self {
+        Self {
+            value,
+            ptr,
+            release,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for RefGuard<'_, T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<T: ?Sized> Drop for RefGuard<'_, T> {
+    fn drop(&mut self) {
+        (self.release)(self.ptr)
+    }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for RefGuard<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+/// A mutable reference to the inner value of a weak reference, created by
+/// `get_mut`.
+///
+/// Like a `RefGuard`, it cannot outlive `enter`. While it exists, the value
+/// cannot be borrowed by way of any other weak reference.
+pub struct RefMut<'a, T: ?Sized> {
+    value: &'a mut T,
+    ptr: *mut (),
+    release: fn(*mut ()),
+}
+
+impl<'a, T: ?Sized> RefMut<'a, T> {
+    /// # Safety
+    ///
+    /// The exclusive borrow must be registered already, `release` is passed
+    /// `ptr` to undo that once the guard is dropped.
+    #[doc(hidden)]
+    pub unsafe fn new(value: &'a mut T, ptr: *mut (), release: fn(*mut ())) -> Self {
+        Self {
+            value,
+            ptr,
+            release,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for RefMut<'_, T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<T: ?Sized> DerefMut for RefMut<'_, T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+impl<T: ?Sized> Drop for RefMut<'_, T> {
+    fn drop(&mut self) {
+        (self.release)(self.ptr)
+    }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for RefMut<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
@@ -0,0 +1,50 @@
+use crate::RefMut;
+
+/// Implemented by the weak references of all `Snarc`s.
+#[doc(hidden)]
+pub trait GetMut<T: ?Sized> {
+    /// # Safety
+    ///
+    /// See `SnarcRefMut::get_mut`.
+    unsafe fn get_mut(&self) -> Option<RefMut<'_, T>>;
+}
+
+/// A weak reference that grants mutable access, created by `new_ref_mut`.
+///
+/// The value is borrowed exclusively, which is tracked by its allocation
+/// rather than a `RefCell` within it. Only borrows of weak references are
+/// tracked, though, so `get_mut` is `unsafe`.
+pub struct SnarcRefMut<R> {
+    inner: R,
+}
+
+impl<R> SnarcRefMut<R> {
+    #[doc(hidden)]
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+
+    /// Borrows the inner value mutably for as long as the returned guard
+    /// exists.
+    ///
+    /// Returns `None` if the inner value is not bound to the current thread,
+    /// or if it is borrowed already, by way of `borrow` or `get_mut`.
+    ///
+    /// # Safety
+    ///
+    /// Neither the references handed out by the owner, e.g., to the closure
+    /// passed to `enter`, nor those returned by `get` may be used while the
+    /// guard exists.
+    #[track_caller]
+    pub unsafe fn get_mut<T: ?Sized>(&mut self) -> Option<RefMut<'_, T>>
+    where
+        R: GetMut<T>,
+    {
+        unsafe { self.inner.get_mut() }
+    }
+
+    /// Returns the weak reference, which grants shared access only.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
@@ -0,0 +1,177 @@
+//! A global registry of erased owners, entered by key.
+//!
+//! Plugin hosts that do not know the set of states at compile time may
+//! register the plugins' owners under string keys and enter them by key later
+//! on. An owner can only be entered or deregistered by the thread that
+//! registered it, its owning thread. Owners that are still registered when
+//! their owning thread exits are deregistered and dropped.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::thread::ThreadId;
+
+use crate::ErasedSnarc;
+
+static REGISTRY: Mutex<Option<HashMap<String, Entry>>> = Mutex::new(None);
+
+thread_local! {
+    static OWNED: Owned = const { Owned(RefCell::new(Vec::new())) };
+}
+
+/// The keys registered by the current thread, deregistered once it exits.
+struct Owned(RefCell<Vec<String>>);
+
+impl Drop for Owned {
+    fn drop(&mut self) {
+        let thread = std::thread::current().id();
+
+        let owners: Vec<_> = {
+            let mut registry = lock();
+            let Some(entries) = registry.as_mut() else {
+                return;
+            };
+
+            self.0
+                .get_mut()
+                .drain(..)
+                .filter_map(|key| {
+                    // the key may have been registered by another thread since
+                    if entries.get(&key)?.thread != thread {
+                        return None;
+                    }
+
+                    entries.remove(&key)
+                })
+                .collect()
+        };
+
+        // dropped without the lock, as the values may access the registry
+        drop(owners);
+    }
+}
+
+struct Entry {
+    thread: ThreadId,
+    /// `None` while the owner is entered.
+    owner: Option<ErasedSnarc>,
+}
+
+/// The error returned when a registered owner is not available.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RegistryError {
+    /// No owner is registered under the key.
+    NotRegistered,
+    /// The owner was registered by another thread.
+    OtherThread,
+    /// The owner is entered, it cannot be entered again or deregistered.
+    Entered,
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RegistryError::NotRegistered => "no owner is registered under the key",
+            RegistryError::OtherThread => "the owner was registered by another thread",
+            RegistryError::Entered => "the owner is entered",
+        })
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// Registers `owner` under `key`, the current thread becomes its owning
+/// thread.
+///
+/// Returns `owner` back if another owner is registered under `key`.
+pub fn register(key: impl Into<String>, owner: impl Into<ErasedSnarc>) -> Result<(), ErasedSnarc> {
+    let mut registry = lock();
+    let entries = registry.get_or_insert_with(HashMap::new);
+
+    match entries.entry(key.into()) {
+        std::collections::hash_map::Entry::Occupied(_) => Err(owner.into()),
+        std::collections::hash_map::Entry::Vacant(vacant) => {
+            OWNED.with(|owned| owned.0.borrow_mut().push(vacant.key().clone()));
+
+            vacant.insert(Entry {
+                thread: std::thread::current().id(),
+                owner: Some(owner.into()),
+            });
+            Ok(())
+        }
+    }
+}
+
+/// Removes the owner registered under `key` and returns it.
+pub fn deregister(key: &str) -> Result<ErasedSnarc, RegistryError> {
+    let mut registry = lock();
+    let entries = registry.as_mut().ok_or(RegistryError::NotRegistered)?;
+
+    available(entries.get(key))?;
+
+    let entry = entries.remove(key).expect("the entry was checked");
+    OWNED.with(|owned| owned.0.borrow_mut().retain(|k| k != key));
+
+    Ok(entry.owner.expect("the entry was checked"))
+}
+
+/// Returns `true` if an owner is registered under `key`.
+pub fn is_registered(key: &str) -> bool {
+    lock()
+        .as_ref()
+        .is_some_and(|entries| entries.contains_key(key))
+}
+
+/// Returns the keys of all registered owners, in no particular order.
+pub fn keys() -> Vec<String> {
+    lock()
+        .as_ref()
+        .map(|entries| entries.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Temporarily bind the inner value of the owner registered under `key` to
+/// this thread and evaluate `f` within that context.
+///
+/// The registry is not locked while `f` is evaluated, so `f` may enter other
+/// owners by key, register or deregister them.
+pub fn enter<F, R>(key: &str, f: F) -> Result<R, RegistryError>
+where
+    F: FnOnce() -> R,
+{
+    let owner = {
+        let mut registry = lock();
+        let entry = registry.as_mut().and_then(|entries| entries.get_mut(key));
+
+        available(entry.as_deref())?;
+        entry.and_then(|entry| entry.owner.take())
+    };
+
+    let mut owner = scopeguard::guard(owner.expect("the entry was checked"), |owner| {
+        let mut registry = lock();
+        let entry = registry.as_mut().and_then(|entries| entries.get_mut(key));
+
+        // the owner cannot be deregistered while it is entered
+        entry.expect("the owner is registered").owner = Some(owner);
+    });
+
+    Ok(owner.enter(f))
+}
+
+fn available(entry: Option<&Entry>) -> Result<(), RegistryError> {
+    let entry = entry.ok_or(RegistryError::NotRegistered)?;
+
+    if entry.thread != std::thread::current().id() {
+        return Err(RegistryError::OtherThread);
+    }
+
+    if entry.owner.is_none() {
+        return Err(RegistryError::Entered);
+    }
+
+    Ok(())
+}
+
+fn lock() -> MutexGuard<'static, Option<HashMap<String, Entry>>> {
+    REGISTRY.lock().unwrap_or_else(|p| p.into_inner())
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+
+use serde::ser::Error;
+use serde::Serialize;
+use serde::Serializer;
+
+use crate::Get;
+
+/// The error returned by `serialize_value`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AccessOrSerError<E> {
+    /// The value was not bound to the current thread.
+    NotEntered,
+    /// Serializing the value failed.
+    Serialize(E),
+}
+
+impl<E: fmt::Display> fmt::Display for AccessOrSerError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessOrSerError::NotEntered => f.write_str("value is not entered"),
+            AccessOrSerError::Serialize(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error> std::error::Error for AccessOrSerError<E> {}
+
+/// Serializes the value of a weak reference, which must be entered at the
+/// time of serialization.
+///
+/// Allows weak references to be serialized as part of larger structures.
+/// Serializing fails with a custom error if the value is not entered.
+pub struct SerializeEntered<'a, T> {
+    snarc_ref: &'a dyn Get<T>,
+}
+
+impl<'a, T> SerializeEntered<'a, T> {
+    /// Wraps the weak reference `snarc_ref`.
+    pub fn new<R: Get<T>>(snarc_ref: &'a R) -> Self {
+        Self { snarc_ref }
+    }
+}
+
+impl<T: Serialize> Serialize for SerializeEntered<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.snarc_ref.get() {
+            Some(value) => value.serialize(serializer),
+            None => Err(S::Error::custom(AccessOrSerError::<S::Error>::NotEntered)),
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn serialize_value<T, S>(
+    snarc_ref: &dyn Get<T>,
+    serializer: S,
+) -> Result<S::Ok, AccessOrSerError<S::Error>>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    let value = snarc_ref.get().ok_or(AccessOrSerError::NotEntered)?;
+    value
+        .serialize(serializer)
+        .map_err(AccessOrSerError::Serialize)
+}
@@ -0,0 +1,49 @@
+use crate::ErasedSnarc;
+
+/// A place to park an erased owner while it is not in use.
+///
+/// Frameworks that hand owners back and forth, e.g., between the stages of a
+/// request, may keep them in a slot and enter whichever one is present.
+#[derive(Debug, Default)]
+pub struct ErasedSlot {
+    owner: Option<ErasedSnarc>,
+}
+
+impl ErasedSlot {
+    /// Creates an empty slot.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if the slot holds an owner.
+    pub fn is_present(&self) -> bool {
+        self.owner.is_some()
+    }
+
+    /// Takes the owner out of the slot, leaving it empty.
+    pub fn take(&mut self) -> Option<ErasedSnarc> {
+        self.owner.take()
+    }
+
+    /// Puts `owner` into the slot, returning the owner it held before.
+    pub fn replace(&mut self, owner: impl Into<ErasedSnarc>) -> Option<ErasedSnarc> {
+        self.owner.replace(owner.into())
+    }
+
+    /// Temporarily binds the inner value of the owner, if any, to this thread
+    /// and evaluates `f` within that context.
+    ///
+    /// Returns `None` without evaluating `f` if the slot is empty.
+    pub fn enter_if_present<F, R>(&mut self, f: F) -> Option<R>
+    where
+        F: FnOnce() -> R,
+    {
+        self.owner.as_mut().map(|owner| owner.enter(f))
+    }
+}
+
+impl From<ErasedSnarc> for ErasedSlot {
+    fn from(owner: ErasedSnarc) -> Self {
+        Self { owner: Some(owner) }
+    }
+}
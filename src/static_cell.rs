@@ -0,0 +1,142 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+/// Declares global, lazily initialized owners.
+///
+/// ```
+/// snarc::static_snarc! {
+///     static COUNTER: Vec<u32> = vec![1, 2, 3];
+/// }
+///
+/// let counter_ref = COUNTER.new_ref();
+/// assert_eq!(COUNTER.enter(|_| counter_ref.get().map(Vec::len)), Some(3));
+/// ```
+#[macro_export]
+macro_rules! static_snarc {
+    ($(
+        $(#[$attr:meta])*
+        $vis:vis static $name:ident: $t:ty = $init:expr;
+    )*) => {$(
+        $(#[$attr])*
+        $vis static $name: $crate::StaticSnarcCell<$t> = $crate::StaticSnarcCell::new(|| $init);
+    )*};
+}
+
+/// A global owner, usually declared by way of [`static_snarc!`].
+///
+/// The value is initialized on first `enter`. The thread that enters first
+/// becomes the cell's main thread, entering from any other thread panics.
+/// Weak references may be created and passed around anywhere, but they only
+/// resolve within `enter`.
+pub struct StaticSnarcCell<T> {
+    init: fn() -> T,
+    value: UnsafeCell<Option<T>>,
+    main: AtomicUsize,
+    entered: AtomicBool,
+}
+
+// the value is only ever accessed on the main thread
+unsafe impl<T: Send> Sync for StaticSnarcCell<T> {}
+
+impl<T> StaticSnarcCell<T> {
+    /// Creates a new cell, whose value will be returned by `init`.
+    pub const fn new(init: fn() -> T) -> Self {
+        Self {
+            init,
+            value: UnsafeCell::new(None),
+            main: AtomicUsize::new(0),
+            entered: AtomicBool::new(false),
+        }
+    }
+
+    fn is_main_thread(&self) -> bool {
+        self.main.load(Ordering::Acquire) == crate::thread_id()
+    }
+
+    /// Creates a new non-owning reference to the value.
+    pub fn new_ref(&'static self) -> StaticSnarcRef<T> {
+        StaticSnarcRef { cell: self }
+    }
+
+    /// Temporarily bind the value to this thread and evaluate `f` within that
+    /// context, initializing the value first if necessary.
+    ///
+    /// Panics if called from a thread other than the main thread or from
+    /// within another `enter` of the same cell.
+    pub fn enter<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let thread_id = crate::thread_id();
+        if let Err(main) =
+            self.main
+                .compare_exchange(0, thread_id, Ordering::AcqRel, Ordering::Acquire)
+        {
+            if main != thread_id {
+                crate::__panic!("StaticSnarcCell entered outside of its main thread.")
+            }
+        }
+
+        if self.entered.swap(true, Ordering::Relaxed) {
+            crate::__panic!("StaticSnarcCell is already entered.")
+        }
+
+        let _guard = scopeguard::guard((), |_| self.entered.store(false, Ordering::Relaxed));
+
+        let value = unsafe {
+            (*self.value.get()).get_or_insert_with(self.init);
+            (*self.value.get()).as_ref().unwrap_unchecked()
+        };
+
+        f(value)
+    }
+}
+
+/// A sendable, non-owning pointer to the value of a [`StaticSnarcCell`].
+pub struct StaticSnarcRef<T: 'static> {
+    cell: &'static StaticSnarcCell<T>,
+}
+
+impl<T> StaticSnarcRef<T> {
+    /// Gets a reference to the value.
+    ///
+    /// Returns `None` if the cell is not currently entered on this thread.
+    #[track_caller]
+    pub fn get(&self) -> Option<&T> {
+        if self.cell.is_main_thread() && self.cell.entered.load(Ordering::Relaxed) {
+            unsafe { (*self.cell.value.get()).as_ref() }
+        } else {
+            crate::failed_get();
+            None
+        }
+    }
+}
+
+impl<T> Clone for StaticSnarcRef<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for StaticSnarcRef<T> {}
+
+#[cfg(test)]
+mod tests {
+    crate::static_snarc! {
+        static VALUE: String = String::from("static");
+    }
+
+    #[test]
+    fn static_refs_get_within_enter_only() {
+        let value_ref = VALUE.new_ref();
+        assert_eq!(value_ref.get(), None);
+
+        assert_eq!(VALUE.enter(|v| v.len()), 6);
+        assert_eq!(VALUE.enter(|_| value_ref.get().cloned()).unwrap(), "static");
+
+        let result = std::thread::spawn(|| VALUE.enter(|_| ())).join();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::thread::LocalKey;
+
+/// Declares global slots that owners are put into for the duration of a
+/// closure, see [`SnarcStaticSlot`].
+///
+/// ```
+/// snarc::snarc_static! {
+///     static LOG: Vec<&'static str>;
+/// }
+///
+/// fn deep_down() {
+///     LOG.with(|log| log.push("deep down"));
+/// }
+///
+/// let mut log = Vec::new();
+/// LOG.set(&mut log, deep_down);
+/// assert_eq!(log, ["deep down"]);
+/// ```
+#[macro_export]
+macro_rules! snarc_static {
+    ($(
+        $(#[$attr:meta])*
+        $vis:vis static $name:ident: $t:ty;
+    )*) => {$(
+        $(#[$attr])*
+        $vis static $name: $crate::SnarcStaticSlot<$t> = {
+            ::std::thread_local! {
+                static SLOT: ::std::cell::Cell<*mut ()> =
+                    const { ::std::cell::Cell::new(::std::ptr::null_mut()) };
+            }
+
+            $crate::SnarcStaticSlot::new(&SLOT)
+        };
+    )*};
+}
+
+/// A global slot, usually declared by way of [`snarc_static!`].
+///
+/// Deep call stacks may reach an owner, e.g., an `ErasedSnarc`, by way of
+/// `with` rather than threading it through every signature. The owner is only
+/// in the slot for the duration of `set`, and only on the thread that called
+/// it.
+pub struct SnarcStaticSlot<T: 'static> {
+    slot: &'static LocalKey<Cell<*mut ()>>,
+    _owner: PhantomData<fn(&mut T)>,
+}
+
+impl<T> SnarcStaticSlot<T> {
+    #[doc(hidden)]
+    pub const fn new(slot: &'static LocalKey<Cell<*mut ()>>) -> Self {
+        Self {
+            slot,
+            _owner: PhantomData,
+        }
+    }
+
+    /// Puts `owner` into the slot and evaluates `f`, the previous owner, if
+    /// any, is put back afterwards.
+    pub fn set<F, R>(&'static self, owner: &mut T, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let previous = self.replace((owner as *mut T).cast());
+
+        let _guard = scopeguard::guard((), |_| {
+            self.replace(previous);
+        });
+
+        f()
+    }
+
+    /// Evaluates `f` with the owner in the slot.
+    ///
+    /// Panics if called outside of `set` or from within another `with` of the
+    /// same slot.
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        // the slot is emptied, so that nested calls cannot alias the owner
+        let owner = self.replace(std::ptr::null_mut());
+
+        if owner.is_null() {
+            crate::__panic!("SnarcStaticSlot is not set or already in use.")
+        }
+
+        let _guard = scopeguard::guard((), |_| {
+            self.replace(owner);
+        });
+
+        f(unsafe { &mut *owner.cast::<T>() })
+    }
+
+    /// Returns `true` if an owner is in the slot, i.e., within `set` but not
+    /// within `with`.
+    pub fn is_set(&'static self) -> bool {
+        self.slot.with(|slot| !slot.get().is_null())
+    }
+
+    fn replace(&'static self, owner: *mut ()) -> *mut () {
+        self.slot.with(|slot| slot.replace(owner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    crate::snarc_static! {
+        static NAME: String;
+    }
+
+    #[test]
+    fn slots_are_set_within_set_only() {
+        assert!(!NAME.is_set());
+
+        let mut outer = String::from("outer");
+        let mut inner = String::from("inner");
+
+        let names = NAME.set(&mut outer, || {
+            let inner = NAME.set(&mut inner, || NAME.with(|name| name.clone()));
+            let outer = NAME.with(|name| name.clone());
+
+            (inner, outer)
+        });
+        assert_eq!(names, (String::from("inner"), String::from("outer")));
+        assert!(!NAME.is_set());
+
+        NAME.set(&mut outer, || {
+            let on_other_thread = std::thread::spawn(|| NAME.is_set()).join().unwrap();
+            assert!(NAME.is_set() && !on_other_thread);
+        });
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(feature = "defmt"),
+        should_panic(expected = "not set or already in use")
+    )]
+    #[cfg_attr(feature = "defmt", should_panic)]
+    fn slots_are_not_used_twice() {
+        let mut name = String::from("name");
+
+        NAME.set(&mut name, || NAME.with(|_| NAME.with(|_| ())));
+    }
+}
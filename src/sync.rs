@@ -0,0 +1,238 @@
+//! Defines twins of `Snarc`, `Narc` and `SnarcRef` based on `Arc` and `Mutex`.
+//!
+//! The structs in this module mirror the core API of those defined by the
+//! `snarc!` macro, so that call sites need not change when switching between
+//! them, e.g., by way of a type alias. References are counted atomically and
+//! `enter` holds a lock, which makes them a baseline for comparing the
+//! correctness and performance of the context-based strategy.
+//!
+//! Owners dereference to their value without taking the lock, so they are only
+//! `Sync` if the value is.
+//!
+//! ```compile_fail
+//! fn assert_sync<T: Sync>() {}
+//!
+//! assert_sync::<snarc::sync::Snarc<std::cell::Cell<u64>>>();
+//! ```
+use std::cell::UnsafeCell;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+struct SyncBox<T> {
+    lock: Mutex<()>,
+    holder: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for SyncBox<T> {}
+unsafe impl<T: Send> Sync for SyncBox<T> {}
+
+impl<T> SyncBox<T> {
+    fn new(value: T) -> Arc<Self> {
+        Arc::new(Self {
+            lock: Mutex::new(()),
+            holder: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+        })
+    }
+
+    fn enter<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let _lock = self.lock.lock().unwrap_or_else(|p| p.into_inner());
+
+        self.holder.store(crate::thread_id(), Ordering::Release);
+        let _guard = scopeguard::guard((), |_| self.holder.store(0, Ordering::Release));
+
+        f(unsafe { &*self.value.get() })
+    }
+
+    fn is_held(&self) -> bool {
+        self.holder.load(Ordering::Acquire) == crate::thread_id()
+    }
+}
+
+/// A sendable, owning pointer to a `T`, based on `Arc` and `Mutex`.
+pub struct Snarc<T> {
+    inner: Arc<SyncBox<T>>,
+    // `Deref` hands out the value without the lock, so `Sync` requires `T: Sync`
+    _value: std::marker::PhantomData<T>,
+}
+
+impl<T> Snarc<T> {
+    /// Creates a new `Snarc` with the given inner `value`.
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: SyncBox::new(value),
+            _value: Default::default(),
+        }
+    }
+
+    /// Creates a new `Snarc` with the inner value returned by `f`.
+    pub fn try_new_with<E, F>(f: F) -> Result<Self, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        f().map(Self::new)
+    }
+
+    /// Turn this `Snarc` into the `!Send` version `Narc`.
+    pub fn into_unsend(self) -> Narc<T> {
+        Narc {
+            inner: self.inner,
+            _value: Default::default(),
+            _unsend: Default::default(),
+        }
+    }
+
+    /// Creates a new non-owning reference to the inner value.
+    pub fn new_ref(&self) -> SnarcRef<T> {
+        SnarcRef {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Lock the inner value, bind it to this thread and evaluate `f` within
+    /// that context.
+    pub fn enter<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.inner.enter(f)
+    }
+}
+
+impl<T> Deref for Snarc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // references only access the value within `enter`, during which the
+        // owner cannot be borrowed mutably
+        unsafe { &*self.inner.value.get() }
+    }
+}
+
+impl<T> DerefMut for Snarc<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.inner.value.get() }
+    }
+}
+
+/// A non-sendable, owning pointer to a `T`, based on `Arc` and `Mutex`.
+pub struct Narc<T> {
+    inner: Arc<SyncBox<T>>,
+    _value: std::marker::PhantomData<T>,
+    _unsend: std::marker::PhantomData<std::sync::MutexGuard<'static, ()>>,
+}
+
+impl<T> Narc<T> {
+    /// Creates a new `Narc` with the given inner `value`.
+    pub fn new(value: T) -> Self {
+        Snarc::new(value).into_unsend()
+    }
+
+    /// Turn this `Narc` into the `Send` version `Snarc`.
+    pub fn into_send(self) -> Snarc<T> {
+        Snarc {
+            inner: self.inner,
+            _value: Default::default(),
+        }
+    }
+
+    /// Creates a new non-owning reference to the inner value.
+    pub fn new_ref(&self) -> SnarcRef<T> {
+        SnarcRef {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> Deref for Narc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.inner.value.get() }
+    }
+}
+
+impl<T> DerefMut for Narc<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.inner.value.get() }
+    }
+}
+
+/// A sendable, non-owning pointer to a `T`, based on `Arc` and `Mutex`.
+///
+/// Unlike the references defined by the `snarc!` macro, these may be cloned
+/// and dropped anywhere.
+///
+/// The last reference to be dropped may drop the value by way of the `Arc`,
+/// so references are only `Send` if the value is.
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+///
+/// assert_send::<snarc::sync::SnarcRef<std::rc::Rc<u64>>>();
+/// ```
+pub struct SnarcRef<T> {
+    inner: Arc<SyncBox<T>>,
+}
+
+impl<T> SnarcRef<T> {
+    /// Gets a reference to the inner value.
+    ///
+    /// Returns `None` if the corresponding owning pointer did not currently
+    /// bind the inner value to the current thread.
+    pub fn get(&self) -> Option<&T> {
+        if self.inner.is_held() {
+            Some(unsafe { &*self.inner.value.get() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Clone for SnarcRef<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Snarc;
+
+    #[test]
+    fn snarc_refs_get_within_enter_only() {
+        let snarc = Snarc::new(1);
+        let snarc_ref = snarc.new_ref();
+
+        assert_eq!(snarc_ref.get(), None);
+        assert_eq!(snarc.enter(|_| snarc_ref.get().copied()), Some(1));
+
+        let snarc_ref = snarc_ref.clone();
+        std::thread::spawn(move || assert_eq!(snarc_ref.get(), None))
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn sync_owners_deref_on_other_threads() {
+        let snarc = Snarc::new(std::sync::atomic::AtomicU64::new(0));
+
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| snarc.fetch_add(1, std::sync::atomic::Ordering::Relaxed));
+            }
+        });
+
+        assert_eq!(
+            snarc.enter(|v| v.load(std::sync::atomic::Ordering::Relaxed)),
+            4
+        );
+    }
+}
@@ -1,13 +1,29 @@
 macro_rules! tests {
     ($send:ident, $unsend:ident, $ref:ident) => {
         mod _tests {
+            use std::cell::Cell;
             use std::cell::RefCell;
             use std::rc::Rc;
+            use std::sync::atomic::AtomicBool;
+            use std::sync::atomic::AtomicUsize;
+            use std::sync::atomic::Ordering;
             use std::sync::Arc;
             use std::sync::Mutex;
 
+            use crate::AsyncDispose;
+            use crate::CallbackSet;
+            use crate::Command;
+            use crate::DoubleBuffer;
             use crate::ErasedNarc;
             use crate::ErasedSnarc;
+            use crate::ErasedSnarcRef;
+            use crate::FfiToken;
+            use crate::Journal;
+            use crate::OnceRef;
+            use crate::recorder::Recorder;
+            use crate::SnarcOrArc;
+            use crate::SnarcWatch;
+            use crate::WrongType;
 
             use super::$send as Snarc;
             use super::$unsend as Narc;
@@ -19,6 +35,32 @@ macro_rules! tests {
                 static_assertions::assert_not_impl_all!(Snarc<Rc<()>>: Send);
             }
 
+            #[test]
+            fn erased_snarc_is_sync_only_by_way_of_sync_erased_snarc() {
+                static_assertions::assert_impl_all!(ErasedSnarc: Send);
+                static_assertions::assert_not_impl_any!(ErasedSnarc: Sync);
+                static_assertions::assert_impl_all!(crate::SyncErasedSnarc: Send, Sync);
+                static_assertions::assert_impl_all!(crate::SyncErasedSnarc: From<Snarc<u8>>);
+                static_assertions::assert_not_impl_any!(
+                    crate::SyncErasedSnarc: From<Snarc<std::cell::Cell<u8>>>
+                );
+            }
+
+            #[test]
+            fn sync_erased_snarc_binds_its_value() {
+                let snarc = Snarc::new(5);
+                let snarc_ref = snarc.new_ref();
+
+                let mut erased = crate::SyncErasedSnarc::from(snarc);
+                let shared = &erased;
+                std::thread::scope(|s| {
+                    s.spawn(move || assert!(!shared.is_entered()));
+                });
+
+                erased.enter(|| assert_eq!(snarc_ref.get(), Some(&5)));
+                ErasedSnarc::from(erased).enter(|| drop(snarc_ref));
+            }
+
             #[test]
             fn narc_is_not_send() {
                 static_assertions::assert_not_impl_all!(Narc<()>: Send);
@@ -133,6 +175,18 @@ macro_rules! tests {
                 assert!(tester.dropped());
             }
 
+            #[test]
+            fn erased_snarc_stores_the_owner_inline() {
+                assert_eq!(
+                    std::mem::size_of::<ErasedSnarc>(),
+                    2 * std::mem::size_of::<usize>()
+                );
+                assert_eq!(
+                    std::mem::size_of::<ErasedSnarc>(),
+                    std::mem::size_of::<ErasedNarc>()
+                );
+            }
+
             #[test]
             fn erased_narc_from_snarc() {
                 let snarc = Snarc::new(Droppable::new());
@@ -149,6 +203,67 @@ macro_rules! tests {
                 assert!(tester.dropped());
             }
 
+            #[test]
+            fn erased_owners_name_their_type() {
+                let name = std::any::type_name::<String>();
+                let erased = ErasedSnarc::from(Snarc::new(String::new()));
+
+                assert_eq!(erased.type_name(), name);
+                assert_eq!(format!("{erased:?}"), format!("ErasedSnarc({name})"));
+
+                let erased = ErasedNarc::from(erased);
+
+                assert_eq!(erased.type_name(), name);
+                assert_eq!(format!("{erased:?}"), format!("ErasedNarc({name})"));
+            }
+
+            #[test]
+            fn erased_snarc_downcasts_to_its_owner() {
+                let erased = ErasedSnarc::from(Snarc::new(5));
+
+                let erased = erased.downcast_owner::<Snarc<u8>>().err().unwrap();
+                let mut snarc = erased.downcast_owner::<Snarc<i32>>().ok().unwrap();
+
+                assert_eq!(Snarc::get_mut(&mut snarc), Some(&mut 5));
+            }
+
+            #[test]
+            fn narc_binds_its_value_only_within_enter() {
+                let narc = Narc::new(5);
+                let snarc_ref = narc.new_ref();
+
+                assert_eq!(snarc_ref.get(), None);
+
+                narc.enter(|v| {
+                    assert_eq!(*v, 5);
+                    assert_eq!(snarc_ref.get(), Some(&5));
+                });
+
+                assert_eq!(snarc_ref.get(), None);
+
+                let erased = ErasedNarc::from(narc);
+
+                assert_eq!(snarc_ref.get(), Some(&5));
+
+                drop(snarc_ref);
+                drop(erased);
+            }
+
+            #[test]
+            fn narc_of_unsendable_value_is_erased() {
+                let narc = Narc::new(Rc::new(5));
+                let snarc_ref = narc.new_ref();
+
+                let erased = narc.into_erased_unsend();
+                assert_eq!(snarc_ref.get().map(|v| **v), Some(5));
+
+                let erased = erased.try_into_send().err().unwrap();
+                assert_eq!(erased.type_name(), std::any::type_name::<Rc<i32>>());
+
+                drop(snarc_ref);
+                drop(erased);
+            }
+
             #[test]
             fn narc_owns_its_value() {
                 let mut narc = Narc::new(5);
@@ -252,7 +367,7 @@ macro_rules! tests {
 
             #[test]
             fn snarc_ref() {
-                let mut snarc = Snarc::new(5);
+                let snarc = Snarc::new(5);
 
                 let snarc_ref = snarc.new_ref();
 
@@ -268,7 +383,7 @@ macro_rules! tests {
 
             #[test]
             fn snarc_ref_is_clonable() {
-                let mut snarc = Snarc::new(5);
+                let snarc = Snarc::new(5);
 
                 let snarc_ref = snarc.new_ref();
 
@@ -285,6 +400,7 @@ macro_rules! tests {
 
             #[test]
             #[should_panic]
+            #[cfg(not(feature = "atomic-clone"))]
             fn clone_snarc_ref_in_invalid_context_panics() {
                 let mut snarc = Snarc::new(SelfReferential(None));
                 *snarc = SelfReferential(Some(snarc.new_ref()));
@@ -293,47 +409,1652 @@ macro_rules! tests {
             }
 
             #[test]
-            #[should_panic]
-            #[cfg(debug_assertions)]
-            fn drop_snarc_ref_in_invalid_context_panics() {
+            #[cfg(not(feature = "atomic-clone"))]
+            fn detached_clones_cost_nothing_without_atomic_clone() {
+                let balance = crate::Balance::default();
+
+                assert_eq!(std::mem::size_of::<crate::Detached>(), 0);
+                assert!(!crate::Detached::try_increment(&balance));
+                assert_eq!(balance.refs(0), 0);
+            }
+
+            #[test]
+            fn drop_snarc_ref_in_invalid_context_is_deferred() {
+                let snarc = Snarc::new(5);
+                let snarc_ref = snarc.new_ref();
+                let other = snarc.new_ref();
+
+                std::thread::spawn(move || drop(snarc_ref)).join().unwrap();
+                assert_eq!(snarc.ref_count(), 1);
+
+                snarc.enter(|_| drop(other));
+                assert!(snarc.is_unique());
+            }
+
+            #[test]
+            fn last_ref_frees_the_allocation_once_the_owner_is_gone() {
                 let snarc = Snarc::new(5);
+                let refs: Vec<_> = (0..8).map(|_| snarc.new_ref()).collect();
+                drop(snarc);
+
+                let handles: Vec<_> = refs
+                    .into_iter()
+                    .map(|snarc_ref| {
+                        std::thread::spawn(move || {
+                            assert!(snarc_ref.is_dangling());
+                            drop(snarc_ref);
+                        })
+                    })
+                    .collect();
+
+                for handle in handles {
+                    handle.join().unwrap();
+                }
+            }
+
+            #[test]
+            fn defer_runs_at_the_next_enter() {
+                let mut snarc = Snarc::new(Mutex::new(Vec::new()));
+                let snarc_ref = snarc.new_ref();
+
+                std::thread::spawn(move || {
+                    snarc_ref.defer(|v: &Mutex<Vec<usize>>| v.lock().unwrap().push(1));
+                    snarc_ref.defer(|v: &Mutex<Vec<usize>>| v.lock().unwrap().push(2));
+                    snarc_ref
+                })
+                .join()
+                .unwrap();
+                assert!(snarc.lock().unwrap().is_empty());
+
+                snarc.enter(|v| assert_eq!(*v.lock().unwrap(), vec![1, 2]));
+
+                let revoked = snarc.new_ref();
+                snarc.revoke_refs();
+                revoked.defer(|v: &Mutex<Vec<usize>>| v.lock().unwrap().push(3));
+                snarc.enter(|v| assert_eq!(*v.lock().unwrap(), vec![1, 2]));
+            }
 
+            #[test]
+            fn enter_with_messages_receives_sent_messages() {
+                let mut snarc = Snarc::new(Mutex::new(0));
                 let snarc_ref = snarc.new_ref();
 
+                let snarc_ref = std::thread::spawn(move || {
+                    snarc_ref.send(1usize).unwrap();
+                    snarc_ref.send(2usize).unwrap();
+                    snarc_ref
+                })
+                .join()
+                .unwrap();
+
+                snarc.enter_with_messages(|v, messages: Vec<usize>| {
+                    *v.lock().unwrap() = messages.iter().sum();
+                });
+                assert_eq!(*snarc.lock().unwrap(), 3);
+                snarc.enter_with_messages(|_, messages: Vec<usize>| assert!(messages.is_empty()));
+
+                snarc.revoke_refs();
+                assert_eq!(snarc_ref.send(4usize), Err(4));
+            }
+
+            #[test]
+            fn new_refs_never_resolve() {
+                let snarc_ref = SnarcRef::<usize>::new();
+                assert!(snarc_ref.is_dangling());
+                assert!(snarc_ref.get().is_none());
+
+                let snarc = Snarc::new(1);
+                snarc.enter(|_| {
+                    let clone = snarc_ref.clone();
+                    assert!(clone.get().is_none());
+                    assert!(SnarcRef::<usize>::default().get().is_none());
+                });
+
+                // dropped outside of `enter`, freeing the allocation
+                drop(snarc_ref.clone());
                 drop(snarc_ref);
             }
 
-            struct SelfReferential(Option<SnarcRef<SelfReferential>>);
+            #[test]
+            #[cfg(feature = "deref-panicking")]
+            fn refs_deref_within_enter() {
+                let snarc = Snarc::new(String::from("value"));
+                let snarc_ref = snarc.new_ref();
 
-            struct Droppable(Arc<Mutex<bool>>);
+                snarc.enter(|_| assert_eq!(snarc_ref.len(), 5));
 
-            impl Droppable {
-                fn new() -> Self {
-                    Self(Default::default())
+                let snarc_ref = std::panic::AssertUnwindSafe(snarc_ref);
+                assert!(std::panic::catch_unwind(|| snarc_ref.len()).is_err());
+            }
+
+            #[test]
+            #[cfg(feature = "snapshot")]
+            fn snapshot_clones_while_neither_entered_nor_mutated() {
+                let mut snarc = Snarc::new(String::from("value"));
+                let snarc_ref = snarc.new_ref();
+
+                let (snarc_ref, snapshot) = std::thread::spawn(move || {
+                    let snapshot = snarc_ref.snapshot();
+                    (snarc_ref, snapshot)
+                })
+                .join()
+                .unwrap();
+                assert_eq!(snapshot.as_deref(), Some("value"));
+
+                snarc.enter(|_| assert_eq!(snarc_ref.snapshot(), None));
+
+                snarc.push('!');
+                assert_eq!(snarc_ref.snapshot(), None);
+
+                snarc.enter(|_| {});
+                assert_eq!(snarc_ref.snapshot().as_deref(), Some("value!"));
+            }
+
+            #[test]
+            fn dispatch_invokes_handlers_within_context() {
+                let mut snarc = Snarc::new(Mutex::new(Vec::new()));
+                let callbacks = CallbackSet::new();
+
+                for i in 0..3 {
+                    let snarc_ref = snarc.new_ref();
+                    callbacks.register(move |n: &usize| {
+                        snarc_ref.get().unwrap().lock().unwrap().push(i * n);
+                    });
                 }
 
-                fn tester(&self) -> DropTester {
-                    DropTester(Arc::clone(&self.0))
+                snarc.dispatch(&callbacks, &2);
+
+                assert_eq!(*snarc.lock().unwrap(), vec![0, 2, 4]);
+
+                snarc.enter(|_| callbacks.clear());
+            }
+
+            #[test]
+            fn dispatch_retains_handlers_on_panic() {
+                let mut snarc = Snarc::new(());
+                let callbacks = CallbackSet::new();
+
+                let snarc_ref = snarc.new_ref();
+                callbacks.register(move |panic: &bool| {
+                    assert!(snarc_ref.get().is_some());
+                    assert!(!*panic);
+                });
+
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    snarc.dispatch(&callbacks, &true)
+                }));
+
+                assert!(result.is_err());
+                assert_eq!(callbacks.len(), 1);
+
+                snarc.dispatch(&callbacks, &false);
+                snarc.enter(|_| callbacks.clear());
+            }
+
+            #[test]
+            fn snarc_ref_roundtrips_through_token() {
+                let snarc = Snarc::new(5);
+
+                let token = snarc.new_ref().into_token();
+                let raw = token.into_raw();
+
+                let snarc_ref = SnarcRef::<i32>::from_token(FfiToken::from_raw(raw)).unwrap();
+
+                snarc.enter(|_| {
+                    assert_eq!(snarc_ref.get(), Some(&5));
+
+                    drop(snarc_ref);
+                });
+            }
+
+            #[test]
+            fn stale_token_resolves_to_none() {
+                let snarc = Snarc::new(5);
+
+                let token = snarc.new_ref().into_token();
+
+                assert!(token.resolve::<SnarcRef<u8>>().is_none());
+
+                let snarc_ref = token.resolve::<SnarcRef<i32>>().unwrap();
+
+                assert!(token.resolve::<SnarcRef<i32>>().is_none());
+
+                snarc.enter(|_| drop(snarc_ref));
+            }
+
+            #[test]
+            #[cfg_attr(not(feature = "defmt"), should_panic(expected = "Too many references."))]
+            #[cfg_attr(feature = "defmt", should_panic)]
+            #[cfg(feature = "count-u16")]
+            fn new_ref_panics_on_count_overflow() {
+                let snarc = Snarc::new(0);
+
+                for _ in 0..=u16::MAX {
+                    std::mem::forget(snarc.new_ref());
                 }
             }
 
-            impl Drop for Droppable {
-                fn drop(&mut self) {
-                    let mut dropped = self.0.lock().unwrap();
-                    *dropped = true;
+            #[test]
+            fn zero_sized_value_is_dropped_once() {
+                static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+                struct Zst;
+
+                impl Drop for Zst {
+                    fn drop(&mut self) {
+                        DROPS.fetch_add(1, Ordering::Relaxed);
+                    }
                 }
+
+                let snarc = Snarc::new(Zst);
+                let snarc_ref = snarc.new_ref();
+
+                snarc.enter(|_| {
+                    assert!(snarc_ref.get().is_some());
+
+                    drop(SnarcRef::clone(&snarc_ref));
+                    drop(snarc_ref);
+                });
+
+                assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+
+                drop(snarc);
+
+                assert_eq!(DROPS.load(Ordering::Relaxed), 1);
             }
 
-            struct DropTester(Arc<Mutex<bool>>);
+            #[test]
+            fn self_ref_references_entered_value() {
+                struct Node(i32);
 
-            impl DropTester {
-                fn dropped(&self) -> bool {
-                    let dropped = self.0.lock().unwrap();
-                    *dropped
+                impl Node {
+                    fn spawn(&self) -> SnarcRef<Node> {
+                        SnarcRef::self_ref().unwrap()
+                    }
                 }
+
+                let snarc = Snarc::new(Node(5));
+
+                assert!(SnarcRef::<Node>::self_ref().is_none());
+
+                let snarc_ref = snarc.enter_with_self(|node| node.spawn());
+
+                assert!(SnarcRef::<Node>::self_ref().is_none());
+                assert!(snarc.enter(|_| SnarcRef::<Node>::self_ref().is_none()));
+
+                snarc.enter(|_| {
+                    assert_eq!(snarc_ref.get().map(|n| n.0), Some(5));
+
+                    drop(snarc_ref);
+                });
             }
-        }
-    }
-}
 
-pub(crate) use tests;
+            #[test]
+            fn snarc_refs_read_published_buffer() {
+                let mut snarc = Snarc::new(DoubleBuffer::from_value(vec![0]));
+
+                let snarc_ref = snarc.new_ref();
+
+                snarc.back_mut().push(1);
+
+                snarc.enter(|_| assert_eq!(snarc_ref.get().unwrap().front(), &[0]));
+
+                snarc.publish();
+                snarc.back_mut().clear();
+
+                snarc.enter(|_| {
+                    assert_eq!(snarc_ref.get().unwrap().front(), &[0, 1]);
+
+                    drop(snarc_ref);
+                });
+            }
+
+            #[test]
+            fn journal_records_commands_of_snarc_refs() {
+                #[derive(Clone)]
+                struct Add(i32);
+
+                impl Command<i32> for Add {
+                    fn apply(&mut self, target: &mut i32) {
+                        *target += self.0;
+                    }
+
+                    fn undo(&mut self, target: &mut i32) {
+                        *target -= self.0;
+                    }
+                }
+
+                let snarc = Snarc::new(Journal::new(0));
+
+                let snarc_ref = snarc.new_ref();
+
+                snarc.enter(|_| {
+                    let journal = snarc_ref.get().unwrap();
+
+                    journal.execute(Add(2));
+                    journal.execute(Add(3));
+                    assert_eq!(*journal.borrow(), 5);
+
+                    assert!(journal.undo());
+                    assert_eq!(*journal.borrow(), 2);
+
+                    assert!(journal.redo());
+                    assert!(!journal.redo());
+                    assert_eq!(*journal.borrow(), 5);
+
+                    drop(snarc_ref);
+                });
+
+                let mut replayed = 10;
+                snarc.replay(&mut replayed);
+
+                assert_eq!(replayed, 15);
+                assert_eq!(snarc.history().len(), 2);
+            }
+
+            #[test]
+            fn listeners_are_notified_on_next_enter() {
+                let mut snarc = Snarc::new(1);
+                let snarc_ref = snarc.new_ref();
+
+                let listener = snarc.enter(|_| snarc_ref.subscribe());
+
+                *snarc = 2;
+                assert!(!listener.has_changed());
+
+                snarc.enter(|_| drop(snarc_ref));
+
+                assert!(listener.take_changed());
+                assert!(!listener.take_changed());
+            }
+
+            #[test]
+            fn mutable_access_increments_version() {
+                let mut snarc = Snarc::new(1);
+                let snarc_ref = snarc.new_ref();
+                assert_eq!(snarc.version(), 0);
+
+                *snarc += 1;
+                assert_eq!(snarc.version(), 1);
+                assert_eq!(snarc_ref.version(), None);
+
+                snarc.enter(|_| {
+                    assert_eq!(snarc_ref.version(), Some(1));
+                    drop(snarc_ref);
+                });
+            }
+
+            #[test]
+            fn scoped_future_is_polled_within_context() {
+                let mut snarc = Snarc::new(7);
+                let snarc_ref = snarc.new_ref();
+
+                let value = futures::executor::block_on(snarc.scoped_future(async {
+                    futures::future::ready(()).await;
+                    let value = *snarc_ref.get().unwrap();
+                    drop(snarc_ref);
+                    value
+                }));
+
+                assert_eq!(value, 7);
+            }
+
+            #[test]
+            fn watch_refs_read_without_entering() {
+                let snarc = Snarc::new(SnarcWatch::new());
+                let snarc_ref = snarc.new_ref();
+                let watch_ref = snarc.watch();
+
+                assert!(watch_ref.latest().is_none());
+
+                snarc.enter(|_| {
+                    snarc_ref.get().unwrap().publish(3);
+                    drop(snarc_ref);
+                });
+
+                let latest = std::thread::spawn(move || watch_ref.latest())
+                    .join()
+                    .unwrap();
+
+                assert_eq!(latest.as_deref(), Some(&3));
+            }
+
+            #[test]
+            fn snarc_or_arc_accesses_either() {
+                let snarc = Snarc::new(1);
+                let snarc_or_arc = SnarcOrArc::from(snarc.new_ref());
+                let arc_or_snarc = SnarcOrArc::from(Arc::new(2));
+
+                assert_eq!(snarc_or_arc.with(|v| *v), None);
+                assert_eq!(arc_or_snarc.with(|v| *v), Some(2));
+
+                snarc.enter(|_| {
+                    assert_eq!(snarc_or_arc.with(|v| *v), Some(1));
+                    drop(snarc_or_arc);
+                });
+            }
+
+            #[test]
+            fn when_entered_resolves_on_next_enter() {
+                use futures::FutureExt;
+
+                let snarc = Snarc::new(1);
+                let snarc_ref = snarc.new_ref();
+
+                let mut entered = Box::pin(snarc_ref.when_entered());
+                assert!(entered.as_mut().now_or_never().is_none());
+
+                snarc.enter(|_| {});
+
+                assert!(entered.now_or_never().is_some());
+                snarc.enter(|_| drop(snarc_ref));
+            }
+
+            #[test]
+            fn once_ref_projects_until_mutation() {
+                let mut snarc = Snarc::new((1, 2));
+                let projections = Cell::new(0);
+                let once_ref = OnceRef::new(snarc.new_ref(), |v: &(i32, i32)| {
+                    projections.set(projections.get() + 1);
+                    &v.1
+                });
+
+                assert_eq!(once_ref.get(), None);
+
+                snarc.enter(|_| assert_eq!(once_ref.get(), Some(&2)));
+                snarc.enter(|_| assert_eq!(once_ref.get(), Some(&2)));
+                assert_eq!(projections.get(), 1);
+
+                snarc.1 = 3;
+
+                snarc.enter(|_| {
+                    assert_eq!(once_ref.get(), Some(&3));
+                    drop(once_ref.into_inner());
+                });
+                assert_eq!(projections.get(), 2);
+            }
+
+            #[test]
+            fn try_new_with_propagates_errors() {
+                let snarc = Snarc::try_new_with(|| Ok::<_, ()>(5)).unwrap();
+                assert_eq!(*snarc, 5);
+
+                let narc = Narc::<Droppable>::try_new_with(|| Err("failed"));
+                assert_eq!(narc.err(), Some("failed"));
+            }
+
+            #[cfg(feature = "diagnostics")]
+            #[test]
+            fn failed_gets_are_recorded() {
+                let snarc = Snarc::new(1);
+                let snarc_ref = snarc.new_ref();
+
+                assert!(snarc_ref.get().is_none());
+
+                let failed_gets = crate::diagnostics::failed_gets();
+                assert!(failed_gets.iter().any(|f| f.location.file() == file!()));
+
+                snarc.enter(|_| drop(snarc_ref));
+            }
+
+            #[test]
+            fn recordings_replay_enters() {
+                let recorder = Recorder::<i32>::start();
+
+                let snarc = Snarc::new(RefCell::new(0));
+                let snarc_ref = snarc.new_ref();
+
+                for delta in [1, 2] {
+                    snarc.enter(|v| {
+                        *v.borrow_mut() += delta;
+                        crate::recorder::record_command(delta);
+                    });
+                }
+                snarc.enter(|_| drop(snarc_ref));
+
+                let recording = recorder.finish();
+
+                let fresh = Snarc::new(RefCell::new(0));
+                let mut enters = 0;
+                recording.replay(0, |commands| {
+                    enters += 1;
+                    fresh.enter(|v| commands.iter().for_each(|c| *v.borrow_mut() += **c));
+                });
+
+                assert_eq!(enters, 3);
+                assert_eq!(*fresh.borrow(), *snarc.borrow());
+            }
+
+            #[test]
+            fn dispose_async_tears_down_within_context() {
+                struct Session {
+                    this: Option<SnarcRef<Session>>,
+                    disposed: Arc<AtomicUsize>,
+                    _droppable: Droppable,
+                }
+
+                impl AsyncDispose for Session {
+                    async fn dispose(&self) {
+                        futures::future::ready(()).await;
+                        assert!(self.this.as_ref().unwrap().get().is_some());
+                        self.disposed.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+
+                let disposed = Arc::new(AtomicUsize::new(0));
+                let droppable = Droppable::new();
+                let tester = droppable.tester();
+
+                let mut snarc = Snarc::new(Session {
+                    this: None,
+                    disposed: Arc::clone(&disposed),
+                    _droppable: droppable,
+                });
+                snarc.this = Some(snarc.new_ref());
+
+                futures::executor::block_on(snarc.dispose_async());
+
+                assert_eq!(disposed.load(Ordering::SeqCst), 1);
+                assert!(tester.dropped());
+            }
+
+            #[cfg(feature = "defmt")]
+            #[test]
+            fn handles_implement_format() {
+                static_assertions::assert_impl_all!(Snarc<()>: defmt::Format);
+                static_assertions::assert_impl_all!(Narc<()>: defmt::Format);
+                static_assertions::assert_impl_all!(SnarcRef<()>: defmt::Format);
+            }
+
+            #[test]
+            fn extension_traits_access_values() {
+                use crate::prelude::*;
+
+                let snarc = Snarc::new((1, 2));
+                let snarc_ref = snarc.new_ref();
+
+                assert_eq!(SnarcRefExt::with(&snarc_ref, |v| v.0), None);
+
+                snarc.enter(|_| {
+                    assert_eq!(SnarcRefExt::with(&snarc_ref, |v| v.0), Some(1));
+                    assert_eq!(SnarcRefExt::map(&snarc_ref, |v| &v.1), Some(&2));
+                    assert_eq!(snarc_ref.expect_msg("entered"), &(1, 2));
+                    drop(snarc_ref);
+                });
+
+                let mut erased = IntoErased::into_erased(snarc);
+                erased.enter(|| {});
+            }
+
+            #[test]
+            fn enter_as_checks_the_type() {
+                let mut erased = Snarc::new(3u8).into_erased();
+
+                assert_eq!(erased.enter_as(|v: &u8| *v), Ok(3));
+                assert_eq!(erased.enter_as(|v: &u16| *v), Err(WrongType));
+            }
+
+            #[test]
+            fn erased_snarc_is_not_entered_outside_of_enter() {
+                let mut erased = Snarc::new(3u8).into_erased();
+
+                assert!(!erased.is_entered());
+                erased.enter(|| {});
+                assert!(!erased.is_entered());
+            }
+
+            #[test]
+            fn erased_enter_guard_binds_until_dropped() {
+                let snarc = Snarc::new(1);
+                let snarc_ref = snarc.new_ref();
+                let mut erased = ErasedSnarc::from(snarc);
+
+                let guard = erased.enter_guard();
+                assert_eq!(guard.type_name(), std::any::type_name::<i32>());
+                assert_eq!(snarc_ref.get(), Some(&1));
+                drop(guard);
+
+                assert!(!erased.is_entered());
+                assert_eq!(snarc_ref.get(), None);
+                erased.enter(|| drop(snarc_ref));
+            }
+
+            #[test]
+            #[cfg_attr(not(feature = "defmt"), should_panic(expected = "EnterGuard was leaked"))]
+            #[cfg_attr(feature = "defmt", should_panic)]
+            fn leaked_erased_enter_guard_poisons_owner() {
+                let mut erased = ErasedSnarc::from(Snarc::new(1));
+                std::mem::forget(erased.enter_guard());
+
+                erased.enter(|| {});
+            }
+
+            #[test]
+            fn erased_snarcs_are_entered_all_at_once() {
+                use crate::EnterAll;
+
+                let snarc = Snarc::new(1);
+                let snarc_ref = snarc.new_ref();
+
+                let mut owners = vec![snarc.into_erased()];
+                assert_eq!(owners.enter_all(|| snarc_ref.get().copied()), Some(1));
+                assert!(!owners[0].is_entered());
+
+                owners.iter_mut().enter_all(|| drop(snarc_ref));
+            }
+
+            #[test]
+            fn erased_slot_enters_whichever_owner_is_present() {
+                let snarc = Snarc::new(1);
+                let snarc_ref = snarc.new_ref();
+
+                let mut slot = crate::ErasedSlot::new();
+                assert_eq!(slot.enter_if_present(|| snarc_ref.get().copied()), None);
+
+                assert!(slot.replace(snarc).is_none());
+                assert!(slot.is_present());
+                assert_eq!(
+                    slot.enter_if_present(|| snarc_ref.get().copied()),
+                    Some(Some(1))
+                );
+
+                let mut erased = slot.take().unwrap();
+                assert!(!slot.is_present());
+                erased.enter(|| drop(snarc_ref));
+            }
+
+            #[test]
+            fn erased_snarc_enter_catch_survives_panics() {
+                let snarc = Snarc::new(1);
+                let snarc_ref = std::panic::AssertUnwindSafe(snarc.new_ref());
+
+                let mut erased = snarc.into_erased();
+
+                let panic = erased
+                    .enter_catch(|| panic!("handler {}", snarc_ref.get().unwrap()))
+                    .err()
+                    .unwrap();
+                assert_eq!(panic.downcast_ref::<String>().unwrap(), "handler 1");
+                assert!(!erased.is_entered());
+
+                assert_eq!(erased.enter_catch(|| snarc_ref.get().copied()).ok(), Some(Some(1)));
+                erased.enter(|| drop(snarc_ref));
+            }
+
+            #[test]
+            fn erased_snarc_may_borrow_from_its_scope() {
+                let greeting = String::from("hello");
+
+                let snarc = Snarc::new(greeting.as_str());
+                let snarc_ref = snarc.new_ref();
+
+                let mut set = crate::EnterSet::new();
+                set.push(snarc.into_erased_scoped());
+                assert_eq!(set.enter(|| snarc_ref.get().map(|s| s.len())), Some(5));
+
+                let mut erased = set.into_inner().pop().unwrap();
+                assert_eq!(erased.type_name(), std::any::type_name::<&str>());
+                erased.enter(|| drop(snarc_ref));
+            }
+
+            #[test]
+            fn scoped_erased_snarc_is_not_downcast() {
+                let mut erased = Snarc::new(5).into_erased_scoped::<'static>();
+
+                assert_eq!(erased.enter_as(|v: &i32| *v), Err(crate::WrongType));
+                assert!(erased.downcast_owner::<Snarc<i32>>().is_err());
+            }
+
+            #[test]
+            fn snarc_type_map_enters_all_owners() {
+                let number = Snarc::new(1);
+                let number_ref = number.new_ref();
+
+                let mut map = crate::SnarcTypeMap::new();
+                assert!(map.insert(number).is_none());
+                assert!(map.contains::<Snarc<i32>>());
+                assert!(!map.contains::<Snarc<u8>>());
+
+                assert_eq!(map.enter_all(|| number_ref.get().copied()), Some(1));
+
+                let replaced = map.insert(Snarc::new(2)).unwrap();
+                assert_eq!(map.len(), 1);
+
+                let number = map.remove::<Snarc<i32>>().unwrap();
+                assert!(map.is_empty());
+                assert_eq!(number.enter(|v| *v), 2);
+
+                replaced.enter(|_| drop(number_ref));
+            }
+
+            #[test]
+            fn registered_owners_are_entered_by_key() {
+                use crate::registry::RegistryError;
+
+                // keys are global, so they must differ between families
+                let key = std::any::type_name::<Snarc<i32>>();
+
+                let snarc = Snarc::new(1);
+                let snarc_ref = snarc.new_ref();
+
+                crate::registry::register(key, snarc).unwrap();
+                assert!(crate::registry::is_registered(key));
+                assert!(crate::registry::keys().iter().any(|k| k == key));
+
+                let rejected = crate::registry::register(key, Snarc::new(2)).err().unwrap();
+                assert_eq!(rejected.type_name(), std::any::type_name::<i32>());
+
+                let value = crate::registry::enter(key, || {
+                    let nested = crate::registry::enter(key, || ());
+                    assert_eq!(nested, Err(RegistryError::Entered));
+                    assert_eq!(crate::registry::deregister(key).err(), Some(RegistryError::Entered));
+
+                    snarc_ref.get().copied()
+                });
+                assert_eq!(value, Ok(Some(1)));
+
+                let result = std::thread::spawn(move || crate::registry::enter(key, || ()))
+                    .join()
+                    .unwrap();
+                assert_eq!(result, Err(RegistryError::OtherThread));
+
+                let mut erased = crate::registry::deregister(key).unwrap();
+                assert!(!crate::registry::is_registered(key));
+                assert_eq!(crate::registry::enter(key, || ()), Err(RegistryError::NotRegistered));
+
+                erased.enter(|| drop(snarc_ref));
+            }
+
+            #[test]
+            fn registered_owners_are_deregistered_once_their_thread_exits() {
+                // keys are global, so they must differ between families
+                let key = std::any::type_name::<Snarc<u8>>();
+
+                let droppable = Droppable::new();
+                let tester = droppable.tester();
+
+                std::thread::spawn(move || {
+                    crate::registry::register(key, Snarc::new(droppable)).unwrap();
+                    assert!(crate::registry::is_registered(key));
+                })
+                .join()
+                .unwrap();
+
+                assert!(!crate::registry::is_registered(key));
+                assert!(tester.dropped());
+            }
+
+            #[test]
+            fn erased_snarc_is_reached_by_way_of_static_slot() {
+                crate::snarc_static! {
+                    static CONTEXT: crate::ErasedSnarc;
+                }
+
+                fn deep_down(snarc_ref: &SnarcRef<i32>) -> Option<i32> {
+                    CONTEXT.with(|erased| erased.enter(|| snarc_ref.get().copied()))
+                }
+
+                let snarc = Snarc::new(1);
+                let snarc_ref = snarc.new_ref();
+
+                let mut erased = snarc.into_erased();
+                assert_eq!(CONTEXT.set(&mut erased, || deep_down(&snarc_ref)), Some(1));
+
+                erased.enter(|| drop(snarc_ref));
+            }
+
+            #[test]
+            fn enter_any_hands_out_the_value() {
+                let mut erased = [
+                    Snarc::new(3u8).into_erased(),
+                    Snarc::new(String::from("any")).into_erased(),
+                ];
+
+                let described = erased
+                    .iter_mut()
+                    .map(|e| {
+                        e.enter_any(|v| match v.downcast_ref::<u8>() {
+                            Some(n) => n.to_string(),
+                            None => v.downcast_ref::<String>().unwrap().clone(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                assert_eq!(described, ["3", "any"]);
+            }
+
+            #[test]
+            fn with_current_resolves_entered_value() {
+                let snarc = Snarc::new(String::from("current"));
+
+                assert_eq!(crate::with_current(|v: &String| v.len()), None);
+                snarc.enter_with_self(|_| {
+                    assert_eq!(crate::with_current(|v: &String| v.len()), Some(7));
+                    assert_eq!(crate::with_current(|v: &u8| *v), None);
+                });
+                snarc.enter(|_| assert_eq!(crate::with_current(|v: &String| v.len()), None));
+            }
+
+            #[test]
+            fn values_that_borrow_can_be_entered() {
+                let text = String::from("borrowed");
+                let snarc = Snarc::new(text.as_str());
+                let snarc_ref = snarc.new_ref();
+
+                assert_eq!(snarc.enter(|_| snarc_ref.get().map(|v| v.len())), Some(8));
+
+                snarc.enter(|_| drop(snarc_ref));
+            }
+
+            #[test]
+            fn owners_know_whether_they_are_unique() {
+                let snarc = Snarc::new(1);
+                assert!(snarc.is_unique());
+
+                let snarc_ref = snarc.new_ref();
+                assert!(snarc.has_refs());
+
+                snarc.enter(|_| drop(snarc_ref));
+                assert!(snarc.is_unique());
+            }
+
+            #[test]
+            fn graph_builder_refs_nodes_before_they_are_defined() {
+                let mut builder = crate::graph::Builder::<Snarc<SelfReferential>>::new();
+                let a = builder.declare();
+                let b = builder.declare();
+
+                let b_ref = builder.new_ref(&b);
+                builder.define(a, SelfReferential(Some(b_ref)));
+                builder.define(b, SelfReferential(None));
+
+                let mut nodes = builder.finish();
+                let b = nodes.pop().unwrap();
+                let mut a = nodes.pop().unwrap();
+
+                let b_ref = a.0.take().unwrap();
+                b.enter(|_| {
+                    assert!(b_ref.get().unwrap().0.is_none());
+                    drop(b_ref);
+                });
+            }
+
+            #[test]
+            fn graph_builder_refs_do_not_resolve_before_nodes_are_defined() {
+                let mut builder = crate::graph::Builder::<Snarc<SelfReferential>>::new();
+                let undefined = builder.declare();
+                let undefined_ref = builder.new_ref(&undefined);
+
+                let other = Snarc::new(SelfReferential(None));
+                other.enter(|_| assert!(undefined_ref.get().is_none()));
+            }
+
+            #[test]
+            #[should_panic(expected = "declared by another Builder")]
+            fn graph_builder_rejects_nodes_of_other_builders() {
+                let mut builder = crate::graph::Builder::<Snarc<SelfReferential>>::new();
+                let mut other = crate::graph::Builder::<Snarc<SelfReferential>>::new();
+                other.declare();
+
+                let declared = builder.declare();
+                other.define(declared, SelfReferential(None));
+            }
+
+            #[test]
+            fn map_split_drops_value_with_last_part() {
+                let droppable = Droppable::new();
+                let tester = droppable.tester();
+                let snarc = Snarc::new((droppable, 1, String::new()));
+
+                let (mut count, mut text) = snarc.map_split(|(_, c, t)| (c, t));
+                *count += 1;
+                text.push_str("split");
+
+                drop(count);
+                assert!(!tester.dropped());
+                assert_eq!(*text, "split");
+
+                drop(text);
+                assert!(tester.dropped());
+            }
+
+            #[test]
+            fn map_split_parts_are_joined() {
+                let droppable = Droppable::new();
+                let tester = droppable.tester();
+                let snarc = Snarc::new((droppable, 1, String::new()));
+
+                let (mut count, mut text) = snarc.map_split(|(_, c, t)| (c, t));
+                *count += 1;
+                text.push_str("split");
+
+                let Ok(snarc) = Snarc::<(Droppable, i32, String)>::join(count, text) else {
+                    panic!("parts of the same owner are joined")
+                };
+                assert!(!tester.dropped());
+                assert_eq!((snarc.1, snarc.2.as_str()), (2, "split"));
+
+                let (a, _) = Snarc::new((1, 2)).map_split(|(a, b)| (a, b));
+                let (_, b) = Snarc::new((3, 4)).map_split(|(a, b)| (a, b));
+                assert!(Snarc::<(i32, i32)>::join(a, b).is_err());
+            }
+
+            #[test]
+            #[cfg(feature = "serde")]
+            fn serialize_value_within_enter_only() {
+                use crate::AccessOrSerError;
+                use crate::SerializeEntered;
+
+                let snarc = Snarc::new(vec![1, 2]);
+                let snarc_ref = snarc.new_ref();
+
+                let result = snarc_ref.serialize_value(&mut serde_json::Serializer::new(Vec::new()));
+                assert!(matches!(result, Err(AccessOrSerError::NotEntered)));
+                assert!(serde_json::to_string(&SerializeEntered::new(&snarc_ref)).is_err());
+
+                snarc.enter(|_| {
+                    let mut json = Vec::new();
+                    snarc_ref
+                        .serialize_value(&mut serde_json::Serializer::new(&mut json))
+                        .unwrap();
+                    assert_eq!(json, b"[1,2]");
+
+                    let json = serde_json::to_string(&SerializeEntered::new(&snarc_ref)).unwrap();
+                    assert_eq!(json, "[1,2]");
+
+                    drop(snarc_ref);
+                });
+            }
+
+            #[test]
+            fn revoked_refs_never_resolve_again() {
+                let mut snarc = Snarc::new(1);
+                let revoked = snarc.new_ref();
+
+                snarc.revoke_refs();
+                let fresh = snarc.new_ref();
+
+                snarc.enter(|_| {
+                    assert_eq!(revoked.get(), None);
+                    assert_eq!(revoked.clone().get(), None);
+                    assert_eq!(fresh.get(), Some(&1));
+                    drop(fresh);
+                });
+
+                // revoked references may be dropped anywhere
+                drop(revoked);
+            }
+
+            #[test]
+            fn enter_mut_requires_uniqueness() {
+                let mut snarc = Snarc::new(1);
+                let version = snarc.version();
+
+                assert_eq!(snarc.enter_mut(|v| std::mem::replace(v, 2)), Some(1));
+                assert!(snarc.version() > version);
+
+                let snarc_ref = snarc.new_ref();
+                assert_eq!(snarc.enter_mut(|v| *v), None);
+
+                snarc.enter(|v| {
+                    assert_eq!(*v, 2);
+                    drop(snarc_ref);
+                });
+            }
+
+            #[test]
+            fn get_mut_requires_uniqueness() {
+                let mut snarc = Snarc::new(1);
+                *Snarc::get_mut(&mut snarc).unwrap() += 1;
+
+                let snarc_ref = snarc.new_ref();
+                assert_eq!(Snarc::get_mut(&mut snarc), None);
+
+                let mut narc = snarc.into_unsend();
+                assert_eq!(Narc::get_mut(&mut narc), None);
+                narc.into_send().enter(|_| {
+                    assert_eq!(snarc_ref.get(), Some(&2));
+                    drop(snarc_ref);
+                });
+            }
+
+            #[test]
+            fn new_cyclic_hands_out_a_reference_to_itself() {
+                let snarc = Snarc::new_cyclic(|weak: &SnarcRef<SelfReferential>| {
+                    assert!(weak.get().is_none());
+                    SelfReferential(Some(weak.clone()))
+                });
+
+                snarc.enter(|value| {
+                    let weak = value.0.as_ref().unwrap();
+                    assert!(weak.get().unwrap().0.is_some());
+                });
+            }
+
+            #[test]
+            fn try_unwrap_requires_uniqueness() {
+                let snarc = Snarc::new(String::from("inner"));
+                let snarc_ref = snarc.new_ref();
+
+                snarc.enter(|_| drop(snarc_ref.clone()));
+                let Err(snarc) = Snarc::try_unwrap(snarc) else {
+                    panic!("unwrapped despite references")
+                };
+
+                snarc.enter(|_| drop(snarc_ref));
+                assert_eq!(Snarc::try_unwrap(snarc).ok().unwrap(), "inner");
+                assert_eq!(Narc::into_inner(Narc::new(1)), Some(1));
+            }
+
+            #[test]
+            fn leak_revokes_references() {
+                let snarc = Snarc::new(String::from("leaked"));
+                let snarc_ref = snarc.new_ref();
+
+                let leaked: &'static String = Snarc::leak(snarc);
+                assert_eq!(leaked, "leaked");
+                assert_eq!(Narc::leak(Narc::new(1)), &1);
+
+                drop(snarc_ref);
+            }
+
+            #[test]
+            fn replace_take_and_swap_preserve_references() {
+                let mut a = Snarc::new(vec![1]);
+                let mut b = Snarc::new(vec![2]);
+                let a_ref = a.new_ref();
+
+                assert_eq!(Snarc::replace(&mut a, vec![3]), [1]);
+                Snarc::swap(&mut a, &mut b);
+                assert_eq!(Snarc::take(&mut b), [3]);
+
+                a.enter(|_| {
+                    assert_eq!(a_ref.get(), Some(&vec![2]));
+                    drop(a_ref);
+                });
+                assert!(b.is_empty());
+            }
+
+            #[test]
+            fn map_transforms_inner_value() {
+                let snarc = Snarc::map(Snarc::new(21u64), |v| v as i64 * 2);
+                let snarc = Snarc::map(snarc, |v| v.to_string());
+                assert_eq!(*snarc, "42");
+
+                let narc = Narc::map(Narc::new(1u8), |v| [v; 3]);
+                assert_eq!(*narc, [1, 1, 1]);
+            }
+
+            #[test]
+            #[cfg(feature = "zeroize")]
+            fn moved_out_values_are_scrubbed() {
+                use crate::tests::scrubbed::leftovers;
+                use crate::tests::scrubbed::SECRET;
+
+                let before = leftovers();
+
+                let value = Snarc::try_unwrap(Snarc::new([SECRET; 4])).ok().unwrap();
+                assert_eq!(value, [SECRET; 4]);
+                assert_eq!(Snarc::into_inner(Snarc::new([SECRET; 4])), Some([SECRET; 4]));
+
+                // a differing layout moves the value to a new allocation
+                let snarc = Snarc::map(Snarc::new([SECRET; 4]), |v| v[0] as u8);
+                assert_eq!(*snarc, SECRET as u8);
+
+                // the slot is scrubbed before `f` runs, should it panic
+                let result = std::panic::catch_unwind(|| {
+                    Snarc::map(Snarc::new([SECRET; 4]), |_| -> [u64; 4] { panic!() })
+                });
+                assert!(result.is_err());
+
+                assert_eq!(leftovers(), before);
+            }
+
+            #[test]
+            fn map_drops_deferred_tasks() {
+                let ran = Arc::new(AtomicBool::new(false));
+
+                let snarc = Snarc::new(vec![1u64]);
+                let snarc_ref = snarc.new_ref();
+                snarc.enter(|_| {
+                    let ran = Arc::clone(&ran);
+                    snarc_ref.defer(move |v: &Vec<u64>| ran.store(v.len() == 1, Ordering::Relaxed));
+                    drop(snarc_ref);
+                });
+
+                // `String` shares the layout of `Vec<u64>`, so the allocation is reused
+                let snarc = Snarc::map(snarc, |v| v.iter().map(u64::to_string).collect::<String>());
+                assert_eq!(snarc.enter(|v| v.clone()), "1");
+                assert!(!ran.load(Ordering::Relaxed));
+            }
+
+            #[test]
+            fn zip_enters_both_values_at_once() {
+                let Ok(zipped) = Snarc::zip(Snarc::new(1), Snarc::new("one")) else {
+                    panic!("unique owners are zipped")
+                };
+                let zipped_ref = zipped.new_ref();
+
+                zipped.enter(|_| {
+                    assert_eq!(zipped_ref.get(), Some(&(1, "one")));
+                    drop(zipped_ref);
+                });
+            }
+
+            #[test]
+            fn zip_returns_both_owners_while_references_remain() {
+                let (a, b) = (Snarc::new(1), Snarc::new("one"));
+                let b_ref = b.new_ref();
+
+                let Err((a, b)) = Snarc::zip(a, b) else {
+                    panic!("owners with references are not zipped")
+                };
+
+                assert!(a.is_unique());
+                b.enter(|_| {
+                    assert_eq!(b_ref.get(), Some(&"one"));
+                    drop(b_ref);
+                });
+            }
+
+            #[test]
+            fn enter_is_reentrant() {
+                let snarc = Snarc::new(1);
+                let snarc_ref = snarc.new_ref();
+
+                snarc.enter(|_| {
+                    assert_eq!(snarc.enter(|_| snarc_ref.get().copied()), Some(1));
+                    assert_eq!(snarc_ref.get(), Some(&1));
+                    drop(snarc_ref);
+                });
+            }
+
+            #[test]
+            fn owners_may_be_dropped_within_enter() {
+                let outer = Snarc::new(vec![Snarc::new(1)]);
+                outer.enter(|_| drop(Snarc::new(2)));
+                drop(outer);
+            }
+
+            #[test]
+            fn enter_many_binds_all_values() {
+                use crate::EnterMany;
+
+                let a = Snarc::new(1);
+                let b = Snarc::new("two");
+                let c = Snarc::new(3.0);
+                let a_ref = a.new_ref();
+
+                let sum = (&a, &b, &c).enter(|a, b, c| {
+                    assert_eq!(*b, "two");
+                    *a_ref.get().unwrap() as f64 + *a as f64 + *c
+                });
+                assert_eq!(sum, 5.0);
+
+                let owners = [Snarc::new(1), Snarc::new(2), Snarc::new(3)];
+                let refs = owners.iter().map(|o| o.new_ref()).collect::<Vec<_>>();
+
+                crate::enter_all(&owners, |values| {
+                    assert_eq!(values, [&1, &2, &3]);
+                    assert!(refs.iter().all(|r| r.get().is_some()));
+                    drop(refs);
+                });
+
+                a.enter(|_| drop(a_ref));
+            }
+
+            #[test]
+            fn close_drops_value_while_refs_remain() {
+                let mut snarc = Snarc::new(Droppable::new());
+                let tester = snarc.tester();
+                let snarc_ref = snarc.new_ref();
+
+                Snarc::close(&mut snarc);
+                assert!(tester.dropped());
+                assert!(Snarc::is_closed(&snarc));
+
+                let other = Snarc::new(Droppable::new());
+                other.enter(|_| assert!(snarc_ref.get().is_none()));
+                drop(snarc_ref);
+
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| snarc.new_ref()));
+                assert!(result.is_err());
+            }
+
+            #[test]
+            fn is_entered_only_within_enter() {
+                let snarc = Snarc::new(1);
+                assert!(!snarc.is_entered());
+
+                snarc.enter(|_| assert!(snarc.is_entered()));
+                assert!(!snarc.is_entered());
+
+                let snarc_ref = snarc.new_ref();
+                assert!(!snarc_ref.is_entered());
+                snarc.enter(|_| {
+                    assert!(snarc_ref.is_entered());
+                    drop(snarc_ref);
+                });
+
+                let snarc = Arc::new(snarc);
+                let other = Arc::clone(&snarc);
+                snarc.enter(|_| {
+                    let on_other_thread = std::thread::spawn(move || other.is_entered());
+                    assert!(!on_other_thread.join().unwrap());
+                });
+            }
+
+            #[test]
+            fn dormant_owners_keep_refs_alive() {
+                static_assertions::assert_impl_all!(crate::Dormant<Snarc<RefCell<()>>>: Sync);
+
+                let snarc = Snarc::new(String::from("dormant"));
+                let snarc_ref = snarc.new_ref();
+
+                let dormant = snarc.into_dormant();
+                let dormant = std::thread::spawn(move || dormant).join().unwrap();
+
+                let snarc = dormant.activate();
+                snarc.enter(|_| {
+                    assert_eq!(snarc_ref.get().unwrap(), "dormant");
+                    drop(snarc_ref);
+                });
+            }
+
+            #[test]
+            fn emplace_writes_value_in_place() {
+                let snarc = unsafe {
+                    Snarc::<[u64; 1024]>::emplace(|slot| {
+                        slot.write([7; 1024]);
+                    })
+                };
+                assert!(snarc.iter().all(|v| *v == 7));
+
+                let result = std::panic::catch_unwind(|| unsafe {
+                    Snarc::<u64>::emplace(|_| panic!("not initialized"))
+                });
+                assert!(result.is_err());
+            }
+
+            #[test]
+            fn try_emplace_frees_the_allocation_on_error() {
+                let snarc = unsafe {
+                    Snarc::<[u64; 1024]>::try_emplace(|slot| {
+                        slot.write([7; 1024]);
+                        Ok::<_, ()>(())
+                    })
+                };
+                assert!(snarc.unwrap().iter().all(|v| *v == 7));
+
+                let result = unsafe { Snarc::<Droppable>::try_emplace(|_| Err("failed")) };
+                assert!(matches!(result, Err("failed")));
+            }
+
+            #[test]
+            fn try_new_allocates() {
+                let snarc = Snarc::try_new(String::from("fallible")).unwrap();
+                let snarc_ref = snarc.new_ref();
+                snarc.enter(|_| {
+                    assert_eq!(snarc_ref.get().unwrap(), "fallible");
+                    drop(snarc_ref);
+                });
+
+                assert_eq!(*Narc::try_new(()).unwrap(), ());
+            }
+
+            #[test]
+            fn uninit_snarcs_are_initialized_in_place() {
+                let mut snarc = Snarc::<[u8; 16]>::new_uninit();
+                snarc.write([3; 16]);
+                let snarc = unsafe { snarc.assume_init() };
+                assert_eq!(*snarc, [3; 16]);
+
+                let snarc = unsafe { Snarc::<u64>::new_zeroed().assume_init() };
+                assert_eq!(*snarc, 0);
+            }
+
+            #[test]
+            fn pinned_refs_get_pinned_values() {
+                let pinned = Snarc::pin(std::marker::PhantomPinned);
+                let pinned_ref = Snarc::new_pinned_ref(&pinned);
+
+                assert!(pinned_ref.get_pinned().is_none());
+                Snarc::pinned_owner(&pinned).enter(|value| {
+                    let pinned_value = pinned_ref.get_pinned().unwrap();
+                    assert!(std::ptr::eq(&*pinned_value, value));
+                    drop(pinned_ref);
+                });
+            }
+
+            #[test]
+            fn with_evaluates_closure_within_enter_only() {
+                let snarc = Snarc::new(String::from("value"));
+                let snarc_ref = snarc.new_ref();
+
+                assert_eq!(snarc_ref.with(|v| v.len()), None);
+                snarc.enter(|_| {
+                    assert_eq!(snarc_ref.with(|v| v.len()), Some(5));
+                    drop(snarc_ref);
+                });
+            }
+
+            #[test]
+            fn combinators_compose_gets() {
+                let snarc = Snarc::new((String::from("value"), Some(2)));
+                let snarc_ref = snarc.new_ref();
+                let fallback = (String::from("fallback"), None);
+
+                assert_eq!(snarc_ref.map_get(|v| v.0.as_str()), None);
+                assert_eq!(snarc_ref.and_then_get(|v| v.1), None);
+                assert_eq!(snarc_ref.get_or_else(|| &fallback).0, "fallback");
+
+                snarc.enter(|_| {
+                    assert_eq!(snarc_ref.map_get(|v| v.0.as_str()), Some("value"));
+                    assert_eq!(snarc_ref.and_then_get(|v| v.1), Some(2));
+                    assert_eq!(snarc_ref.get_or_else(|| &fallback).0, "value");
+                    drop(snarc_ref);
+                });
+            }
+
+            #[test]
+            fn refs_compare_by_pointer() {
+                let snarc = Snarc::new(1);
+                let other = Snarc::new(1);
+
+                snarc.enter(|_| {
+                    let snarc_ref = snarc.new_ref();
+                    let other_ref = other.new_ref();
+
+                    assert!(Snarc::ptr_eq(&snarc, &snarc));
+                    assert!(!Snarc::ptr_eq(&snarc, &other));
+                    assert!(snarc_ref.ptr_eq(&snarc_ref.clone()));
+                    assert!(!snarc_ref.ptr_eq(&other_ref));
+                    assert!(snarc_ref.same_owner(&snarc));
+                    assert!(!snarc_ref.same_owner(&other));
+
+                    other.enter(|_| drop(other_ref));
+                });
+            }
+
+            #[test]
+            fn refs_key_hash_sets_by_pointer() {
+                let snarc = Snarc::new(1);
+                let other = Snarc::new(1);
+
+                snarc.enter(|_| {
+                    other.enter(|_| {
+                        let mut refs = std::collections::HashSet::new();
+                        assert!(refs.insert(snarc.new_ref()));
+                        assert!(!refs.insert(snarc.new_ref()));
+                        assert!(refs.insert(other.new_ref()));
+                        assert_eq!(refs.len(), 2);
+                    });
+                });
+            }
+
+            #[test]
+            fn ref_count_counts_weak_references() {
+                let snarc = Snarc::new(1);
+                assert_eq!(snarc.ref_count(), 0);
+
+                let snarc_ref = snarc.new_ref();
+                assert_eq!(snarc.ref_count(), 1);
+                assert_eq!(snarc_ref.ref_count(), None);
+
+                snarc.enter(|_| {
+                    let clone = snarc_ref.clone();
+                    assert_eq!(clone.ref_count(), Some(2));
+                    drop(clone);
+                    drop(snarc_ref);
+                });
+                assert_eq!(snarc.ref_count(), 0);
+            }
+
+            #[test]
+            fn refs_dangle_once_their_owner_is_dropped() {
+                let mut snarc = Snarc::new(1);
+                let snarc_ref = snarc.new_ref();
+                assert!(!snarc_ref.is_dangling());
+
+                snarc.revoke_refs();
+                assert!(!snarc_ref.is_dangling());
+
+                drop(snarc);
+                assert!(snarc_ref.is_dangling());
+            }
+
+            #[test]
+            fn erased_refs_are_checked_and_downcast() {
+                let snarc = Snarc::new(1);
+                let refs = [
+                    snarc.new_ref().into_erased(),
+                    ErasedSnarcRef::from(SnarcRef::<u8>::new()),
+                ];
+
+                assert!(!refs[0].is_entered());
+                assert!(!refs[0].is_dangling());
+                assert!(refs[1].is_dangling());
+
+                snarc.enter(|_| {
+                    assert!(refs[0].is_entered());
+                    assert!(!refs[1].is_entered());
+                });
+
+                let [erased, _] = refs;
+                let erased = erased.downcast_weak::<SnarcRef<u8>>().err().unwrap();
+                let snarc_ref = erased.downcast_weak::<SnarcRef<i32>>().ok().unwrap();
+
+                snarc.enter(|_| {
+                    assert_eq!(snarc_ref.get(), Some(&1));
+                    drop(snarc_ref);
+                });
+            }
+
+            #[test]
+            fn mapped_refs_project_to_parts() {
+                let snarc = Snarc::new((1, (String::from("two"), 3)));
+                let second = snarc.new_ref().map(|v| &v.1);
+                assert_eq!(snarc.ref_count(), 1);
+
+                assert_eq!(second.get(), None);
+                snarc.enter(|_| {
+                    let third = second.map(|v| &v.1);
+                    assert_eq!(third.get(), Some(&3));
+                    drop(third);
+                });
+            }
+
+            #[test]
+            fn mutable_refs_borrow_exclusively() {
+                let snarc = Snarc::new(String::from("value"));
+                let mut snarc_mut = snarc.new_ref_mut();
+                let snarc_ref = snarc.new_ref();
+
+                // neither the owner's references nor `get` are used meanwhile
+                assert!(unsafe { snarc_mut.get_mut() }.is_none());
+                snarc.enter(|_| {
+                    let mut value = unsafe { snarc_mut.get_mut() }.unwrap();
+                    value.push('s');
+                    assert!(snarc_ref.borrow().is_none());
+                    drop(value);
+
+                    let borrowed = snarc_ref.borrow().unwrap();
+                    assert_eq!(*borrowed, "values");
+                    assert!(unsafe { snarc_mut.get_mut() }.is_none());
+                    drop(borrowed);
+
+                    drop(snarc_mut);
+                    drop(snarc_ref);
+                });
+            }
+
+            #[test]
+            fn refs_borrow_like_ref_cells() {
+                let snarc = Snarc::new(vec![1]);
+                let snarc_ref = snarc.new_ref();
+                let other_ref = snarc.new_ref();
+
+                // neither the owner's references nor `get` are used meanwhile
+                assert!(unsafe { snarc_ref.borrow_mut() }.is_none());
+                snarc.enter(|_| {
+                    let mut borrowed = unsafe { snarc_ref.borrow_mut() }.unwrap();
+                    borrowed.push(2);
+                    assert!(other_ref.borrow().is_none());
+                    assert!(unsafe { other_ref.borrow_mut() }.is_none());
+                    drop(borrowed);
+
+                    let (a, b) = (snarc_ref.borrow().unwrap(), other_ref.borrow().unwrap());
+                    assert_eq!((&*a, &*b), (&vec![1, 2], &vec![1, 2]));
+                    assert!(unsafe { snarc_ref.borrow_mut() }.is_none());
+                    drop((a, b));
+
+                    drop(snarc_ref);
+                    drop(other_ref);
+                });
+            }
+
+            #[test]
+            #[cfg(feature = "atomic-clone")]
+            fn refs_clone_outside_of_enter() {
+                let snarc = Snarc::new(1);
+                let snarc_ref = snarc.new_ref();
+
+                let (snarc_ref, clone) = std::thread::spawn(move || {
+                    let clone = snarc_ref.clone();
+                    (snarc_ref, clone)
+                })
+                .join()
+                .unwrap();
+                assert_eq!(snarc.ref_count(), 2);
+
+                snarc.enter(|_| {
+                    assert_eq!(clone.get(), Some(&1));
+                    drop(clone);
+                    drop(snarc_ref);
+                });
+                assert!(snarc.is_unique());
+            }
+
+            #[test]
+            fn borrow_guards_the_entered_context() {
+                let snarc = Snarc::new(String::from("value"));
+                let snarc_ref = snarc.new_ref();
+
+                assert!(snarc_ref.borrow().is_none());
+                snarc.enter(|_| {
+                    let borrowed = snarc_ref.borrow().unwrap();
+                    assert_eq!(borrowed.len(), 5);
+                });
+                snarc.enter(|_| drop(snarc_ref));
+            }
+
+            #[test]
+            #[cfg_attr(not(feature = "defmt"), should_panic(expected = "outlived the enter(…) context"))]
+            #[cfg_attr(feature = "defmt", should_panic)]
+            fn borrow_guards_may_not_outlive_enter() {
+                let snarc = Snarc::new(String::from("value"));
+                let snarc_ref = snarc.new_ref();
+
+                let mut escaped = None;
+                snarc.enter(|_| escaped = snarc_ref.borrow());
+                drop(escaped);
+            }
+
+            struct SelfReferential(Option<SnarcRef<SelfReferential>>);
+
+            struct Droppable(Arc<Mutex<bool>>);
+
+            impl Droppable {
+                fn new() -> Self {
+                    Self(Default::default())
+                }
+
+                fn tester(&self) -> DropTester {
+                    DropTester(Arc::clone(&self.0))
+                }
+            }
+
+            impl Drop for Droppable {
+                fn drop(&mut self) {
+                    let mut dropped = self.0.lock().unwrap();
+                    *dropped = true;
+                }
+            }
+
+            struct DropTester(Arc<Mutex<bool>>);
+
+            impl DropTester {
+                fn dropped(&self) -> bool {
+                    let dropped = self.0.lock().unwrap();
+                    *dropped
+                }
+            }
+        }
+    }
+}
+
+pub(crate) use tests;
+
+/// A global allocator that inspects the allocations it frees for `SECRET`,
+/// given the `zeroize` feature.
+#[cfg(feature = "zeroize")]
+pub(crate) mod scrubbed {
+    use std::alloc::GlobalAlloc;
+    use std::alloc::Layout;
+    use std::alloc::System;
+    use std::cell::Cell;
+
+    pub(crate) const SECRET: u64 = 0x5ec2_e75e_c2e7_5ec2;
+
+    thread_local!(static LEFTOVERS: Cell<usize> = const { Cell::new(0) });
+
+    struct Inspecting;
+
+    unsafe impl GlobalAlloc for Inspecting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let bytes = std::slice::from_raw_parts(ptr, layout.size());
+            if bytes.windows(8).any(|w| w == SECRET.to_ne_bytes()) {
+                let _ = LEFTOVERS.try_with(|l| l.set(l.get() + 1));
+            }
+
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Inspecting = Inspecting;
+
+    /// Returns the number of allocations freed on the current thread that
+    /// still held `SECRET`.
+    pub(crate) fn leftovers() -> usize {
+        LEFTOVERS.with(Cell::get)
+    }
+}
+
+/// Runs `f` in a child process, which is expected to abort, and returns its
+/// standard error.
+///
+/// `test` must be the path of the calling test, within the crate, so that the
+/// child process runs `f` in place of spawning yet another one.
+#[cfg(feature = "debug-canary")]
+pub(crate) fn aborts(test: &str, f: impl FnOnce()) -> String {
+    const VAR: &str = "SNARC_ABORTING_TEST";
+
+    if std::env::var(VAR).as_deref() == Ok(test) {
+        f();
+        std::process::exit(0);
+    }
+
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args([test, "--exact", "--nocapture", "--test-threads=1"])
+        .env(VAR, test)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success(), "{test} did not abort");
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
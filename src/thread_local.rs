@@ -5,358 +5,2379 @@
 //! instances cannot bind their value to the current thread at the same time.
 //! The `Snarc` defined in this module uses a per-instance `ThreadLocal` to lift
 //! this restriction.
+//!
+//! Unlike those of `snarc!`, the owners defined here may hold unsized values,
+//! such as `Snarc<[u8]>` or `Snarc<dyn Trait>`, which are created by way of
+//! `from_box`. (The shared context of `snarc!` does not allocate zero-sized
+//! values, which requires their size to be known statically.)
+//!
+//! As the value comes last in its control block, the `debug-canary` feature
+//! detects corruption of the block's header only, not overruns of the value.
 use std::alloc;
+use std::any::TypeId;
+use std::future::Future;
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::ptr;
+use std::task::Poll;
 
+use crate::AsyncDispose;
+use crate::CallbackSet;
+use crate::Canary;
+use crate::ChangeListener;
 use crate::Context;
 use crate::ErasedNarc;
 use crate::ErasedSnarc;
+use crate::ErasedSnarcRef;
+use crate::FfiToken;
+use crate::Get;
+use crate::Notifier;
+use crate::RefContext;
+use crate::SnarcOrArc;
+use crate::SnarcPart;
 use crate::State;
 
-struct SnarcBox<T> {
-    count: std::cell::Cell<usize>,
-    thread_local: thread_local::ThreadLocal<std::cell::Cell<State>>,
+/// The value comes last, so that it may be unsized. The tail canary thus
+/// precedes it, see `Canary`.
+#[repr(C)]
+struct SnarcBox<T: ?Sized> {
+    head: Canary,
+    count: std::cell::Cell<crate::Count>,
+    balance: crate::Balance,
+    generation: std::sync::atomic::AtomicU32,
+    dropped: std::sync::atomic::AtomicBool,
+    guarded: std::cell::Cell<bool>,
+    borrows: crate::BorrowFlag,
+    snapshots: crate::SnapshotLock,
+    holder: crate::Holder,
+    thread_local: std::sync::OnceLock<Box<thread_local::ThreadLocal<std::cell::Cell<State>>>>,
+    notifier: Notifier,
+    deferred: crate::DeferQueue,
+    mailbox: crate::Mailbox,
+    tail: Canary,
     value: T,
 }
 
 impl<T> SnarcBox<T> {
     fn new_ptr(value: T) -> *mut Self {
         Box::leak(Box::new(Self {
+            head: Canary::head(),
             count: std::cell::Cell::new(0),
-            thread_local: thread_local::ThreadLocal::new(),
+            balance: Default::default(),
+            generation: std::sync::atomic::AtomicU32::new(0),
+            dropped: std::sync::atomic::AtomicBool::new(false),
+            guarded: std::cell::Cell::new(false),
+            borrows: Default::default(),
+            snapshots: Default::default(),
+            holder: Default::default(),
+            thread_local: std::sync::OnceLock::new(),
+            notifier: Default::default(),
+            deferred: Default::default(),
+            mailbox: Default::default(),
+            tail: Canary::tail(),
             value,
         }))
     }
 
-    #[inline]
-    #[allow(clippy::mut_from_ref)]
-    unsafe fn into_mut_unchecked(ptr: &*mut SnarcBox<T>) -> &mut T {
-        &mut (**ptr).value
+    /// Allocates a control block whose value is written later, by `init`.
+    fn new_uninit_ptr() -> *mut Self {
+        Self::try_new_uninit_ptr()
+            .unwrap_or_else(|_| alloc::handle_alloc_error(alloc::Layout::new::<Self>()))
     }
-}
 
-/// A sendable, owning reference-counted pointer to a `T`.
-pub struct Snarc<T> {
-    ptr: *mut SnarcBox<T>,
-    phantom: std::marker::PhantomData<SnarcBox<T>>,
+    /// Like `new_uninit_ptr`, but fails instead of aborting if memory cannot be
+    /// allocated.
+    fn try_new_uninit_ptr() -> Result<*mut Self, crate::AllocError> {
+        let this = unsafe { alloc::alloc(alloc::Layout::new::<Self>()) }.cast::<Self>();
+        if this.is_null() {
+            return Err(crate::AllocError);
+        }
+
+        unsafe { Self::init_header(this) };
+
+        Ok(this)
+    }
 }
 
-unsafe impl<T: Send> Send for Snarc<T> {}
-unsafe impl<T: Sync> Sync for Snarc<T> {}
+impl<T: ?Sized> SnarcBox<T> {
+    /// Moves `value` into a new control block.
+    ///
+    /// The box's allocation is grown in place of allocating anew, if the
+    /// alignments of the two agree, see `reuses`.
+    fn from_box_ptr(value: Box<T>) -> *mut Self {
+        let value_layout = alloc::Layout::for_value(&*value);
+        let (layout, offset) = Self::layout(value_layout);
 
-impl<T> Snarc<T> {
-    /// Creates a new `Snarc` with the given inner `value`.
-    pub fn new(value: T) -> Self {
-        Self {
-            ptr: SnarcBox::new_ptr(value),
-            phantom: std::marker::PhantomData,
+        let raw = Box::into_raw(value);
+
+        let mem = unsafe {
+            if Self::reuses(value_layout, layout) {
+                let mem = alloc::realloc(raw.cast(), value_layout, layout.size());
+                if mem.is_null() {
+                    alloc::handle_alloc_error(layout)
+                }
+                ptr::copy(mem, mem.add(offset), value_layout.size());
+                mem
+            } else {
+                let mem = alloc::alloc(layout);
+                if mem.is_null() {
+                    alloc::handle_alloc_error(layout)
+                }
+                ptr::copy_nonoverlapping(raw.cast::<u8>(), mem.add(offset), value_layout.size());
+                crate::scrub_bytes(raw.cast(), value_layout.size());
+                if value_layout.size() != 0 {
+                    alloc::dealloc(raw.cast(), value_layout);
+                }
+                mem
+            }
+        };
+
+        let this = with_addr(raw as *mut Self, mem);
+
+        unsafe {
+            Self::init_header(this);
+            debug_assert_eq!(
+                ptr::addr_of_mut!((*this).value)
+                    .cast::<u8>()
+                    .offset_from(mem),
+                offset as isize
+            );
         }
+
+        this
     }
 
-    /// Turn this `Snarc` into the `!Send` version `Narc`.
-    pub fn into_unsend(mut self) -> Narc<T> {
-        let narc = Narc {
-            ptr: self.ptr,
-            phantom: self.phantom,
+    /// Moves the value out of the control block, which is freed.
+    ///
+    /// The allocation is shrunk to become the box's, if the alignments of the
+    /// two agree, see `reuses`.
+    ///
+    /// # Safety
+    ///
+    /// There must not be any references to the control block.
+    unsafe fn into_box(this: *mut Self) -> Box<T> {
+        let value = ptr::addr_of_mut!((*this).value);
+        let value_layout = alloc::Layout::for_value(&*value);
+        let (layout, offset) = Self::layout(value_layout);
+
+        Self::drop_header(this);
+
+        let mem = this.cast::<u8>();
+        let size = value_layout.size();
+        let raw = if Self::reuses(value_layout, layout) {
+            ptr::copy(mem.add(offset), mem, size);
+            let raw = alloc::realloc(mem, layout, size);
+            if raw.is_null() {
+                alloc::handle_alloc_error(value_layout)
+            }
+            raw
+        } else {
+            let raw = if value_layout.size() == 0 {
+                // boxes of zero-sized values are dangling, but aligned
+                ptr::without_provenance_mut(value_layout.align())
+            } else {
+                let raw = alloc::alloc(value_layout);
+                if raw.is_null() {
+                    alloc::handle_alloc_error(value_layout)
+                }
+                raw
+            };
+            ptr::copy_nonoverlapping(mem.add(offset), raw, size);
+            crate::scrub_bytes(mem.add(offset), size);
+            alloc::dealloc(mem, layout);
+            raw
         };
 
-        self.ptr = ptr::null_mut();
+        Box::from_raw(with_addr(value, raw))
+    }
 
-        narc
+    /// Returns the layout of a control block for a value of the given layout,
+    /// along with the offset of the value.
+    fn layout(value_layout: alloc::Layout) -> (alloc::Layout, usize) {
+        // replicates the `repr(C)` layout, the header being the fields that
+        // precede the value
+        let header = alloc::Layout::from_size_align(
+            std::mem::offset_of!(SnarcBox<()>, value),
+            std::mem::align_of::<SnarcBox<()>>(),
+        )
+        .unwrap();
+        let (layout, offset) = header.extend(value_layout).unwrap();
+
+        (layout.pad_to_align(), offset)
     }
 
-    /// Turn this parameterized `Snarc` the unparameterized `ErasedSnarc`.
-    pub fn into_erased(self) -> ErasedSnarc
-    where
-        T: Send + 'static,
-    {
-        ErasedSnarc {
-            inner: Box::new(self),
-        }
+    /// Returns `true` if allocations for a value and its control block may be
+    /// converted into one another by `realloc`.
+    ///
+    /// Never given the `zeroize` feature, since `realloc` may move the value
+    /// and free its old allocation without scrubbing it.
+    fn reuses(value_layout: alloc::Layout, layout: alloc::Layout) -> bool {
+        !cfg!(feature = "zeroize")
+            && value_layout.size() != 0
+            && value_layout.align() == layout.align()
+    }
+
+    /// # Safety
+    ///
+    /// `this` must point to an allocation for a `SnarcBox<T>`.
+    unsafe fn init_header(this: *mut Self) {
+        ptr::addr_of_mut!((*this).head).write(Canary::head());
+        ptr::addr_of_mut!((*this).count).write(std::cell::Cell::new(0));
+        ptr::addr_of_mut!((*this).balance).write(Default::default());
+        ptr::addr_of_mut!((*this).generation).write(std::sync::atomic::AtomicU32::new(0));
+        ptr::addr_of_mut!((*this).dropped).write(std::sync::atomic::AtomicBool::new(false));
+        ptr::addr_of_mut!((*this).guarded).write(std::cell::Cell::new(false));
+        ptr::addr_of_mut!((*this).borrows).write(Default::default());
+        ptr::addr_of_mut!((*this).snapshots).write(Default::default());
+        ptr::addr_of_mut!((*this).holder).write(Default::default());
+        ptr::addr_of_mut!((*this).thread_local).write(std::sync::OnceLock::new());
+        ptr::addr_of_mut!((*this).notifier).write(Default::default());
+        ptr::addr_of_mut!((*this).deferred).write(Default::default());
+        ptr::addr_of_mut!((*this).mailbox).write(Default::default());
+        ptr::addr_of_mut!((*this).tail).write(Canary::tail());
     }
 
     #[inline(always)]
-    fn inner(&self) -> &SnarcBox<T> {
-        unsafe { &*self.ptr }
+    fn check(&self) {
+        Canary::check(&self.head, &self.tail)
     }
 
-    /// Creates a new non-owning reference to the inner value.
-    pub fn new_ref(&self) -> SnarcRef<T> {
-        let inner = self.inner();
+    #[inline(always)]
+    fn generation(&self) -> u32 {
+        self.generation.load(std::sync::atomic::Ordering::Relaxed)
+    }
 
-        inner.count.set(inner.count.get() + 1);
+    fn set_generation(&self, generation: u32) {
+        self.generation
+            .store(generation, std::sync::atomic::Ordering::Relaxed);
+    }
 
-        SnarcRef {
-            ptr: self.ptr,
-            phantom: Default::default(),
+    /// Returns the number of weak references, including those cloned or
+    /// dropped outside of `enter` that were not reconciled yet.
+    fn refs(&self) -> usize {
+        self.balance.refs(self.count.get())
+    }
+
+    /// Counts one weak reference less, must be called within `enter`.
+    fn decrement(&self) {
+        self.balance.reconcile(&self.count);
+        self.count.set(self.count.get() - 1);
+    }
+
+    /// Frees the allocation, but not the value, unless references remain. In
+    /// that case, the last reference to be dropped frees it.
+    fn release(this: *mut Self) {
+        if unsafe { (*this).balance.orphan((*this).count.get()) } {
+            unsafe { Self::free(this) }
         }
     }
 
-    /// Temporarily bind the inner value to this thread and evaluate `f` within
-    /// that context.
-    pub fn enter<F, R>(&mut self, f: F) -> R
-    where
-        F: FnOnce(&T) -> R,
-    {
-        let inner = self.inner();
-        inner.thread_local.get_or_default().set(State::Entered);
+    /// Frees the allocation, but not the value.
+    ///
+    /// # Safety
+    ///
+    /// The control block must not be used afterwards.
+    unsafe fn free(this: *mut Self) {
+        let layout = alloc::Layout::for_value(&*this);
+        Self::drop_header(this);
+        alloc::dealloc(this.cast(), layout);
+    }
 
-        let _guard = scopeguard::guard((), |_| {
-            inner.thread_local.get_or_default().set(State::Default);
-        });
+    /// Drops all fields but the value.
+    ///
+    /// # Safety
+    ///
+    /// The header must not be used afterwards.
+    unsafe fn drop_header(this: *mut Self) {
+        ptr::addr_of_mut!((*this).head).drop_in_place();
+        ptr::addr_of_mut!((*this).count).drop_in_place();
+        ptr::addr_of_mut!((*this).borrows).drop_in_place();
+        ptr::addr_of_mut!((*this).thread_local).drop_in_place();
+        ptr::addr_of_mut!((*this).notifier).drop_in_place();
+        ptr::addr_of_mut!((*this).deferred).drop_in_place();
+        ptr::addr_of_mut!((*this).mailbox).drop_in_place();
+        ptr::addr_of_mut!((*this).tail).drop_in_place();
+    }
 
-        f(&inner.value)
+    /// Undoes a `SnarcRef::borrow` or `get_mut`.
+    fn unborrow(this: *mut ()) {
+        // the header precedes the value, so its layout does not depend on `T`
+        unsafe { (*this.cast::<SnarcBox<()>>()).borrows.release() }
     }
-}
 
-impl<T: Send + 'static> From<Snarc<T>> for ErasedSnarc {
-    fn from(snarc: Snarc<T>) -> Self {
-        snarc.into_erased()
+    /// Records that the owner is gone, see `SnarcRef::is_dangling`.
+    fn mark_dropped(&self) {
+        self.dropped
+            .store(true, std::sync::atomic::Ordering::Release);
     }
-}
 
-impl<T: Send + 'static> From<Snarc<T>> for ErasedNarc {
-    fn from(snarc: Snarc<T>) -> Self {
-        snarc.into_unsend().into_erased()
+    fn revoke(&self) {
+        self.generation
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
     }
-}
 
-impl<T> Context for Snarc<T> {
-    fn set(&mut self, v: State) {
-        self.inner().thread_local.get_or_default().set(v)
+    /// Returns the state bound to the current thread.
+    ///
+    /// The `ThreadLocal` is only allocated once a state is set, so that values
+    /// that are never entered cost a single allocation.
+    #[inline]
+    fn state(&self) -> State {
+        self.thread_local
+            .get()
+            .and_then(|thread_local| thread_local.get())
+            .map_or(State::Default, |state| state.get())
     }
-}
 
-impl<T> Deref for Snarc<T> {
-    type Target = T;
+    /// Claims the value for the current thread, as only one thread at a time
+    /// may clone and drop references.
+    ///
+    /// Returns `false` if the current thread holds the value already, panics
+    /// if another thread does.
+    fn claim(&self) -> bool {
+        match self.holder.claim() {
+            Some(claimed) => claimed,
+            None => crate::__panic!("Snarc is already entered on another thread."),
+        }
+    }
+
+    /// Undoes a `claim` that returned `true`.
+    fn unclaim(&self) {
+        self.holder.release();
+    }
+
+    /// Runs the tasks queued by `SnarcRef::defer`, must be called within
+    /// `enter`.
+    #[inline]
+    fn drain_deferred(&self) {
+        // the tasks are cleared whenever the value is mapped to another type
+        unsafe { self.deferred.drain(&self.value) }
+    }
+
+    #[inline]
+    fn set_state(&self, v: State) {
+        match v {
+            State::Default => {
+                crate::check_borrows(self.borrows.count());
+                self.snapshots.unlock();
+            }
+            State::Entered => {
+                self.snapshots.lock();
+                self.balance.reconcile(&self.count);
+            }
+            State::Unsend => self.snapshots.lock(),
+        }
+
+        match self.thread_local.get() {
+            Some(thread_local) => thread_local.get_or_default().set(v),
+            None if v == State::Default => {}
+            None => self
+                .thread_local
+                .get_or_init(Default::default)
+                .get_or_default()
+                .set(v),
+        }
+    }
 
     #[inline(always)]
-    fn deref(&self) -> &Self::Target {
-        &self.inner().value
+    fn value(this: *mut Self) -> *mut T {
+        Self::check_open(this);
+        unsafe { ptr::addr_of_mut!((*this).value) }
     }
-}
 
-impl<T> DerefMut for Snarc<T> {
+    /// Panics if `this` belongs to an owner that was closed, see
+    /// [`Snarc::close`].
     #[inline(always)]
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { SnarcBox::into_mut_unchecked(&self.ptr) }
+    fn check_open(this: *const Self) {
+        if this.is_null() {
+            crate::__panic!("Owner was closed.")
+        }
     }
-}
 
-impl<T> Drop for Snarc<T> {
-    fn drop(&mut self) {
-        if !self.ptr.is_null() {
-            {
-                self.inner()
-                    .thread_local
-                    .get_or_default()
-                    .set(State::Entered);
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn into_mut_unchecked(ptr: &*mut SnarcBox<T>) -> &mut T {
+        Self::check_open(*ptr);
 
-                let _guard = scopeguard::guard((), |_| {
-                    self.inner()
-                        .thread_local
-                        .get_or_default()
-                        .set(State::Default)
-                });
+        if (**ptr).guarded.get() {
+            crate::__panic!("Snarc accessed mutably after its EnterGuard was leaked.")
+        }
 
-                unsafe {
-                    // destroy the contained object
-                    ptr::drop_in_place(SnarcBox::into_mut_unchecked(&self.ptr));
-                }
-            }
+        (**ptr).snapshots.lock();
 
-            if self.inner().count.get() == 0 {
-                unsafe {
-                    ptr::addr_of_mut!((*self.ptr).count).drop_in_place();
-                    let layout = alloc::Layout::for_value(&*self.ptr);
-                    alloc::dealloc(self.ptr.cast(), layout);
-                }
-            }
-        }
+        (**ptr).notifier.mark_dirty();
+        &mut (**ptr).value
     }
 }
 
-/// A non-sendable, owning reference-counted pointer to a `T`.
-///
-/// When `Narc` is used exclusively, i.e., never `Snarc`, then `Rc` should
-/// likely be used instead.
-pub struct Narc<T> {
+/// Replaces the address of `ptr`, but keeps its metadata, which cannot be
+/// conjured for unsized values otherwise.
+fn with_addr<T: ?Sized>(mut ptr: *mut T, addr: *mut u8) -> *mut T {
+    // the address is the first component of a (possibly wide) pointer
+    unsafe { ptr::write(ptr::addr_of_mut!(ptr).cast::<*mut u8>(), addr) };
+    ptr
+}
+
+/// A sendable, owning reference-counted pointer to a `T`.
+pub struct Snarc<T: ?Sized> {
     ptr: *mut SnarcBox<T>,
     phantom: std::marker::PhantomData<SnarcBox<T>>,
 }
 
-unsafe impl<T: Sync> Sync for Narc<T> {}
+unsafe impl<T: ?Sized + Send> Send for Snarc<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for Snarc<T> {}
 
-impl<T> Narc<T> {
-    /// Creates a new `Narc` with the given inner `value`.
-    pub fn new(value: T) -> Self {
+impl<T: ?Sized> Snarc<T> {
+    /// Creates a new `Snarc` with the given inner `value`.
+    pub fn new(value: T) -> Self
+    where
+        T: Sized,
+    {
         Self {
             ptr: SnarcBox::new_ptr(value),
             phantom: std::marker::PhantomData,
         }
     }
 
-    /// Turn this `Narc` into the `Send` version `Snarc`.
-    pub fn into_send(mut self) -> Snarc<T> {
-        let snarc = Snarc {
-            ptr: self.ptr,
-            phantom: self.phantom,
-        };
+    /// Creates a new `Snarc` with the given inner `value`, failing instead of
+    /// aborting if memory cannot be allocated.
+    pub fn try_new(value: T) -> Result<Self, crate::AllocError>
+    where
+        T: Sized,
+    {
+        let ptr = SnarcBox::<T>::try_new_uninit_ptr()?;
+        unsafe { ptr::addr_of_mut!((*ptr).value).write(value) };
 
-        self.ptr = ptr::null_mut();
+        Ok(Self {
+            ptr,
+            phantom: std::marker::PhantomData,
+        })
+    }
 
-        snarc
+    /// Creates a new `Snarc` with the boxed, possibly unsized, `value`.
+    ///
+    /// The box's allocation is reused where its alignment permits, otherwise
+    /// the value is moved to a new one.
+    ///
+    /// ```
+    /// use snarc::thread_local::Snarc;
+    ///
+    /// let slice: Snarc<[u8]> = Snarc::from_box(Box::new([1, 2, 3]));
+    /// assert_eq!(&*slice, &[1, 2, 3]);
+    /// ```
+    pub fn from_box(value: Box<T>) -> Self {
+        Self {
+            ptr: SnarcBox::from_box_ptr(value),
+            phantom: std::marker::PhantomData,
+        }
     }
 
-    /// Turn this parameterized `Narc` the unparameterized `ErasedNarc`.
-    pub fn into_erased(self) -> ErasedNarc
+    /// Creates a new `Snarc` with the inner value returned by `f`, or fails
+    /// with its error.
+    ///
+    /// The value is moved into the allocation, see `try_emplace` to construct
+    /// it in place.
+    pub fn try_new_with<E, F>(f: F) -> Result<Self, E>
     where
-        T: Send + 'static,
+        T: Sized,
+        F: FnOnce() -> Result<T, E>,
     {
-        self.into_send().into_erased().into_unsend()
-    }
-
-    #[inline(always)]
-    fn inner(&self) -> &SnarcBox<T> {
-        unsafe { &*self.ptr }
+        f().map(Self::new)
     }
 
-    /// Creates a new non-owning reference to the inner value.
-    pub fn new_ref(&self) -> SnarcRef<T> {
-        let inner = self.inner();
+    /// Creates a new `Snarc` whose inner value is written in place by `f`, so
+    /// that large values need not be moved.
+    ///
+    /// The allocation is freed again should `f` panic.
+    ///
+    /// # Safety
+    ///
+    /// `f` must initialize the value it is handed.
+    pub unsafe fn emplace<F>(f: F) -> Self
+    where
+        T: Sized,
+        F: FnOnce(&mut std::mem::MaybeUninit<T>),
+    {
+        let ptr = SnarcBox::<T>::new_uninit_ptr();
 
-        inner.count.set(inner.count.get() + 1);
+        let release = scopeguard::guard_on_unwind((), |_| SnarcBox::release(ptr));
+        f(&mut *SnarcBox::value(ptr).cast::<std::mem::MaybeUninit<T>>());
+        scopeguard::ScopeGuard::into_inner(release);
 
-        SnarcRef {
-            ptr: self.ptr,
-            phantom: Default::default(),
+        Self {
+            ptr,
+            phantom: std::marker::PhantomData,
         }
     }
-}
 
-impl<T: Send + 'static> From<Narc<T>> for ErasedSnarc {
-    fn from(narc: Narc<T>) -> Self {
-        narc.into_send().into_erased()
-    }
-}
+    /// Like `emplace`, but `f` may fail, in which case the allocation is freed
+    /// and the error returned.
+    ///
+    /// # Safety
+    ///
+    /// `f` must initialize the value it is handed, unless it fails.
+    pub unsafe fn try_emplace<E, F>(f: F) -> Result<Self, E>
+    where
+        T: Sized,
+        F: FnOnce(&mut std::mem::MaybeUninit<T>) -> Result<(), E>,
+    {
+        let ptr = SnarcBox::<T>::new_uninit_ptr();
 
-impl<T: Send + 'static> From<Narc<T>> for ErasedNarc {
-    fn from(narc: Narc<T>) -> Self {
-        narc.into_erased()
+        let release = scopeguard::guard((), |_| SnarcBox::release(ptr));
+        f(&mut *SnarcBox::value(ptr).cast::<std::mem::MaybeUninit<T>>())?;
+        scopeguard::ScopeGuard::into_inner(release);
+
+        Ok(Self {
+            ptr,
+            phantom: std::marker::PhantomData,
+        })
     }
-}
 
-impl<T> Deref for Narc<T> {
-    type Target = T;
+    /// Creates a new `Snarc` whose inner value is returned by `f`, which is
+    /// handed a weak reference to it.
+    ///
+    /// `f` is evaluated within the `enter` context, so the reference may be
+    /// cloned, but it does not resolve until `f` returns.
+    pub fn new_cyclic<F>(f: F) -> Self
+    where
+        T: Sized,
+        F: FnOnce(&SnarcRef<T>) -> T,
+    {
+        let ptr = SnarcBox::<T>::new_uninit_ptr();
+        let inner = unsafe { &*ptr };
 
-    #[inline(always)]
-    fn deref(&self) -> &Self::Target {
-        &self.inner().value
-    }
-}
+        // references minted before the value is written resolve once the
+        // generations match again
+        inner.set_generation(1);
+        crate::increment(&inner.count);
 
-impl<T> DerefMut for Narc<T> {
-    #[inline(always)]
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { SnarcBox::into_mut_unchecked(&self.ptr) }
-    }
-}
+        let weak = SnarcRef {
+            ptr,
+            generation: 0,
+            phantom: Default::default(),
+        };
 
-impl<T> Drop for Narc<T> {
-    fn drop(&mut self) {
-        if !self.ptr.is_null() {
-            {
-                self.inner()
-                    .thread_local
-                    .get_or_default()
-                    .set(State::Entered);
+        inner.set_state(State::Entered);
 
-                let _guard = scopeguard::guard((), |_| {
-                    self.inner()
-                        .thread_local
-                        .get_or_default()
-                        .set(State::Default)
-                });
+        let release = scopeguard::guard_on_unwind((), |_| SnarcBox::release(ptr));
 
-                unsafe {
-                    // destroy the contained object
-                    ptr::drop_in_place(SnarcBox::into_mut_unchecked(&self.ptr));
-                }
-            }
+        let value = {
+            let weak = scopeguard::guard(weak, |weak| {
+                drop(weak);
+                inner.set_state(State::Default);
+            });
 
-            if self.inner().count.get() == 0 {
-                unsafe {
-                    ptr::addr_of_mut!((*self.ptr).count).drop_in_place();
-                    let layout = alloc::Layout::for_value(&*self.ptr);
-                    alloc::dealloc(self.ptr.cast(), layout);
-                }
-            }
-        }
-    }
-}
+            f(&weak)
+        };
 
-/// A sendable, non-owning reference-counted pointer to a `T`.
-pub struct SnarcRef<T> {
-    ptr: *mut SnarcBox<T>,
-    phantom: std::marker::PhantomData<SnarcBox<T>>,
-}
+        scopeguard::ScopeGuard::into_inner(release);
 
-unsafe impl<T> Send for SnarcRef<T> {}
-unsafe impl<T> Sync for SnarcRef<T> {}
+        unsafe { ptr::addr_of_mut!((*ptr).value).write(value) };
+        inner.set_generation(0);
 
-impl<T> SnarcRef<T> {
-    #[inline(always)]
-    fn inner(&self) -> &SnarcBox<T> {
-        unsafe { &*self.ptr }
+        Self {
+            ptr,
+            phantom: std::marker::PhantomData,
+        }
     }
 
-    /// Gets a reference to the inner value.
-    ///
-    /// Returns `None` if the corresponding owning pointer did not currently
-    /// bind the inner value to the current thread.
-    pub fn get(&self) -> Option<&T> {
-        let inner = self.inner();
+    /// Puts this `Snarc` to rest, keeping the inner value and weak references
+    /// to it alive, see [`Dormant`](crate::Dormant).
+    pub fn into_dormant(self) -> crate::Dormant<Self> {
+        crate::Dormant::new(self)
+    }
 
-        if inner.thread_local.get_or_default().get().is_set() {
+    /// Turn this `Snarc` into the `!Send` version `Narc`.
+    pub fn into_unsend(mut self) -> Narc<T> {
+        let narc = Narc {
+            ptr: self.ptr,
+            phantom: self.phantom,
+        };
+
+        self.ptr = with_addr(self.ptr, ptr::null_mut());
+
+        narc
+    }
+
+    /// Turn this parameterized `Snarc` the unparameterized `ErasedSnarc`.
+    pub fn into_erased(self) -> ErasedSnarc
+    where
+        T: Sized,
+        T: Send + 'static,
+    {
+        ErasedSnarc::from(crate::ErasedOwner::new(self))
+    }
+
+    /// Like `into_erased`, but for values that borrow for `'a`.
+    ///
+    /// The inner value of the resulting `ScopedErasedSnarc` can neither be
+    /// accessed nor downcast, even if `'a` is `'static`.
+    pub fn into_erased_scoped<'a>(self) -> crate::ScopedErasedSnarc<'a>
+    where
+        T: Sized,
+        T: Send + 'a,
+    {
+        crate::ScopedErasedSnarc::from(crate::ErasedOwner::new_scoped(self))
+    }
+
+    /// Splits the inner value into the two disjoint parts returned by `f`.
+    ///
+    /// The parts share this `Snarc`'s allocation, the inner value is dropped
+    /// once both of them are. They are turned back into a `Snarc` by `join`.
+    pub fn map_split<A, B>(self, f: fn(&mut T) -> (&mut A, &mut B)) -> (SnarcPart<A>, SnarcPart<B>)
+    where
+        T: Sized,
+        T: Send + 'static,
+    {
+        // the inner value is boxed, so it does not move along with `self`
+        unsafe { SnarcPart::split(self, f) }
+    }
+
+    /// Joins the parts returned by `map_split` back into the `Snarc` they
+    /// were split from.
+    ///
+    /// Returns the parts back if they were split from different owners.
+    #[allow(clippy::type_complexity)]
+    pub fn join<A, B>(
+        a: SnarcPart<A>,
+        b: SnarcPart<B>,
+    ) -> Result<Self, (SnarcPart<A>, SnarcPart<B>)>
+    where
+        T: Sized,
+        T: Send + 'static,
+    {
+        SnarcPart::join(a, b)
+    }
+
+    #[inline(always)]
+    fn inner(&self) -> &SnarcBox<T> {
+        SnarcBox::check_open(self.ptr);
+        unsafe { &*self.ptr }
+    }
+
+    /// Drops the inner value right away, weak references remain valid but no
+    /// longer resolve.
+    ///
+    /// This releases the resources held by the value even while weak
+    /// references remain. Afterwards, `this` may only be dropped, any other
+    /// use panics, see `is_closed`.
+    pub fn close(this: &mut Self) {
+        if !this.ptr.is_null() {
+            // closed up front, so that the value is not dropped twice should
+            // its destructor panic
+            let closed = with_addr(this.ptr, ptr::null_mut());
+            let ptr = std::mem::replace(&mut this.ptr, closed);
+            let inner = unsafe { &*ptr };
+
+            inner.check();
+
+            {
+                // without references, nothing can observe the binding
+                let bind = inner.refs() != 0;
+
+                if bind {
+                    inner.set_state(State::Entered);
+                }
+
+                let _guard = scopeguard::guard((), |_| {
+                    if bind {
+                        inner.set_state(State::Default)
+                    }
+                });
+
+                inner.snapshots.close();
+
+                unsafe {
+                    // destroy the contained object
+                    ptr::drop_in_place(SnarcBox::into_mut_unchecked(&ptr));
+                    crate::scrub(SnarcBox::value(ptr));
+                }
+
+                // the tasks and messages may hold references, which are best
+                // dropped bound
+                inner.deferred.clear();
+                inner.mailbox.clear();
+            }
+
+            inner.mark_dropped();
+            inner.revoke();
+            SnarcBox::release(ptr);
+        }
+    }
+
+    /// Returns `true` if `this` was closed, see `close`.
+    pub fn is_closed(this: &Self) -> bool {
+        this.ptr.is_null()
+    }
+
+    /// Returns `true` if `this` and `other` point to the same allocation, like
+    /// `Rc::ptr_eq`.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        ptr::addr_eq(this.ptr, other.ptr)
+    }
+
+    /// Returns a mutable reference to the inner value if there are no weak
+    /// references to it, like `Arc::get_mut`.
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        if this.is_unique() {
+            Some(unsafe { SnarcBox::into_mut_unchecked(&this.ptr) })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the inner value if there are no weak references to it,
+    /// otherwise `this` is returned unchanged.
+    pub fn try_unwrap(this: Self) -> Result<T, Self>
+    where
+        T: Sized,
+    {
+        if !this.is_unique() {
+            return Err(this);
+        }
+
+        let this = std::mem::ManuallyDrop::new(this);
+        let value = unsafe { SnarcBox::value(this.ptr).read() };
+        unsafe { crate::scrub(SnarcBox::value(this.ptr)) };
+        SnarcBox::release(this.ptr);
+
+        Ok(value)
+    }
+
+    /// Returns the inner value if there are no weak references to it,
+    /// otherwise `this` is dropped.
+    pub fn into_inner(this: Self) -> Option<T>
+    where
+        T: Sized,
+    {
+        Self::try_unwrap(this).ok()
+    }
+
+    /// Returns the boxed inner value if there are no weak references to it,
+    /// otherwise `this` is returned unchanged.
+    ///
+    /// Like `try_unwrap`, but for possibly unsized values. The allocation is
+    /// reused where its alignment permits.
+    ///
+    /// ```
+    /// use snarc::thread_local::Snarc;
+    ///
+    /// let slice: Snarc<[u8]> = Snarc::from_box(Box::new([1, 2, 3]));
+    /// let slice_ref = slice.new_ref();
+    ///
+    /// let slice = Snarc::into_box(slice).err().unwrap();
+    /// slice.enter(|_| drop(slice_ref));
+    /// assert_eq!(Snarc::into_box(slice).ok().unwrap(), Box::from([1, 2, 3]));
+    /// ```
+    pub fn into_box(this: Self) -> Result<Box<T>, Self> {
+        if !this.is_unique() {
+            return Err(this);
+        }
+
+        let this = std::mem::ManuallyDrop::new(this);
+        Ok(unsafe { SnarcBox::into_box(this.ptr) })
+    }
+
+    /// Consumes and leaks `this`, returning a reference to the inner value,
+    /// like `Box::leak`.
+    ///
+    /// Existing weak references are revoked, so that the value cannot be
+    /// accessed elsewhere. The value is never dropped, so it is not scrubbed
+    /// given the `zeroize` feature.
+    pub fn leak<'a>(this: Self) -> &'a T
+    where
+        T: 'a,
+    {
+        this.inner().mark_dropped();
+        this.inner().revoke();
+
+        let this = std::mem::ManuallyDrop::new(this);
+        unsafe { &(*this.ptr).value }
+    }
+
+    /// Replaces the inner value, returning the old one.
+    ///
+    /// Weak references remain valid and resolve to `value`.
+    pub fn replace(this: &mut Self, value: T) -> T
+    where
+        T: Sized,
+    {
+        std::mem::replace(&mut **this, value)
+    }
+
+    /// Takes the inner value, leaving `T::default()` in its place.
+    pub fn take(this: &mut Self) -> T
+    where
+        T: Sized,
+        T: Default,
+    {
+        std::mem::take(&mut **this)
+    }
+
+    /// Swaps the inner values of `this` and `other`.
+    ///
+    /// Weak references keep pointing to their owner, so they resolve to the
+    /// other value afterwards.
+    pub fn swap(this: &mut Self, other: &mut Self)
+    where
+        T: Sized,
+    {
+        std::mem::swap(&mut **this, &mut **other)
+    }
+
+    /// Transforms the inner value with `f`, reusing the allocation if `T` and
+    /// `U` share a layout.
+    ///
+    /// Panics if there are weak references to the inner value.
+    pub fn map<U, F>(this: Self, f: F) -> Snarc<U>
+    where
+        T: Sized,
+        F: FnOnce(T) -> U,
+    {
+        if !this.is_unique() {
+            crate::__panic!("Snarc::map() while references remain")
+        }
+
+        if alloc::Layout::new::<T>() != alloc::Layout::new::<U>() {
+            let value = Self::try_unwrap(this).ok().unwrap();
+            return Snarc::new(f(value));
+        }
+
+        let this = std::mem::ManuallyDrop::new(this);
+        let ptr = this.ptr;
+
+        let release = scopeguard::guard_on_unwind((), |_| SnarcBox::release(ptr));
+        let value = unsafe { SnarcBox::value(ptr).read() };
+        unsafe { crate::scrub(SnarcBox::value(ptr)) };
+        let value = f(value);
+        scopeguard::ScopeGuard::into_inner(release);
+
+        // queued tasks expect a `T`, so they are dropped like they would be
+        // along with the allocation
+        let inner = unsafe { &*ptr };
+        inner.deferred.clear();
+        inner.mailbox.clear();
+
+        // the control blocks are `repr(C)`, so they share a layout too
+        let ptr = ptr.cast::<SnarcBox<U>>();
+        unsafe { ptr::addr_of_mut!((*ptr).value).write(value) };
+
+        Snarc {
+            ptr,
+            phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns `true` if there are no weak references to the inner value.
+    pub fn is_unique(&self) -> bool {
+        !self.has_refs()
+    }
+
+    /// Returns `true` if there are weak references to the inner value.
+    pub fn has_refs(&self) -> bool {
+        self.inner().refs() != 0
+    }
+
+    /// Returns the number of weak references to the inner value.
+    pub fn ref_count(&self) -> usize {
+        self.inner().refs()
+    }
+
+    /// Returns `true` if the inner value is bound to the current thread, i.e.,
+    /// within `enter`.
+    pub fn is_entered(&self) -> bool {
+        self.inner().state() == State::Entered
+    }
+
+    /// Returns the version of the inner value, which is incremented by every
+    /// mutable access.
+    pub fn version(&self) -> u64 {
+        self.inner().notifier.version()
+    }
+
+    /// Creates a new `Snarc` whose inner `value` is pinned, it will not be
+    /// moved until it is dropped.
+    pub fn pin(value: T) -> std::pin::Pin<Self>
+    where
+        T: Sized,
+    {
+        // the value is only ever moved by way of `&mut Self` or `Self`, neither
+        // of which `Pin` hands out
+        unsafe { std::pin::Pin::new_unchecked(Self::new(value)) }
+    }
+
+    /// Returns the pinned owner, e.g., to `enter` it.
+    ///
+    /// This is sound because none of the methods taking `&self` move the inner
+    /// value.
+    pub fn pinned_owner(this: &std::pin::Pin<Self>) -> &Self {
+        // `Pin` is `repr(transparent)`
+        unsafe { &*(this as *const std::pin::Pin<Self>).cast::<Self>() }
+    }
+
+    /// Creates a new non-owning reference to the pinned inner value.
+    pub fn new_pinned_ref(this: &std::pin::Pin<Self>) -> crate::PinnedRef<SnarcRef<T>> {
+        let new_ref = Self::pinned_owner(this).new_ref();
+        unsafe { crate::PinnedRef::new_unchecked(new_ref) }
+    }
+
+    /// Creates a new non-owning reference to the inner value.
+    pub fn new_ref(&self) -> SnarcRef<T> {
+        let inner = self.inner();
+
+        crate::increment(&inner.count);
+        crate::trace_new_ref(self.ptr.cast());
+
+        SnarcRef {
+            ptr: self.ptr,
+            generation: inner.generation(),
+            phantom: Default::default(),
+        }
+    }
+
+    /// Creates a new non-owning reference that grants mutable access to the
+    /// inner value, see `SnarcRefMut`.
+    pub fn new_ref_mut(&self) -> crate::SnarcRefMut<SnarcRef<T>> {
+        crate::SnarcRefMut::new(self.new_ref())
+    }
+
+    /// Revokes all existing weak references, they will never again resolve.
+    ///
+    /// References created afterwards are unaffected.
+    pub fn revoke_refs(&mut self) {
+        self.inner().revoke();
+    }
+
+    /// Temporarily bind the inner value to this thread and evaluate `f` within
+    /// that context.
+    ///
+    /// Panics if the inner value is entered on another thread. The value is
+    /// not recorded as entered, so neither `self_ref` nor `with_current` can
+    /// observe it, see `enter_with_self`.
+    pub fn enter<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.enter_recorded(|ptr| crate::record_entered_untyped(ptr.cast()), f)
+    }
+
+    /// Like `enter`, but records the value as entered, so that `self_ref` and
+    /// `with_current` observe it within `f`.
+    pub fn enter_with_self<F, R>(&self, f: F) -> R
+    where
+        T: 'static,
+        F: FnOnce(&T) -> R,
+    {
+        let record = |ptr: *mut SnarcBox<T>| {
+            crate::record_entered(
+                TypeId::of::<SnarcBox<T>>(),
+                ptr.cast(),
+                TypeId::of::<T>(),
+                unsafe { ptr::addr_of!((*ptr).value) }.cast(),
+            )
+        };
+
+        self.enter_recorded(record, f)
+    }
+
+    fn enter_recorded<E, F, R>(&self, record: E, f: F) -> R
+    where
+        E: FnOnce(*mut SnarcBox<T>) -> crate::EnteredRecord,
+        F: FnOnce(&T) -> R,
+    {
+        let inner = self.inner();
+        inner.check();
+
+        let claimed = inner.claim();
+
+        let state = inner.state();
+        inner.set_state(State::Entered);
+
+        let _guard = scopeguard::guard((), |_| {
+            inner.set_state(state);
+
+            if claimed {
+                inner.unclaim();
+            }
+        });
+
+        let _entered = record(self.ptr);
+
+        inner.notifier.notify();
+        inner.drain_deferred();
+
+        f(&inner.value)
+    }
+
+    /// Like `enter`, but also hands `f` the messages sent by weak references
+    /// since, see `SnarcRef::send`.
+    ///
+    /// Panics if messages of another type were sent.
+    pub fn enter_with_messages<M, F, R>(&self, f: F) -> R
+    where
+        M: Send + 'static,
+        F: FnOnce(&T, Vec<M>) -> R,
+    {
+        self.enter(|value| f(value, self.inner().mailbox.take()))
+    }
+
+    /// Like `enter`, but grants mutable access to the inner value.
+    ///
+    /// Returns `None` if there are weak references to the inner value. The
+    /// value is not recorded as entered, so neither `self_ref` nor
+    /// `with_current` can observe it.
+    pub fn enter_mut<F, R>(&mut self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        if !self.is_unique() {
+            return None;
+        }
+
+        let inner = self.inner();
+        inner.check();
+        inner.set_state(State::Entered);
+        inner.notifier.notify();
+        inner.drain_deferred();
+
+        let ptr = self.ptr;
+        let _guard = scopeguard::guard((), |_| {
+            unsafe { (*ptr).set_state(State::Default) };
+        });
+
+        // without weak references, only `f` can access the value
+        Some(f(unsafe { SnarcBox::into_mut_unchecked(&self.ptr) }))
+    }
+
+    /// Binds the inner value to this thread until the returned guard is
+    /// dropped.
+    ///
+    /// Unlike `enter`, this permits early returns and `?` within the entered
+    /// scope. Should the guard be leaked, the inner value is leaked as well,
+    /// accessing it mutably panics and so does entering it on another thread.
+    pub fn enter_guard(&mut self) -> EnterGuard<'_, T>
+    where
+        T: 'static,
+    {
+        let inner = self.inner();
+        inner.check();
+
+        // a leaked guard keeps its claim, so that no other thread may enter
+        let claimed = inner.claim();
+
+        inner.set_state(State::Entered);
+        inner.guarded.set(true);
+
+        let entered = crate::record_entered(
+            TypeId::of::<SnarcBox<T>>(),
+            self.ptr.cast(),
+            TypeId::of::<T>(),
+            ptr::addr_of!(inner.value).cast(),
+        );
+
+        inner.notifier.notify();
+        inner.drain_deferred();
+
+        EnterGuard {
+            inner,
+            claimed,
+            _entered: entered,
+        }
+    }
+
+    /// Temporarily bind the inner value to this thread and invoke all of
+    /// `callbacks`' handlers within that context.
+    pub fn dispatch<Args>(&mut self, callbacks: &CallbackSet<Args>, args: &Args) {
+        self.enter(|_| callbacks.invoke(args))
+    }
+
+    /// Wraps `fut` so that every poll happens within the `enter` context.
+    ///
+    /// The returned future borrows `self`, so the owner cannot be dropped
+    /// before the future completes or is dropped itself.
+    pub async fn scoped_future<F>(&mut self, fut: F) -> F::Output
+    where
+        F: Future,
+    {
+        let mut fut = std::pin::pin!(fut);
+        std::future::poll_fn(|cx| self.enter(|_| fut.as_mut().poll(cx))).await
+    }
+
+    /// Evaluates the asynchronous teardown of the inner value within the
+    /// `enter` context, then drops it.
+    pub async fn dispose_async(self)
+    where
+        T: Sized,
+        T: AsyncDispose,
+    {
+        // the future borrows the value by way of `self`, which outlives it
+        let mut fut = std::pin::pin!((*self).dispose());
+        std::future::poll_fn(|cx| self.enter(|_| fut.as_mut().poll(cx))).await;
+    }
+}
+
+impl<A, B> Snarc<(A, B)> {
+    /// Combines the inner values of `a` and `b`, so that they can be entered
+    /// together.
+    ///
+    /// Returns `a` and `b` back if there are weak references to either value.
+    pub fn zip(a: Snarc<A>, b: Snarc<B>) -> Result<Self, (Snarc<A>, Snarc<B>)> {
+        // both are checked up front, so that neither value is unwrapped in vain
+        if !a.is_unique() || !b.is_unique() {
+            return Err((a, b));
+        }
+
+        let (Ok(a), Ok(b)) = (Snarc::try_unwrap(a), Snarc::try_unwrap(b)) else {
+            unreachable!("both owners are unique")
+        };
+
+        Ok(Self::new((a, b)))
+    }
+}
+
+impl<T> Snarc<T> {
+    /// Creates a new `Snarc` with uninitialized contents, like
+    /// `Arc::new_uninit`.
+    pub fn new_uninit() -> Snarc<std::mem::MaybeUninit<T>> {
+        Snarc {
+            ptr: SnarcBox::new_uninit_ptr(),
+            phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Creates a new `Snarc` with uninitialized contents, whose bytes are all
+    /// zeros, like `Arc::new_zeroed`.
+    pub fn new_zeroed() -> Snarc<std::mem::MaybeUninit<T>> {
+        let ptr = SnarcBox::<std::mem::MaybeUninit<T>>::new_uninit_ptr();
+        unsafe { SnarcBox::value(ptr).write_bytes(0, 1) };
+
+        Snarc {
+            ptr,
+            phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T> Snarc<std::mem::MaybeUninit<T>> {
+    /// Converts to `Snarc<T>`, like `Arc::assume_init`.
+    ///
+    /// Weak references remain typed `MaybeUninit<T>`.
+    ///
+    /// # Safety
+    ///
+    /// The inner value must be initialized.
+    pub unsafe fn assume_init(self) -> Snarc<T> {
+        let this = std::mem::ManuallyDrop::new(self);
+
+        // the control block is `repr(C)` and `MaybeUninit<T>` shares the layout
+        // of `T`
+        Snarc {
+            ptr: this.ptr.cast(),
+            phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T: Send + 'static> From<Snarc<T>> for ErasedSnarc {
+    fn from(snarc: Snarc<T>) -> Self {
+        snarc.into_erased()
+    }
+}
+
+impl<T: Send + Sync + 'static> From<Snarc<T>> for crate::SyncErasedSnarc {
+    fn from(snarc: Snarc<T>) -> Self {
+        crate::SyncErasedSnarc::new(snarc)
+    }
+}
+
+impl<T: Send + 'static> From<Snarc<T>> for ErasedNarc {
+    fn from(snarc: Snarc<T>) -> Self {
+        snarc.into_unsend().into_erased()
+    }
+}
+
+unsafe impl<T> Context for Snarc<T> {
+    type Value = T;
+
+    fn into_raw(self) -> *mut () {
+        std::mem::ManuallyDrop::new(self).ptr.cast()
+    }
+
+    unsafe fn from_raw(ptr: *mut ()) -> Self {
+        Self {
+            ptr: ptr.cast(),
+            phantom: std::marker::PhantomData,
+        }
+    }
+
+    fn set(&mut self, v: State) {
+        self.inner().set_state(v)
+    }
+
+    fn value(&self) -> *const T {
+        ptr::addr_of!(self.inner().value)
+    }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn is_entered(&self) -> bool {
+        Snarc::is_entered(self)
+    }
+}
+
+impl<T: ?Sized> From<Box<T>> for Snarc<T> {
+    fn from(value: Box<T>) -> Self {
+        Self::from_box(value)
+    }
+}
+
+impl<T: ?Sized> Deref for Snarc<T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        &self.inner().value
+    }
+}
+
+impl<T: ?Sized> DerefMut for Snarc<T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { SnarcBox::into_mut_unchecked(&self.ptr) }
+    }
+}
+
+impl<T: ?Sized> Drop for Snarc<T> {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            if self.inner().guarded.get() {
+                // an `EnterGuard` was leaked, so references may still access the value
+                return;
+            }
+
+            Self::close(self);
+        }
+    }
+}
+
+/// Binds the inner value of a [`Snarc`] to the current thread until dropped.
+///
+/// Created by [`Snarc::enter_guard`].
+pub struct EnterGuard<'a, T: ?Sized> {
+    inner: &'a SnarcBox<T>,
+    claimed: bool,
+    _entered: crate::EnteredRecord,
+}
+
+impl<T: ?Sized> Deref for EnterGuard<'_, T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        &self.inner.value
+    }
+}
+
+impl<T: ?Sized> Drop for EnterGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inner.set_state(State::Default);
+        self.inner.guarded.set(false);
+
+        if self.claimed {
+            self.inner.unclaim();
+        }
+    }
+}
+
+/// A non-sendable, owning reference-counted pointer to a `T`.
+///
+/// When `Narc` is used exclusively, i.e., never `Snarc`, then `Rc` should
+/// likely be used instead.
+///
+/// Like `Snarc`, it binds the inner value only within `enter`. Only an
+/// `ErasedNarc`, which cannot access the value itself, keeps it bound for its
+/// whole lifetime.
+pub struct Narc<T: ?Sized> {
+    ptr: *mut SnarcBox<T>,
+    phantom: std::marker::PhantomData<SnarcBox<T>>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for Narc<T> {}
+
+impl<T: ?Sized> Narc<T> {
+    /// Creates a new `Narc` with the given inner `value`.
+    pub fn new(value: T) -> Self
+    where
+        T: Sized,
+    {
+        Self {
+            ptr: SnarcBox::new_ptr(value),
+            phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Creates a new `Narc` with the given inner `value`, failing instead of
+    /// aborting if memory cannot be allocated.
+    pub fn try_new(value: T) -> Result<Self, crate::AllocError>
+    where
+        T: Sized,
+    {
+        let ptr = SnarcBox::<T>::try_new_uninit_ptr()?;
+        unsafe { ptr::addr_of_mut!((*ptr).value).write(value) };
+
+        Ok(Self {
+            ptr,
+            phantom: std::marker::PhantomData,
+        })
+    }
+
+    /// Creates a new `Narc` with the boxed, possibly unsized, `value`.
+    ///
+    /// The box's allocation is reused where its alignment permits, otherwise
+    /// the value is moved to a new one.
+    ///
+    /// ```
+    /// use snarc::thread_local::Narc;
+    ///
+    /// let slice: Narc<[u8]> = Narc::from_box(Box::new([1, 2, 3]));
+    /// assert_eq!(&*slice, &[1, 2, 3]);
+    /// ```
+    pub fn from_box(value: Box<T>) -> Self {
+        Self {
+            ptr: SnarcBox::from_box_ptr(value),
+            phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Creates a new `Narc` with the inner value returned by `f`, or fails
+    /// with its error.
+    pub fn try_new_with<E, F>(f: F) -> Result<Self, E>
+    where
+        T: Sized,
+        F: FnOnce() -> Result<T, E>,
+    {
+        f().map(Self::new)
+    }
+
+    /// Turn this `Narc` into the `Send` version `Snarc`.
+    pub fn into_send(mut self) -> Snarc<T> {
+        let snarc = Snarc {
+            ptr: self.ptr,
+            phantom: self.phantom,
+        };
+
+        self.ptr = with_addr(self.ptr, ptr::null_mut());
+
+        snarc
+    }
+
+    /// Turn this parameterized `Narc` the unparameterized `ErasedNarc`.
+    pub fn into_erased(self) -> ErasedNarc
+    where
+        T: Sized,
+        T: Send + 'static,
+    {
+        self.into_send().into_erased().into_unsend()
+    }
+
+    /// Like `into_erased`, but for values that are not `Send`.
+    ///
+    /// The resulting `ErasedNarc` cannot be turned into an `ErasedSnarc`, see
+    /// `ErasedNarc::try_into_send`.
+    pub fn into_erased_unsend(self) -> ErasedNarc
+    where
+        T: Sized,
+        T: 'static,
+    {
+        ErasedNarc::from_unsend(self.into_send())
+    }
+
+    #[inline(always)]
+    fn inner(&self) -> &SnarcBox<T> {
+        SnarcBox::check_open(self.ptr);
+        unsafe { &*self.ptr }
+    }
+
+    /// Drops the inner value right away, weak references remain valid but no
+    /// longer resolve.
+    ///
+    /// This releases the resources held by the value even while weak
+    /// references remain. Afterwards, `this` may only be dropped, any other
+    /// use panics, see `is_closed`.
+    pub fn close(this: &mut Self) {
+        if !this.ptr.is_null() {
+            // closed up front, so that the value is not dropped twice should
+            // its destructor panic
+            let closed = with_addr(this.ptr, ptr::null_mut());
+            let ptr = std::mem::replace(&mut this.ptr, closed);
+            let inner = unsafe { &*ptr };
+
+            inner.check();
+
+            {
+                // without references, nothing can observe the binding
+                let bind = inner.refs() != 0;
+
+                if bind {
+                    inner.set_state(State::Entered);
+                }
+
+                let _guard = scopeguard::guard((), |_| {
+                    if bind {
+                        inner.set_state(State::Default)
+                    }
+                });
+
+                inner.snapshots.close();
+
+                unsafe {
+                    // destroy the contained object
+                    ptr::drop_in_place(SnarcBox::into_mut_unchecked(&ptr));
+                    crate::scrub(SnarcBox::value(ptr));
+                }
+
+                // the tasks and messages may hold references, which are best
+                // dropped bound
+                inner.deferred.clear();
+                inner.mailbox.clear();
+            }
+
+            inner.mark_dropped();
+            inner.revoke();
+            SnarcBox::release(ptr);
+        }
+    }
+
+    /// Returns `true` if `this` was closed, see `close`.
+    pub fn is_closed(this: &Self) -> bool {
+        this.ptr.is_null()
+    }
+
+    /// Returns `true` if `this` and `other` point to the same allocation, like
+    /// `Rc::ptr_eq`.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        ptr::addr_eq(this.ptr, other.ptr)
+    }
+
+    /// Returns a mutable reference to the inner value if there are no weak
+    /// references to it, like `Arc::get_mut`.
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        if this.is_unique() {
+            Some(unsafe { SnarcBox::into_mut_unchecked(&this.ptr) })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the inner value if there are no weak references to it,
+    /// otherwise `this` is returned unchanged.
+    pub fn try_unwrap(this: Self) -> Result<T, Self>
+    where
+        T: Sized,
+    {
+        if !this.is_unique() {
+            return Err(this);
+        }
+
+        let this = std::mem::ManuallyDrop::new(this);
+        let value = unsafe { SnarcBox::value(this.ptr).read() };
+        unsafe { crate::scrub(SnarcBox::value(this.ptr)) };
+        SnarcBox::release(this.ptr);
+
+        Ok(value)
+    }
+
+    /// Returns the inner value if there are no weak references to it,
+    /// otherwise `this` is dropped.
+    pub fn into_inner(this: Self) -> Option<T>
+    where
+        T: Sized,
+    {
+        Self::try_unwrap(this).ok()
+    }
+
+    /// Returns the boxed inner value if there are no weak references to it,
+    /// otherwise `this` is returned unchanged.
+    ///
+    /// Like `try_unwrap`, but for possibly unsized values. The allocation is
+    /// reused where its alignment permits.
+    ///
+    /// ```
+    /// use snarc::thread_local::Narc;
+    ///
+    /// let slice: Narc<[u8]> = Narc::from_box(Box::new([1, 2, 3]));
+    /// assert_eq!(Narc::into_box(slice).ok().unwrap(), Box::from([1, 2, 3]));
+    /// ```
+    pub fn into_box(this: Self) -> Result<Box<T>, Self> {
+        if !this.is_unique() {
+            return Err(this);
+        }
+
+        let this = std::mem::ManuallyDrop::new(this);
+        Ok(unsafe { SnarcBox::into_box(this.ptr) })
+    }
+
+    /// Consumes and leaks `this`, returning a reference to the inner value,
+    /// like `Box::leak`.
+    ///
+    /// Existing weak references are revoked, so that the value cannot be
+    /// accessed elsewhere. The value is never dropped, so it is not scrubbed
+    /// given the `zeroize` feature.
+    pub fn leak<'a>(this: Self) -> &'a T
+    where
+        T: 'a,
+    {
+        this.inner().mark_dropped();
+        this.inner().revoke();
+
+        let this = std::mem::ManuallyDrop::new(this);
+        unsafe { &(*this.ptr).value }
+    }
+
+    /// Replaces the inner value, returning the old one.
+    ///
+    /// Weak references remain valid and resolve to `value`.
+    pub fn replace(this: &mut Self, value: T) -> T
+    where
+        T: Sized,
+    {
+        std::mem::replace(&mut **this, value)
+    }
+
+    /// Takes the inner value, leaving `T::default()` in its place.
+    pub fn take(this: &mut Self) -> T
+    where
+        T: Sized,
+        T: Default,
+    {
+        std::mem::take(&mut **this)
+    }
+
+    /// Swaps the inner values of `this` and `other`.
+    ///
+    /// Weak references keep pointing to their owner, so they resolve to the
+    /// other value afterwards.
+    pub fn swap(this: &mut Self, other: &mut Self)
+    where
+        T: Sized,
+    {
+        std::mem::swap(&mut **this, &mut **other)
+    }
+
+    /// Transforms the inner value with `f`, reusing the allocation if `T` and
+    /// `U` share a layout.
+    ///
+    /// Panics if there are weak references to the inner value.
+    pub fn map<U, F>(this: Self, f: F) -> Narc<U>
+    where
+        T: Sized,
+        F: FnOnce(T) -> U,
+    {
+        if !this.is_unique() {
+            crate::__panic!("Narc::map() while references remain")
+        }
+
+        if alloc::Layout::new::<T>() != alloc::Layout::new::<U>() {
+            let value = Self::try_unwrap(this).ok().unwrap();
+            return Narc::new(f(value));
+        }
+
+        let this = std::mem::ManuallyDrop::new(this);
+        let ptr = this.ptr;
+
+        let release = scopeguard::guard_on_unwind((), |_| SnarcBox::release(ptr));
+        let value = unsafe { SnarcBox::value(ptr).read() };
+        unsafe { crate::scrub(SnarcBox::value(ptr)) };
+        let value = f(value);
+        scopeguard::ScopeGuard::into_inner(release);
+
+        // queued tasks expect a `T`, so they are dropped like they would be
+        // along with the allocation
+        let inner = unsafe { &*ptr };
+        inner.deferred.clear();
+        inner.mailbox.clear();
+
+        // the control blocks are `repr(C)`, so they share a layout too
+        let ptr = ptr.cast::<SnarcBox<U>>();
+        unsafe { ptr::addr_of_mut!((*ptr).value).write(value) };
+
+        Narc {
+            ptr,
+            phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns `true` if there are no weak references to the inner value.
+    pub fn is_unique(&self) -> bool {
+        !self.has_refs()
+    }
+
+    /// Returns `true` if there are weak references to the inner value.
+    pub fn has_refs(&self) -> bool {
+        self.inner().refs() != 0
+    }
+
+    /// Returns the number of weak references to the inner value.
+    pub fn ref_count(&self) -> usize {
+        self.inner().refs()
+    }
+
+    /// Returns the version of the inner value, which is incremented by every
+    /// mutable access.
+    pub fn version(&self) -> u64 {
+        self.inner().notifier.version()
+    }
+
+    /// Creates a new non-owning reference to the inner value.
+    pub fn new_ref(&self) -> SnarcRef<T> {
+        let inner = self.inner();
+
+        crate::increment(&inner.count);
+        crate::trace_new_ref(self.ptr.cast());
+
+        SnarcRef {
+            ptr: self.ptr,
+            generation: inner.generation(),
+            phantom: Default::default(),
+        }
+    }
+
+    /// Creates a new non-owning reference that grants mutable access to the
+    /// inner value, see `SnarcRefMut`.
+    pub fn new_ref_mut(&self) -> crate::SnarcRefMut<SnarcRef<T>> {
+        crate::SnarcRefMut::new(self.new_ref())
+    }
+
+    /// Revokes all existing weak references, they will never again resolve.
+    ///
+    /// References created afterwards are unaffected.
+    pub fn revoke_refs(&mut self) {
+        self.inner().revoke();
+    }
+
+    /// Temporarily bind the inner value to this thread and evaluate `f` within
+    /// that context, like `Snarc::enter`.
+    ///
+    /// Outside of `enter`, weak references do not resolve, since the inner
+    /// value may be mutated by way of `DerefMut`.
+    pub fn enter<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.as_send().enter(f)
+    }
+
+    /// Like `enter`, but records the value as entered, like
+    /// `Snarc::enter_with_self`.
+    pub fn enter_with_self<F, R>(&self, f: F) -> R
+    where
+        T: 'static,
+        F: FnOnce(&T) -> R,
+    {
+        self.as_send().enter_with_self(f)
+    }
+
+    fn as_send(&self) -> std::mem::ManuallyDrop<Snarc<T>> {
+        // merely borrows the allocation, which `self` keeps owning
+        std::mem::ManuallyDrop::new(Snarc {
+            ptr: self.ptr,
+            phantom: self.phantom,
+        })
+    }
+}
+
+impl<T: Send + 'static> From<Narc<T>> for ErasedSnarc {
+    fn from(narc: Narc<T>) -> Self {
+        narc.into_send().into_erased()
+    }
+}
+
+impl<T: Send + 'static> From<Narc<T>> for ErasedNarc {
+    fn from(narc: Narc<T>) -> Self {
+        narc.into_erased()
+    }
+}
+
+impl<T: ?Sized> From<Box<T>> for Narc<T> {
+    fn from(value: Box<T>) -> Self {
+        Self::from_box(value)
+    }
+}
+
+impl<T: ?Sized> Deref for Narc<T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        &self.inner().value
+    }
+}
+
+impl<T: ?Sized> DerefMut for Narc<T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { SnarcBox::into_mut_unchecked(&self.ptr) }
+    }
+}
+
+impl<T: ?Sized> Drop for Narc<T> {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            if self.inner().guarded.get() {
+                // an `EnterGuard` was leaked, so references may still access the value
+                return;
+            }
+
+            Self::close(self);
+        }
+    }
+}
+
+/// A sendable, non-owning reference-counted pointer to a `T`.
+pub struct SnarcRef<T: ?Sized> {
+    ptr: *mut SnarcBox<T>,
+    generation: u32,
+    phantom: std::marker::PhantomData<SnarcBox<T>>,
+}
+
+unsafe impl<T: ?Sized> Send for SnarcRef<T> {}
+unsafe impl<T: ?Sized> Sync for SnarcRef<T> {}
+
+impl<T> SnarcRef<T> {
+    /// Creates a reference without an owner, like `Weak::new`. It never
+    /// resolves and is dangling from the start.
+    ///
+    /// This allows for structs to hold a reference before the owner exists.
+    pub fn new() -> Self {
+        let ptr = SnarcBox::<T>::new_uninit_ptr();
+        let inner = unsafe { &*ptr };
+
+        inner.mark_dropped();
+        crate::increment(&inner.count);
+        SnarcBox::release(ptr);
+
+        // the value is never written, so the reference is revoked from the
+        // start
+        SnarcRef {
+            ptr,
+            generation: 1,
+            phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T> Default for SnarcRef<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> SnarcRef<T> {
+    #[inline(always)]
+    fn inner(&self) -> &SnarcBox<T> {
+        unsafe { &*self.ptr }
+    }
+
+    #[inline(always)]
+    fn is_revoked(&self) -> bool {
+        self.inner().generation() != self.generation
+    }
+
+    #[inline(always)]
+    fn is_accessible(&self) -> bool {
+        self.inner().state().is_set() && !self.is_revoked()
+    }
+
+    /// Gets a reference to the inner value.
+    ///
+    /// Returns `None` if the corresponding owning pointer did not currently
+    /// bind the inner value to the current thread. The reference is only
+    /// bound to `self`, prefer `borrow` where it might outlive the context.
+    #[track_caller]
+    pub fn get(&self) -> Option<&T> {
+        let inner = self.inner();
+        inner.check();
+
+        if self.is_accessible() {
             Some(&inner.value)
+        } else {
+            crate::failed_get();
+            None
+        }
+    }
+
+    /// Gets a reference to the inner value, panicking if it is not bound to
+    /// the current thread.
+    #[track_caller]
+    pub fn expect(&self) -> &T {
+        match self.get() {
+            Some(value) => value,
+            None => crate::__panic!("SnarcRef::expect() outside of Snarc::enter(…)"),
+        }
+    }
+
+    /// Like `expect`, but panics with `msg`.
+    #[track_caller]
+    pub fn expect_with(&self, msg: &str) -> &T {
+        match self.get() {
+            Some(value) => value,
+            None => crate::__panic!("{}", msg),
+        }
+    }
+
+    /// Like `expect`, but panics with the message returned by `f`, which is
+    /// only evaluated if the inner value is not bound to the current thread.
+    #[track_caller]
+    pub fn expect_with_fn<F, M>(&self, f: F) -> &T
+    where
+        F: FnOnce() -> M,
+        M: std::fmt::Display,
+    {
+        match self.get() {
+            Some(value) => value,
+            None => crate::__panic!(@display f()),
+        }
+    }
+
+    /// Borrows the inner value for as long as the returned guard exists.
+    ///
+    /// Returns `None` if the corresponding owning pointer did not currently
+    /// bind the inner value to the current thread, like `get`. Unlike the
+    /// reference returned by `get`, the guard cannot outlive
+    /// `Snarc::enter(…)`, leaving it before the guard is dropped panics.
+    /// Returns `None` as well while the value is borrowed mutably, see
+    /// `SnarcRefMut`.
+    ///
+    /// ```
+    /// use snarc::thread_local::Snarc;
+    ///
+    /// let snarc = Snarc::new(7);
+    /// let snarc_ref = snarc.new_ref();
+    ///
+    /// assert!(snarc_ref.borrow().is_none());
+    /// snarc.enter(|_| {
+    ///     assert_eq!(*snarc_ref.borrow().unwrap(), 7);
+    ///     drop(snarc_ref);
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn borrow(&self) -> Option<crate::RefGuard<'_, T>> {
+        let value = self.get()?;
+
+        if !self.inner().borrows.try_borrow() {
+            return None;
+        }
+
+        Some(unsafe { crate::RefGuard::new(value, self.ptr.cast(), SnarcBox::<T>::unborrow) })
+    }
+
+    /// Borrows the inner value mutably for as long as the returned guard
+    /// exists, like `RefCell::borrow_mut`.
+    ///
+    /// Returns `None` outside of `Snarc::enter(…)`, like `get`, and while the
+    /// value is borrowed by way of another weak reference.
+    ///
+    /// ```
+    /// use snarc::thread_local::Snarc;
+    ///
+    /// let snarc = Snarc::new(vec![1]);
+    /// let snarc_ref = snarc.new_ref();
+    ///
+    /// snarc.enter(|_| {
+    ///     // neither the closure's argument nor `get` is used meanwhile
+    ///     unsafe { snarc_ref.borrow_mut() }.unwrap().push(2);
+    ///     assert_eq!(*snarc_ref.borrow().unwrap(), [1, 2]);
+    ///     drop(snarc_ref);
+    /// });
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// Only the borrows of weak references are tracked. Neither the references
+    /// handed out by the owner, e.g., to the closure passed to `enter`, nor
+    /// those returned by `get` may be used while the guard exists.
+    #[track_caller]
+    pub unsafe fn borrow_mut(&self) -> Option<crate::RefMut<'_, T>> {
+        let inner = self.inner();
+        inner.check();
+
+        if !self.is_accessible() {
+            crate::failed_get();
+            return None;
+        }
+
+        if !inner.borrows.try_borrow_mut() {
+            return None;
+        }
+
+        inner.notifier.mark_dirty();
+
+        // other weak references cannot borrow the value while this guard exists
+        let value = unsafe { &mut *SnarcBox::value(self.ptr) };
+        Some(unsafe { crate::RefMut::new(value, self.ptr.cast(), SnarcBox::<T>::unborrow) })
+    }
+
+    /// Evaluates `f` with a reference to the inner value.
+    ///
+    /// Returns `None` if the corresponding owning pointer did not currently
+    /// bind the inner value to the current thread, like `get`.
+    #[track_caller]
+    pub fn with<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.get().map(f)
+    }
+
+    /// Gets a reference to a part of the inner value.
+    ///
+    /// Returns `None` outside of `enter`, like `get`.
+    #[track_caller]
+    pub fn map_get<U, F>(&self, f: F) -> Option<&U>
+    where
+        U: ?Sized,
+        F: FnOnce(&T) -> &U,
+    {
+        self.get().map(f)
+    }
+
+    /// Evaluates `f` with a reference to the inner value, flattening the
+    /// result.
+    ///
+    /// Returns `None` outside of `enter`, like `get`.
+    #[track_caller]
+    pub fn and_then_get<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&T) -> Option<R>,
+    {
+        self.get().and_then(f)
+    }
+
+    /// Gets a reference to the inner value, or the one returned by `f`
+    /// outside of `enter`.
+    #[track_caller]
+    pub fn get_or_else<'a, F>(&'a self, f: F) -> &'a T
+    where
+        F: FnOnce() -> &'a T,
+    {
+        self.get().unwrap_or_else(f)
+    }
+
+    /// Projects this reference to a part of the inner value, e.g., a field.
+    ///
+    /// ```
+    /// use snarc::thread_local::Snarc;
+    ///
+    /// let snarc = Snarc::new((1, String::from("two")));
+    /// let second = snarc.new_ref().map(|(_, second)| second);
+    ///
+    /// assert_eq!(second.get(), None);
+    /// snarc.enter(|_| {
+    ///     assert_eq!(second.get().unwrap(), "two");
+    ///     drop(second);
+    /// });
+    /// ```
+    pub fn map<U, F>(self, f: F) -> crate::MappedSnarcRef<U>
+    where
+        T: Sized + 'static,
+        U: 'static,
+        F: Fn(&T) -> &U + Send + Sync + 'static,
+    {
+        crate::MappedSnarcRef::new(self, f)
+    }
+
+    /// Returns `true` if `self` and `other` refer to the same value.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        ptr::addr_eq(self.ptr, other.ptr)
+    }
+
+    /// Returns `true` if `owner` owns the value `self` refers to.
+    pub fn same_owner(&self, owner: &Snarc<T>) -> bool {
+        ptr::addr_eq(self.ptr, owner.ptr)
+    }
+
+    /// Returns `true` if the owner was dropped, closed or leaked, so that this
+    /// reference will never resolve again.
+    ///
+    /// Unlike `get`, this may be called outside of `Snarc::enter(…)`, which
+    /// lets long-lived references clean themselves up.
+    pub fn is_dangling(&self) -> bool {
+        let inner = self.inner();
+        inner.check();
+
+        inner.dropped.load(std::sync::atomic::Ordering::Acquire)
+    }
+
+    /// Returns `true` if `get` would currently succeed, i.e., within
+    /// `Snarc::enter(…)`, without accessing the inner value.
+    #[inline]
+    pub fn is_entered(&self) -> bool {
+        self.inner().check();
+        self.is_accessible()
+    }
+
+    /// Returns the number of weak references to the inner value, including
+    /// this one.
+    ///
+    /// Returns `None` outside of `Snarc::enter(…)`, like `get`.
+    pub fn ref_count(&self) -> Option<usize> {
+        let inner = self.inner();
+        inner.check();
+
+        if self.is_accessible() {
+            Some(inner.refs())
         } else {
             None
         }
     }
-}
 
-impl<T> Clone for SnarcRef<T> {
-    fn clone(&self) -> Self {
+    /// Returns the version of the inner value.
+    ///
+    /// Returns `None` outside of `Snarc::enter(…)`, like `get`.
+    pub fn version(&self) -> Option<u64> {
         let inner = self.inner();
+        inner.check();
+
+        if self.is_accessible() {
+            Some(inner.notifier.version())
+        } else {
+            None
+        }
+    }
 
-        if inner.thread_local.get_or_default().get().is_set() {
-            inner.count.set(inner.count.get() + 1);
+    /// Returns a future that resolves once the inner value is bound to the
+    /// current thread, or once the owner enters next.
+    pub fn when_entered(&self) -> impl Future<Output = ()> + '_ {
+        let mut since = None;
 
-            SnarcRef {
-                ptr: self.ptr,
-                phantom: Default::default(),
+        std::future::poll_fn(move |cx| {
+            let inner = self.inner();
+            inner.check();
+
+            if self.is_accessible() {
+                Poll::Ready(())
+            } else {
+                inner.notifier.poll_entered(&mut since, cx)
             }
+        })
+    }
+
+    /// Subscribes to changes of the inner value.
+    ///
+    /// Panics outside of `Snarc::enter(…)`, like `clone`.
+    pub fn subscribe(&self) -> ChangeListener {
+        let inner = self.inner();
+        inner.check();
+
+        if inner.state().is_set() {
+            inner.notifier.subscribe()
         } else {
-            panic!("SnarcRef::clone() outside of Snarc::enter(…)")
+            crate::__panic!("SnarcRef::subscribe() outside of Snarc::enter(…)")
+        }
+    }
+
+    /// Schedules `f` to run at the start of the owner's next `enter`, from any
+    /// thread.
+    ///
+    /// `f` is dropped without running if `self` is revoked, or once the owner
+    /// is dropped. A `Narc` runs it only once it is sent and entered.
+    pub fn defer<F>(&self, f: F)
+    where
+        F: FnOnce(&T) + Send + 'static,
+    {
+        let inner = self.inner();
+        inner.check();
+
+        if !self.is_revoked() {
+            inner.deferred.push(f);
+        }
+    }
+
+    /// Clones the inner value from any thread, while the owner neither enters
+    /// it nor accesses it mutably.
+    ///
+    /// Returns `None` if the value is entered, or if the owner accessed it
+    /// mutably since it was last entered, as well as if `self` is revoked, or
+    /// another snapshot is being taken. Meanwhile, the owner waits before
+    /// entering or mutating the value.
+    ///
+    /// Requires the `snapshot` feature.
+    #[cfg(feature = "snapshot")]
+    pub fn snapshot(&self) -> Option<T>
+    where
+        T: Sync + Clone,
+    {
+        let inner = self.inner();
+        inner.check();
+
+        if !inner.snapshots.try_read() {
+            return None;
+        }
+
+        let _guard = scopeguard::guard((), |_| inner.snapshots.end_read());
+
+        // the owner cannot drop the value meanwhile, see `close`
+        if self.is_revoked() {
+            return None;
+        }
+
+        Some(inner.value.clone())
+    }
+
+    /// Sends `message` to the owner, from any thread, which receives it by way
+    /// of `Snarc::enter_with_messages`.
+    ///
+    /// Returns `message` if `self` is revoked, or once the owner is dropped.
+    /// Panics if messages of another type were sent to the same value before.
+    pub fn send<M: Send + 'static>(&self, message: M) -> Result<(), M> {
+        let inner = self.inner();
+        inner.check();
+
+        if self.is_revoked() {
+            return Err(message);
+        }
+
+        inner.mailbox.send(message);
+        Ok(())
+    }
+
+    /// Creates a new non-owning reference to the innermost value of type `T`
+    /// that is bound to this thread by
+    /// `enter_with_self`.
+    ///
+    /// This allows methods of `T` to reference their own value without it
+    /// having to store a reference to itself.
+    pub fn self_ref() -> Option<Self>
+    where
+        T: Sized + 'static,
+    {
+        let ptr: *mut SnarcBox<T> = crate::innermost_entered(TypeId::of::<SnarcBox<T>>())?.cast();
+        let inner = unsafe { &*ptr };
+
+        crate::increment(&inner.count);
+        crate::trace_new_ref(ptr.cast());
+
+        Some(SnarcRef {
+            ptr,
+            generation: inner.generation(),
+            phantom: Default::default(),
+        })
+    }
+
+    /// Moves this reference into the process-wide token registry.
+    pub fn into_token(self) -> FfiToken
+    where
+        T: 'static,
+    {
+        FfiToken::register(self)
+    }
+
+    /// Takes the reference that `token` stands for out of the registry.
+    ///
+    /// Returns `None` if `token` is stale or stands for another type.
+    pub fn from_token(token: FfiToken) -> Option<Self>
+    where
+        T: 'static,
+    {
+        token.resolve()
+    }
+
+    /// Turn this parameterized `SnarcRef` the unparameterized `ErasedSnarcRef`.
+    pub fn into_erased(self) -> ErasedSnarcRef
+    where
+        T: Sized,
+        T: 'static,
+    {
+        let snarc_ref: Box<dyn RefContext> = Box::new(self);
+        ErasedSnarcRef::from(snarc_ref)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> SnarcRef<T> {
+    /// Serializes the inner value with `serializer`.
+    ///
+    /// Fails with `AccessOrSerError::NotEntered` if the corresponding owning
+    /// pointer did not currently bind the inner value to the current thread.
+    pub fn serialize_value<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, crate::AccessOrSerError<S::Error>> {
+        crate::serialize_value(self, serializer)
+    }
+}
+
+impl<T: ?Sized> crate::GetMut<T> for SnarcRef<T> {
+    #[track_caller]
+    unsafe fn get_mut(&self) -> Option<crate::RefMut<'_, T>> {
+        unsafe { self.borrow_mut() }
+    }
+}
+
+impl<T> Get<T> for SnarcRef<T> {
+    #[track_caller]
+    fn get(&self) -> Option<&T> {
+        SnarcRef::get(self)
+    }
+
+    fn version(&self) -> Option<u64> {
+        SnarcRef::version(self)
+    }
+}
+
+impl<T: 'static> crate::Enter for Snarc<T> {
+    fn enter<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        Snarc::enter(self, f)
+    }
+}
+
+unsafe impl<T> crate::graph::Node for Snarc<T> {
+    type Value = T;
+    type Ref = SnarcRef<T>;
+
+    fn alloc_uninit() -> *mut () {
+        let ptr = SnarcBox::<T>::new_uninit_ptr();
+        // placeholders are revoked until the value is written, see `init`
+        unsafe { (*ptr).set_generation(1) };
+
+        ptr.cast()
+    }
+
+    fn placeholder(ptr: *mut ()) -> SnarcRef<T> {
+        let ptr = ptr.cast::<SnarcBox<T>>();
+        crate::increment(unsafe { &(*ptr).count });
+
+        SnarcRef {
+            ptr,
+            generation: 0,
+            phantom: Default::default(),
+        }
+    }
+
+    unsafe fn init(ptr: *mut (), value: T) -> Self {
+        let ptr = ptr.cast::<SnarcBox<T>>();
+        ptr::addr_of_mut!((*ptr).value).write(value);
+        (*ptr).set_generation(0);
+
+        Self {
+            ptr,
+            phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T: 'static> From<SnarcRef<T>> for SnarcOrArc<T> {
+    fn from(snarc_ref: SnarcRef<T>) -> Self {
+        SnarcOrArc::Snarc(Box::new(snarc_ref))
+    }
+}
+
+impl<T: 'static> From<SnarcRef<T>> for ErasedSnarcRef {
+    fn from(snarc_ref: SnarcRef<T>) -> Self {
+        snarc_ref.into_erased()
+    }
+}
+
+impl<T: 'static> RefContext for SnarcRef<T> {
+    fn is_entered(&self) -> bool {
+        SnarcRef::is_entered(self)
+    }
+
+    fn is_dangling(&self) -> bool {
+        SnarcRef::is_dangling(self)
+    }
+}
+
+impl<T: ?Sized> Clone for SnarcRef<T> {
+    fn clone(&self) -> Self {
+        let inner = self.inner();
+
+        if inner.balance.is_orphaned() {
+            // the owner is gone, the count is no longer used
+            inner.balance.increment();
+        } else if inner.state().is_set() {
+            crate::increment(&inner.count);
+        } else if !crate::Detached::try_increment(&inner.balance) {
+            crate::__panic!("SnarcRef::clone() outside of Snarc::enter(…)")
+        }
+
+        SnarcRef {
+            ptr: self.ptr,
+            generation: self.generation,
+            phantom: Default::default(),
+        }
+    }
+}
+
+/// Resolves like `expect`, for code that prefers ergonomics over handling
+/// `None`.
+#[cfg(feature = "deref-panicking")]
+impl<T: ?Sized> Deref for SnarcRef<T> {
+    type Target = T;
+
+    #[track_caller]
+    fn deref(&self) -> &T {
+        match self.get() {
+            Some(value) => value,
+            None => crate::__panic!("SnarcRef::deref() outside of Snarc::enter(…)"),
         }
     }
 }
 
-impl<T> Drop for SnarcRef<T> {
+/// References compare equal if they refer to the same value, see `ptr_eq`.
+impl<T: ?Sized> PartialEq for SnarcRef<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other)
+    }
+}
+
+impl<T: ?Sized> Eq for SnarcRef<T> {}
+
+impl<T: ?Sized> std::hash::Hash for SnarcRef<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.ptr.cast::<()>().hash(state)
+    }
+}
+
+impl<T: ?Sized> Drop for SnarcRef<T> {
     fn drop(&mut self) {
         let inner = self.inner();
+        inner.check();
 
-        if inner.thread_local.get_or_default().get().is_set() {
-            inner.count.set(inner.count.get() - 1);
-        } else {
-            #[cfg(debug_assertions)]
-            panic!("SnarcRef::drop() outside of Snarc::enter(…)")
+        if inner.state().is_set() {
+            inner.decrement();
+        } else if inner.balance.decrement() {
+            // the owner is gone and this was the last reference
+            unsafe { SnarcBox::free(self.ptr) }
         }
     }
 }
 
+#[cfg(feature = "defmt")]
+impl<T> defmt::Format for Snarc<T> {
+    fn format(&self, fmt: defmt::Formatter<'_>) {
+        defmt::Format::format("Snarc", fmt)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T> defmt::Format for Narc<T> {
+    fn format(&self, fmt: defmt::Formatter<'_>) {
+        defmt::Format::format("Narc", fmt)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T> defmt::Format for SnarcRef<T> {
+    fn format(&self, fmt: defmt::Formatter<'_>) {
+        defmt::Format::format("SnarcRef", fmt)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::Narc;
@@ -364,4 +2385,269 @@ mod tests {
     use super::SnarcRef;
 
     crate::tests::tests!(Snarc, Narc, SnarcRef);
+
+    #[test]
+    fn owners_that_are_never_entered_do_not_allocate_a_thread_local() {
+        struct Probe(std::cell::Cell<*const super::SnarcBox<Probe>>);
+
+        impl Drop for Probe {
+            fn drop(&mut self) {
+                // an owner that is dropped binds its value first, if at all
+                let inner = unsafe { &*self.0.get() };
+                assert!(inner.thread_local.get().is_none());
+            }
+        }
+
+        let snarc = Snarc::new(Probe(std::cell::Cell::new(std::ptr::null())));
+        snarc.0.set(snarc.ptr);
+        assert!(snarc.inner().thread_local.get().is_none());
+        drop(snarc);
+
+        let narc = Narc::new(Probe(std::cell::Cell::new(std::ptr::null())));
+        narc.0.set(narc.ptr);
+        assert!(narc.inner().thread_local.get().is_none());
+        drop(narc);
+
+        let snarc = Snarc::new(1);
+        snarc.enter(|_| ());
+        assert!(snarc.inner().thread_local.get().is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "debug-canary")]
+    fn tail_canary_precedes_the_value() {
+        let snarc = Snarc::new(0u64);
+        let inner = snarc.inner();
+
+        let tail = std::ptr::addr_of!(inner.tail) as usize;
+        let value = std::ptr::addr_of!(inner.value) as usize;
+        assert!(tail + std::mem::size_of::<crate::Canary>() <= value);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-canary")]
+    fn corrupted_headers_abort() {
+        let test = concat!(module_path!(), "::corrupted_headers_abort");
+        let test = test.split_once("::").unwrap().1;
+
+        let stderr = crate::tests::aborts(test, || {
+            let snarc = Snarc::new(0u64);
+            unsafe {
+                std::ptr::addr_of_mut!((*snarc.ptr).tail)
+                    .cast::<u64>()
+                    .write(0)
+            };
+            snarc.enter(|_| ());
+        });
+        // defmt does not log to stderr
+        assert!(
+            cfg!(feature = "defmt") || stderr.contains("snarc: corrupted control block"),
+            "{stderr}"
+        );
+    }
+
+    #[test]
+    fn enter_is_exclusive_to_one_thread() {
+        let snarc = std::sync::Arc::new(Snarc::new(1));
+        let snarc_ref = snarc.new_ref();
+
+        snarc.enter(|_| {
+            snarc.enter(|_| assert_eq!(snarc_ref.get(), Some(&1)));
+            assert_eq!(snarc_ref.get(), Some(&1));
+
+            let other = std::sync::Arc::clone(&snarc);
+            let result = std::thread::spawn(move || other.enter(|_| ())).join();
+            assert!(result.is_err());
+
+            drop(snarc_ref);
+        });
+    }
+
+    #[test]
+    fn enter_set_binds_all_owners() {
+        let (a, b) = (Snarc::new(1), Snarc::new("two"));
+        let (a_ref, b_ref) = (a.new_ref(), b.new_ref());
+
+        let mut set = crate::EnterSet::new();
+        set.push(a);
+        set.push(b);
+
+        set.enter(|| {
+            assert_eq!((a_ref.get(), b_ref.get()), (Some(&1), Some(&"two")));
+        });
+        assert_eq!((a_ref.get(), b_ref.get()), (None, None));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            set.enter(|| panic!("unwinding"))
+        }));
+        assert!(result.is_err());
+        assert_eq!((a_ref.get(), b_ref.get()), (None, None));
+
+        set.enter(|| drop((a_ref, b_ref)));
+    }
+
+    #[test]
+    fn erased_narc_of_unsendable_value_downcasts() {
+        let erased = Narc::new(std::rc::Rc::new(1)).into_erased_unsend();
+
+        let erased = erased.downcast::<u8>().err().unwrap();
+        let narc = erased.downcast::<std::rc::Rc<i32>>().ok().unwrap();
+
+        assert_eq!(**narc, 1);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "defmt"), should_panic(expected = "is not Send"))]
+    #[cfg_attr(feature = "defmt", should_panic)]
+    fn erased_narc_of_unsendable_value_is_not_sent() {
+        let erased = Narc::new(std::rc::Rc::new(1)).into_erased_unsend();
+
+        erased.into_send();
+    }
+
+    #[test]
+    fn enter_guard_binds_until_dropped() {
+        fn first_char(snarc_ref: &SnarcRef<String>) -> Option<char> {
+            let value = snarc_ref.get()?;
+            value.chars().next()
+        }
+
+        let mut snarc = Snarc::new(String::from("guarded"));
+        let snarc_ref = snarc.new_ref();
+
+        let guard = snarc.enter_guard();
+        assert_eq!(guard.len(), 7);
+        assert_eq!(first_char(&snarc_ref), Some('g'));
+        drop(guard);
+
+        assert_eq!(first_char(&snarc_ref), None);
+        snarc.push('!');
+        snarc.enter(|_| drop(snarc_ref));
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(feature = "defmt"),
+        should_panic(expected = "EnterGuard was leaked")
+    )]
+    #[cfg_attr(feature = "defmt", should_panic)]
+    fn leaked_enter_guard_poisons_owner() {
+        let mut snarc = Snarc::new(1);
+        std::mem::forget(snarc.enter_guard());
+
+        *snarc += 1;
+    }
+
+    #[test]
+    fn leaked_enter_guard_keeps_other_threads_out() {
+        let mut snarc = Snarc::new(1);
+        std::mem::forget(snarc.enter_guard());
+
+        let snarc = &snarc;
+        let result = std::thread::scope(|s| s.spawn(move || snarc.enter(|v| *v)).join());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn unsized_values_are_accessible() {
+        let counter = std::rc::Rc::new(std::cell::Cell::new(0));
+        let slice: Snarc<[String]> =
+            Snarc::from_box(Box::new([String::from("a"), String::from("b")]));
+        let callback: Snarc<dyn Fn() -> usize> = Snarc::from_box({
+            let counter = std::rc::Rc::clone(&counter);
+            Box::new(move || {
+                counter.set(counter.get() + 1);
+                counter.get()
+            })
+        });
+        let slice_ref = slice.new_ref();
+        let callback_ref = callback.new_ref();
+
+        slice.enter(|slice| {
+            assert_eq!(slice.len(), 2);
+            assert_eq!(slice_ref.get().map(|s| s.join("")), Some("ab".into()));
+            drop(slice_ref);
+        });
+        callback.enter(|callback| {
+            assert_eq!(callback(), 1);
+            assert_eq!(callback_ref.get().map(|c| c()), Some(2));
+            drop(callback_ref);
+        });
+
+        drop(callback);
+        assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn boxes_round_trip() {
+        let words: Snarc<[u64]> = Snarc::from_box(Box::new([1, 2, 3]));
+        let bytes: Snarc<[u8]> = Box::<[u8]>::from(*b"bytes").into();
+        let empty: Narc<[u8]> = Narc::from_box(Box::new([]));
+
+        assert_eq!(Snarc::into_box(words).ok().unwrap(), Box::from([1, 2, 3]));
+        assert_eq!(Snarc::into_box(bytes).ok().unwrap(), Box::from(*b"bytes"));
+        assert_eq!(Narc::into_box(empty).ok().unwrap(), Box::<[u8]>::from([]));
+
+        let counter = std::rc::Rc::new(());
+        let dynamic: Snarc<dyn std::any::Any> = Snarc::from_box(Box::new(counter.clone()));
+        let dynamic = Snarc::into_box(dynamic).ok().unwrap();
+        assert_eq!(std::rc::Rc::strong_count(&counter), 2);
+        assert!(dynamic.is::<std::rc::Rc<()>>());
+        drop(dynamic);
+        assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn boxes_are_scrubbed_when_moved_out() {
+        use crate::tests::scrubbed::leftovers;
+        use crate::tests::scrubbed::SECRET;
+
+        let before = leftovers();
+
+        let words: Snarc<[u64]> = Snarc::from_box(Box::new([SECRET; 4]));
+        let words = Snarc::into_box(words).ok().unwrap();
+        let bytes: Snarc<[u8]> = Snarc::from_box(SECRET.to_ne_bytes().repeat(4).into());
+        let bytes = Snarc::into_box(bytes).ok().unwrap();
+
+        assert_eq!(leftovers(), before);
+
+        assert_eq!(*words, [SECRET; 4]);
+        assert_eq!(*bytes, *SECRET.to_ne_bytes().repeat(4));
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(feature = "defmt"),
+        should_panic(expected = "SnarcRef::expect() outside of Snarc::enter(…)")
+    )]
+    #[cfg_attr(feature = "defmt", should_panic)]
+    fn expect_panics_outside_of_enter() {
+        let snarc = Snarc::new(1);
+        let snarc_ref = snarc.new_ref();
+
+        snarc.enter(|_| assert_eq!(snarc_ref.expect(), &1));
+        snarc_ref.expect();
+    }
+
+    #[test]
+    #[cfg(not(feature = "defmt"))]
+    fn expect_with_panics_with_the_given_message() {
+        let snarc = Snarc::new(1);
+        let snarc_ref = snarc.new_ref();
+
+        snarc.enter(|_| {
+            assert_eq!(snarc_ref.expect_with("entered"), &1);
+            assert_eq!(snarc_ref.expect_with_fn(|| unreachable!() as &str), &1);
+        });
+
+        let snarc_ref = std::panic::AssertUnwindSafe(snarc_ref);
+        let panic = std::panic::catch_unwind(|| snarc_ref.expect_with("not entered"));
+        assert_eq!(
+            panic.unwrap_err().downcast_ref(),
+            Some(&String::from("not entered"))
+        );
+        let panic = std::panic::catch_unwind(|| snarc_ref.expect_with_fn(|| 42));
+        assert_eq!(panic.unwrap_err().downcast_ref(), Some(&String::from("42")));
+    }
 }
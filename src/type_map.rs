@@ -0,0 +1,73 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+
+use crate::Context;
+use crate::EnterAll;
+use crate::ErasedOwner;
+use crate::ErasedSnarc;
+
+/// A bag of erased owners, holding at most one owner of each type.
+///
+/// Applications may keep their shared states in a map, enter all of them once
+/// per task tick and retrieve the typed owners whenever they need them back.
+#[derive(Debug, Default)]
+pub struct SnarcTypeMap {
+    owners: HashMap<TypeId, ErasedSnarc>,
+}
+
+impl SnarcTypeMap {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `owner` to the map, returning the owner of the same type it held
+    /// before.
+    pub fn insert<S>(&mut self, owner: S) -> Option<S>
+    where
+        S: Context + Send + 'static,
+        S::Value: 'static,
+    {
+        let erased = ErasedSnarc::from(ErasedOwner::new(owner));
+
+        self.owners.insert(TypeId::of::<S>(), erased).map(downcast)
+    }
+
+    /// Returns `true` if the map holds an owner of type `S`.
+    pub fn contains<S: Context + 'static>(&self) -> bool {
+        self.owners.contains_key(&TypeId::of::<S>())
+    }
+
+    /// Takes the owner of type `S` out of the map.
+    pub fn remove<S: Context + 'static>(&mut self) -> Option<S> {
+        self.owners.remove(&TypeId::of::<S>()).map(downcast)
+    }
+
+    /// Returns the number of owners in the map.
+    pub fn len(&self) -> usize {
+        self.owners.len()
+    }
+
+    /// Returns `true` if the map holds no owners.
+    pub fn is_empty(&self) -> bool {
+        self.owners.is_empty()
+    }
+
+    /// Temporarily bind the inner values of all owners to this thread and
+    /// evaluate `f` within that context.
+    ///
+    /// The order in which the owners are entered is unspecified.
+    pub fn enter_all<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        self.owners.values_mut().enter_all(f)
+    }
+}
+
+fn downcast<S: Context + 'static>(erased: ErasedSnarc) -> S {
+    match erased.downcast_owner() {
+        Ok(owner) => owner,
+        Err(_) => unreachable!("owners are keyed by their type"),
+    }
+}
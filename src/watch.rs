@@ -0,0 +1,67 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
+type Latest<S> = Arc<Mutex<Option<Arc<S>>>>;
+
+/// Publishes snapshots that can be read from any thread at any time.
+///
+/// Wrapped by an owning pointer, e.g., `Snarc<(State, SnarcWatch<Summary>)>`,
+/// snapshots are [`publish`][SnarcWatch::publish()]ed by the owner or within
+/// its `enter` context. Readers holding a [`SnarcWatchRef`] need not enter at
+/// all, they always see the most recently published snapshot.
+pub struct SnarcWatch<S> {
+    latest: Latest<S>,
+}
+
+impl<S> SnarcWatch<S> {
+    /// Creates a new `SnarcWatch` without a snapshot.
+    pub fn new() -> Self {
+        Self {
+            latest: Default::default(),
+        }
+    }
+
+    /// Replaces the latest snapshot.
+    pub fn publish(&self, snapshot: S) {
+        let snapshot = Some(Arc::new(snapshot));
+        *self.latest.lock().unwrap_or_else(|p| p.into_inner()) = snapshot;
+    }
+
+    /// Creates a new reader of published snapshots.
+    pub fn watch(&self) -> SnarcWatchRef<S> {
+        SnarcWatchRef {
+            latest: Arc::clone(&self.latest),
+        }
+    }
+}
+
+impl<S> Default for SnarcWatch<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads the snapshots published by a [`SnarcWatch`].
+pub struct SnarcWatchRef<S> {
+    latest: Latest<S>,
+}
+
+impl<S> SnarcWatchRef<S> {
+    /// Returns the most recently published snapshot.
+    ///
+    /// Returns `None` if nothing was published yet.
+    pub fn latest(&self) -> Option<Arc<S>> {
+        self.latest
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .clone()
+    }
+}
+
+impl<S> Clone for SnarcWatchRef<S> {
+    fn clone(&self) -> Self {
+        Self {
+            latest: Arc::clone(&self.latest),
+        }
+    }
+}